@@ -0,0 +1,117 @@
+// Benchmarks for the git-backed code paths, run against a fixture datadog-agent checkout
+// so the numbers are comparable between machines and between implementations
+use criterion::{criterion_group, criterion_main, Criterion};
+use gix::ObjectId;
+use nightlies::{
+    nightly::{tags_to_nightlies_in, tags_to_nightlies_parallel_in, Nightly, Tag},
+    repo::{forget_lookups, get_first_nightlies_containing_changes_in, graph::CommitGraph},
+};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+const NUM_COMMITS: usize = 100;
+
+fn fixture() -> (common::FixtureHome, gix::Repository, Vec<Tag>) {
+    let home = common::FixtureHome::new(NUM_COMMITS);
+    let repo = gix::open(home.repo_path()).unwrap();
+    // One nightly every other commit
+    let tags = (0..NUM_COMMITS)
+        .step_by(2)
+        .flat_map(|n| home.tags_for_commit(n))
+        .collect();
+    (home, repo, tags)
+}
+
+fn head(repo: &gix::Repository, nightly: &Nightly) -> ObjectId {
+    repo.rev_parse_single(nightly.sha.as_str())
+        .unwrap()
+        .detach()
+}
+
+fn bench_enrichment(c: &mut Criterion) {
+    let (_home, repo, tags) = fixture();
+    // Lookups are forgotten between iterations, or every one after the first would be a
+    // memo hit
+    c.bench_function("enrichment/serial", |b| {
+        b.iter(|| {
+            forget_lookups();
+            tags_to_nightlies_in(&repo, &tags)
+        });
+    });
+    c.bench_function("enrichment/parallel", |b| {
+        b.iter(|| {
+            forget_lookups();
+            tags_to_nightlies_parallel_in(&repo, &tags)
+        });
+    });
+}
+
+fn bench_containment(c: &mut Criterion) {
+    let (home, repo, tags) = fixture();
+    let nightlies = tags_to_nightlies_in(&repo, &tags);
+    let oldest_change = repo
+        .rev_parse_single(home.commits[1].as_str())
+        .unwrap()
+        .detach();
+
+    // A walk of every nightly's history, as containment was first answered
+    c.bench_function("containment/rev_walk", |b| {
+        b.iter(|| {
+            nightlies
+                .iter()
+                .filter(|nightly| {
+                    repo.rev_walk(Some(head(&repo, nightly)))
+                        .all()
+                        .unwrap()
+                        .filter_map(Result::ok)
+                        .any(|rev| rev.id == oldest_change)
+                })
+                .count()
+        });
+    });
+    c.bench_function("containment/commit_graph", |b| {
+        b.iter(|| {
+            let heads: Vec<ObjectId> = nightlies.iter().map(|n| head(&repo, n)).collect();
+            let graph = CommitGraph::build(&repo, heads.iter().copied()).unwrap();
+            heads
+                .iter()
+                .filter(|head| graph.is_ancestor(&oldest_change, head))
+                .count()
+        });
+    });
+}
+
+fn bench_containment_batch(c: &mut Criterion) {
+    let (home, repo, tags) = fixture();
+    let nightlies = tags_to_nightlies_in(&repo, &tags);
+    let changes: Vec<String> = home.commits.clone();
+
+    c.bench_function("containment_batch/serial", |b| {
+        b.iter(|| {
+            let heads: Vec<ObjectId> = nightlies.iter().map(|n| head(&repo, n)).collect();
+            let graph = CommitGraph::build(&repo, heads.iter().copied()).unwrap();
+            changes
+                .iter()
+                .map(|change| {
+                    let change = repo.rev_parse_single(change.as_str()).unwrap().detach();
+                    heads
+                        .iter()
+                        .filter(|head| graph.is_ancestor(&change, head))
+                        .count()
+                })
+                .sum::<usize>()
+        });
+    });
+    c.bench_function("containment_batch/parallel", |b| {
+        b.iter(|| get_first_nightlies_containing_changes_in(&repo, &nightlies, &changes).unwrap());
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_enrichment,
+    bench_containment,
+    bench_containment_batch
+);
+criterion_main!(benches);
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::DateTime;
+use gix::{ObjectId, Repository};
+
+use crate::{
+    nightly::{tags_to_nightlies_in, tags_to_nightlies_parallel_in, Tag},
+    repo::{forget_lookups, graph::CommitGraph},
+    NightlyError,
+};
+
+// Timings of the git-backed code paths against a local checkout, for `nightlies --bench`
+// The criterion benchmarks in benches/ are more precise, this only needs the binary and a
+// checkout, e.g. a fixture one
+
+/// How many of the latest commits of 'origin/main' nightlies are made up for, one every
+/// other commit
+const NUM_COMMITS: usize = 200;
+
+/// How long a code path took on average over the runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub name: &'static str,
+    pub average: Duration,
+}
+
+/// Times containment (walking each nightly's history vs checking ancestry in a commit
+/// graph) and enrichment (serial vs parallel commit lookups) `runs` times each, with made
+/// up nightlies for every other one of the latest commits of 'origin/main'
+/// Lookups remembered by a run are forgotten before the next
+///
+/// # Errors
+/// - If 'origin/main' cannot be found or walked
+pub fn run(repo: &Repository, runs: u32) -> Result<Vec<Timing>> {
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?
+        .detach();
+    let mut commits = Vec::new();
+    for info in repo.rev_walk(Some(origin_main)).all()?.take(NUM_COMMITS) {
+        let commit = info?.object()?;
+        commits.push((commit.id, commit.time()?.seconds));
+    }
+    let Some(&(oldest, _)) = commits.last() else {
+        return Err(NightlyError::GenericError("'origin/main' has no commits".to_string()).into());
+    };
+    let heads: Vec<ObjectId> = commits.iter().step_by(2).map(|(id, _)| *id).collect();
+    let tags: Vec<Tag> = commits
+        .iter()
+        .step_by(2)
+        .filter_map(|(id, seconds)| {
+            let sha = id.to_hex_with_len(8).to_string();
+            Some(Tag {
+                name: format!("nightly-main-{sha}-py3"),
+                last_pushed: DateTime::from_timestamp(seconds + 4 * 3600, 0)?,
+                digest: format!("sha256:{id}"),
+                last_pulled: None,
+                pull_count: None,
+            })
+        })
+        .collect();
+
+    let rev_walk = || {
+        heads
+            .iter()
+            .filter(|head| {
+                repo.rev_walk(Some(**head))
+                    .all()
+                    .is_ok_and(|mut walk| walk.any(|info| info.is_ok_and(|i| i.id == oldest)))
+            })
+            .count()
+    };
+    let commit_graph = || {
+        CommitGraph::build(repo, heads.iter().copied()).map(|graph| {
+            heads
+                .iter()
+                .filter(|head| graph.is_ancestor(&oldest, head))
+                .count()
+        })
+    };
+    Ok(vec![
+        time("containment/rev_walk", runs, rev_walk),
+        time("containment/commit_graph", runs, commit_graph),
+        time("enrichment/serial", runs, || {
+            tags_to_nightlies_in(repo, &tags)
+        }),
+        time("enrichment/parallel", runs, || {
+            tags_to_nightlies_parallel_in(repo, &tags)
+        }),
+    ])
+}
+
+/// Times `runs` runs of `f`, starting each from no remembered lookups
+fn time<T>(name: &'static str, runs: u32, mut f: impl FnMut() -> T) -> Timing {
+    let mut total = Duration::ZERO;
+    for _ in 0..runs {
+        forget_lookups();
+        let start = Instant::now();
+        std::hint::black_box(f());
+        total += start.elapsed();
+    }
+    Timing {
+        name,
+        average: total / runs.max(1),
+    }
+}
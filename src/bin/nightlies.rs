@@ -1,19 +1,60 @@
+use std::collections::HashMap;
 use std::fmt::Write;
-use std::io::Write as IoWrite;
+use std::io::{IsTerminal, Write as IoWrite};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use nightlies::{
+    build::build_local,
+    business_day::WeekendFilter,
+    client::{Freshness, NightliesClient, SessionContext},
+    config::{load_config, save_repo_path},
+    crash_report::{install_panic_hook, last_crash_report},
+    exit_code,
+    feed::{feed_nightlies, render_feed},
+    format::{bar, day_heading, Glyphs},
+    ical::render_calendar,
+    image::{check_provenance, pipeline_id_from_labels, revision_from_labels},
+    investigation::{Investigation, TOP_SUSPECTS},
+    man::render_man_page,
     nightly::{
-        enrich_nightlies, fetch_docker_registry_tags, find_nightly_by_build_sha,
-        load_db_from_cache, print, query_range, save_db_to_cache,
+        completion_candidates, enrich_go_versions, enrich_incremental_commit_counts,
+        enrich_nightlies, estimate_next_nightly, print, print_formatted, print_labels, print_plain,
+        prune_nightlies, refresh_pull_metadata, Flavor, NextNightlyEstimate, Nightly,
+        NightlyTemplate, HTTP_LOG_TARGET,
     },
-    repo::get_first_nightly_containing_change,
+    quarantine::{merge_quarantine, quarantine_rejected, quarantine_tags, QuarantinedEntry},
+    query::{
+        adaptive_window_days, daily_trend, dedupe, filter::Filter, go_version_changes,
+        latest_as_of, link_predecessors, nth_latest, orphans, predecessor, pulled_since,
+        query_range, sort_oldest_first, tag_format_advisory, Clock, TrendMetric,
+    },
+    repo::{
+        branch_overlap, clone_agent_repo, fetch_agent_repo, get_first_nightlies_containing_changes,
+        get_first_nightly_containing_change, get_pending_commits, grep_commit_range, is_git_repo,
+        list_commit_range, release_branch_cuts, IgnorePaths,
+    },
+    resolve::{ResolverClass, Resolvers},
+    site::render_site,
+    snapshot::{load_snapshot, write_snapshot},
+    symbols::{download_symbols, render_symbol_url},
     NightlyError,
 };
 use tabwriter::TabWriter;
-use tracing::{info, level_filters::LevelFilter, warn};
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use tracing::{debug, info, level_filters::LevelFilter, warn, Event, Level, Subscriber};
+use tracing_subscriber::{
+    fmt::{self, writer::BoxMakeWriter},
+    layer::Context,
+    prelude::*,
+    EnvFilter, Layer,
+};
+
+/// Checks an --ignore-paths glob up front, so a typo is a usage error
+fn parse_ignore_glob(s: &str) -> Result<String, NightlyError> {
+    IgnorePaths::new(&[s])?;
+    Ok(s.to_string())
+}
 
 fn parse_datetime(s: &str) -> Result<DateTime<Utc>, NightlyError> {
     let mut err_str = String::new();
@@ -37,9 +78,243 @@ fn parse_datetime(s: &str) -> Result<DateTime<Utc>, NightlyError> {
                 .unwrap();
         }
     }
+    // RFC3339 without seconds or offset, e.g. 2024-06-12T09:00Z, taken as UTC
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s.trim_end_matches('Z'), format) {
+            return Ok(datetime.and_utc());
+        }
+    }
+    err_str.push_str("\n Error parsing date as YYYY-MM-DDTHH:MM[:SS]");
     Err(NightlyError::DateParseError(err_str))
 }
 
+fn format_next_nightly(estimate: &NextNightlyEstimate, now: DateTime<Utc>, args: &Args) -> String {
+    let until = estimate.expected_at - now;
+    let until = if until.num_hours() >= 1 {
+        format!("in ~{}h", until.num_hours())
+    } else if until.num_minutes() >= 1 {
+        format!("in ~{}m", until.num_minutes())
+    } else {
+        String::from("any minute now")
+    };
+    let std_dev = estimate.std_dev.num_minutes();
+    let std_dev = if args.plain {
+        format!("give or take {std_dev} minutes")
+    } else {
+        format!("{}{std_dev}m", args.glyphs().plus_minus)
+    };
+    format!(
+        "Next nightly expected around {} UTC ({until}, {std_dev})",
+        estimate.expected_at.format("%H:%M"),
+    )
+}
+
+/// The identifiers given to a flag, `-` reads them from stdin, one per line
+fn read_identifiers(value: &str) -> Result<Vec<String>, NightlyError> {
+    if value != "-" {
+        return Ok(vec![value.to_string()]);
+    }
+    let mut identifiers = Vec::new();
+    for line in std::io::stdin().lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            identifiers.push(line.to_string());
+        }
+    }
+    Ok(identifiers)
+}
+
+/// Prints a single nightly in the style chosen by the args
+/// `--format` lines are left to [`print_all_formatted`], they must not go through a
+/// tabwriter that would realign their tabs
+fn print_nightly<W>(writer: W, nightly: &Nightly, args: &Args)
+where
+    W: IoWrite,
+{
+    if args.plain {
+        print_plain(writer, nightly, args.all_tags, args.print_digest);
+    } else {
+        print(writer, nightly, args.all_tags, args.print_digest);
+    }
+}
+
+/// Prints a `--format` line per nightly straight to stdout, without headers or footers
+fn print_all_formatted(nightlies: &[&Nightly], template: &NightlyTemplate) {
+    let mut stdout = std::io::stdout().lock();
+    for nightly in nightlies {
+        print_formatted(&mut stdout, nightly, template);
+    }
+}
+
+/// Prints the given nightlies under a header for each calendar day (by `clock`)
+/// Release branch cuts and Go toolchain bumps are marked above the first nightly with them
+/// Any `--grep` matches for a nightly's sha are printed beneath it
+/// With `--plain` the headers are labeled lines and nothing is indented
+fn print_grouped_by_day<W>(
+    mut writer: W,
+    nightlies: &[&Nightly],
+    matches: &HashMap<String, Vec<String>>,
+    cuts: &HashMap<String, Vec<String>>,
+    go_bumps: &HashMap<String, (String, String)>,
+    clock: Clock,
+    args: &Args,
+) where
+    W: IoWrite,
+{
+    let mut current_day = None;
+    for nightly in nightlies {
+        let day = clock.timestamp(nightly).date_naive();
+        if current_day != Some(day) {
+            if args.plain {
+                writeln!(writer, "Day: {}", day_heading(day))
+            } else {
+                let rule = args.glyphs().rule;
+                writeln!(writer, "{rule} {} {rule}", day_heading(day))
+            }
+            .expect("Error writing to writer");
+            current_day = Some(day);
+        }
+        for branch in cuts.get(&nightly.sha).into_iter().flatten() {
+            if args.plain {
+                writeln!(writer, "Release branch cut: {branch}")
+            } else {
+                let rule = args.glyphs().rule;
+                writeln!(writer, "  {rule} release branch {branch} cut here {rule}")
+            }
+            .expect("Error writing to writer");
+        }
+        if let Some((from, to)) = go_bumps.get(&nightly.sha) {
+            if args.plain {
+                writeln!(writer, "Go toolchain changed: {from} to {to}")
+            } else {
+                let rule = args.glyphs().rule;
+                writeln!(writer, "  {rule} Go toolchain {from} -> {to} {rule}")
+            }
+            .expect("Error writing to writer");
+        }
+
+        if args.plain {
+            print_nightly(&mut writer, nightly, args);
+            for commit in matches.get(&nightly.sha).into_iter().flatten() {
+                writeln!(writer, "Matching commit: {commit}").expect("Error writing to writer");
+            }
+            continue;
+        }
+
+        let mut printed = Vec::new();
+        print(&mut printed, nightly, args.all_tags, args.print_digest);
+        for line in String::from_utf8_lossy(&printed).lines() {
+            writeln!(writer, "  {line}").expect("Error writing to writer");
+        }
+        for commit in matches.get(&nightly.sha).into_iter().flatten() {
+            writeln!(writer, "    Matching commit: {commit}").expect("Error writing to writer");
+        }
+    }
+}
+
+/// Finds the nightly given by any kind of identifier, see [`ResolverClass`], or `base` for
+/// the base nightly of the session context
+fn find_identified_nightly<'a>(
+    client: &NightliesClient,
+    nightlies: &'a [Nightly],
+    context: &SessionContext,
+    identifier: &str,
+) -> Result<&'a Nightly, NightlyError> {
+    let identifier = if identifier.trim() == "base" {
+        context.base.as_deref().ok_or_else(|| {
+            NightlyError::NightlyNotFound(String::from(
+                "'base', no base nightly is set, see --context-set-base",
+            ))
+        })?
+    } else {
+        identifier
+    };
+    Resolvers::accepting_in(ResolverClass::ALL, &client.repo_path)
+        .resolve(nightlies, identifier)
+        .nightly()
+        .ok_or_else(|| NightlyError::NightlyNotFound(format!("'{identifier}'")))
+}
+
+/// Release branches cut between the listed nightlies, keyed by the first nightly after
+/// each cut
+fn listed_release_cuts(
+    client: &NightliesClient,
+    listed: &[&Nightly],
+) -> HashMap<String, Vec<String>> {
+    // Without commit timestamps there is no checkout to look at the branches in
+    if listed.iter().all(|n| n.sha_timestamp.is_none()) {
+        return HashMap::new();
+    }
+    let Some(repo) = client.open_agent_repo() else {
+        return HashMap::new();
+    };
+    release_branch_cuts(&repo, listed).unwrap_or_else(|e| {
+        warn!("Could not look for release branch cuts: {}", e);
+        HashMap::new()
+    })
+}
+
+/// Keeps only the listed nightlies whose incremental commit range (since their
+/// predecessor) has a commit subject matching `pattern`, returning the matching commits
+/// per sha
+fn grep_listed(
+    client: &NightliesClient,
+    listed: &mut Vec<&Nightly>,
+    pattern: &str,
+    ignore: &IgnorePaths,
+) -> Result<HashMap<String, Vec<String>>, NightlyError> {
+    let repo = client.open_agent_repo().ok_or_else(|| {
+        NightlyError::GitError(String::from("--grep needs a datadog-agent checkout"))
+    })?;
+
+    let mut matches = HashMap::new();
+    for nightly in listed.iter() {
+        let Some(prev_sha) = &nightly.predecessor_sha else {
+            debug!("No predecessor for nightly {}, skipping grep", nightly.sha);
+            continue;
+        };
+        match grep_commit_range(&repo, prev_sha, &nightly.sha, pattern, ignore) {
+            Ok(commits) if !commits.is_empty() => {
+                matches.insert(nightly.sha.clone(), commits);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Could not search commits of nightly {}: {}", nightly.sha, e),
+        }
+    }
+
+    listed.retain(|n| matches.contains_key(&n.sha));
+    Ok(matches)
+}
+
+/// Prints the full image URIs of the latest and previous nightlies, ready to copy
+fn print_footer<W>(mut writer: W, nightlies: &[Nightly])
+where
+    W: IoWrite,
+{
+    for (label, n) in [("Latest", 0), ("Previous", 1)] {
+        let Some(tag) = nth_latest(nightlies, n).and_then(Nightly::first_valid_tag) else {
+            continue;
+        };
+        writeln!(writer, "{label}:\tdatadog/agent-dev:{}", tag.name)
+            .expect("Error writing to writer");
+    }
+    // Only worth showing once the registry told us about pulls
+    if nightlies
+        .iter()
+        .any(|n| n.first_valid_tag().is_some_and(|t| t.last_pulled.is_some()))
+    {
+        let since = Utc::now() - Duration::days(DEFAULT_WINDOW_DAYS);
+        writeln!(
+            writer,
+            "Pulled in the last {DEFAULT_WINDOW_DAYS} days:\t{} of {} nightlies",
+            pulled_since(nightlies, since),
+            nightlies.len()
+        )
+        .expect("Error writing to writer");
+    }
+}
+
 /// Lists the most recent agent-dev nightly images and a GH link for each
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -53,11 +328,15 @@ struct Args {
     print_digest: bool,
 
     /// If the given build_sha exists as a nightly, print the tag
+    /// `@0`, `@1`, ... select the latest, previous, ... nightly instead, and a tag name or
+    /// image digest selects the nightly it belongs to
+    /// `-` reads build shas from stdin, one per line
     #[arg(long)]
     build_sha: Option<String>,
 
     /// Given a sha that exists in the 'main' branch of the datadog-agent repo, print
     /// the first nightly that contains that sha
+    /// `-` reads shas from stdin, one per line
     /// EXPERIMENTAL - there are known bugs, use at your own risk
     #[arg(long)]
     agent_sha: Option<String>,
@@ -81,21 +360,665 @@ struct Args {
     /// End date for query (inclusive), format: YYYY-MM-DDTHH:MM:SS
     #[arg(short, long, value_parser = parse_datetime)]
     to_date: Option<DateTime<Utc>>,
+
+    /// Chart a metric of the nightlies per day, over --from-date..--to-date or else the
+    /// last 30 days
+    #[arg(long, default_value_t = false)]
+    trend: bool,
+
+    /// With --trend, what to chart
+    #[arg(long, value_enum, default_value_t = TrendMetric::Commits, requires = "trend")]
+    metric: TrendMetric,
+
+    /// Include nightlies whose tags have been deleted from the registry
+    #[arg(long, default_value_t = false)]
+    include_archived: bool,
+
+    /// Prune cached nightlies older than the configured retention and exit
+    #[arg(long, default_value_t = false)]
+    prune_cache: bool,
+
+    /// Summarize the commits on 'main' that are not yet in any nightly
+    #[arg(long, default_value_t = false)]
+    pending: bool,
+
+    /// Show when the next nightly is expected to be published
+    #[arg(long, default_value_t = false)]
+    next: bool,
+
+    /// Print completion candidates for the given word from the cache, for use by shell
+    /// completion scripts
+    #[arg(long, hide = true)]
+    complete: Option<String>,
+
+    /// Fetch the latest changes into the datadog-agent checkout before resolving shas
+    #[arg(long, default_value_t = false, conflicts_with = "cache_only")]
+    force_fetch: bool,
+
+    /// Sync with the registry and the datadog-agent remote before answering, even when
+    /// the config refreshes in the background
+    #[arg(long, default_value_t = false, conflicts_with = "cache_only")]
+    refresh: bool,
+
+    /// Answer from the cache and the local checkout without any network access
+    #[arg(long, default_value_t = false)]
+    cache_only: bool,
+
+    /// Which timestamp orders the listing
+    #[arg(long, value_enum, default_value_t = Clock::Commit)]
+    sort_by: Clock,
+
+    /// Only list nightlies that added a commit whose subject contains this text (ignoring case)
+    #[arg(long)]
+    grep: Option<String>,
+
+    /// Leave the files matching this gitignore-style glob, e.g. 'test/**' or '*.md', out
+    /// of --grep, --pending, --conflicts-with and the feed and site changelogs, along with
+    /// the commits only changing such files
+    /// Can be given several times, the last matching glob decides and '!' globs bring
+    /// paths back, e.g. '!test/fixtures/**'
+    #[arg(long, value_name = "GLOB", value_parser = parse_ignore_glob)]
+    ignore_paths: Vec<String>,
+
+    /// Check that the config and cache can be read, without any network access, and exit
+    /// Exits non-zero when something is wrong, for use by wrapper scripts and shell prompts
+    #[arg(long, default_value_t = false)]
+    ping: bool,
+
+    /// Print the most recent crash report and exit
+    #[arg(long, default_value_t = false)]
+    crash_report_last: bool,
+
+    /// Screen reader friendly output: labeled lines, no box drawing, symbols or color
+    #[arg(long, default_value_t = false)]
+    plain: bool,
+
+    /// Only print ASCII characters, for terminals and log systems that mangle anything else
+    /// Can also be set with `ascii = true` in the `[output]` section of the config
+    #[arg(long, default_value_t = false)]
+    ascii: bool,
+
+    /// List nightlies whose commit could not be found in the datadog-agent checkout
+    #[arg(long, default_value_t = false)]
+    orphans: bool,
+
+    /// List the registry entries that could not be parsed as nightly tags, e.g. after a
+    /// change to the upstream tag naming
+    #[arg(long, default_value_t = false)]
+    quarantine: bool,
+
+    /// Remember the given nightly as the base of the current investigation, other flags
+    /// taking an IDENTIFIER then accept `base` for it until --context-clear
+    #[arg(long, value_name = "IDENTIFIER")]
+    context_set_base: Option<String>,
+
+    /// Print the current session context
+    #[arg(long, default_value_t = false)]
+    context_show: bool,
+
+    /// Forget the session context
+    #[arg(long, default_value_t = false)]
+    context_clear: bool,
+
+    /// Log every registry request with its status and timing, credentials are redacted
+    #[arg(long, default_value_t = false)]
+    trace_http: bool,
+
+    /// Fail instead of degrading: any warning (no agent checkout, unreadable cache, ...)
+    /// stops the run with exit code 7
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Write the nightlies(1) man page into the given directory and exit
+    #[arg(long, value_name = "DIR")]
+    generate_man: Option<std::path::PathBuf>,
+
+    /// Write the config (secrets redacted), cache and latest crash report to the given
+    /// file for a bug report and exit
+    /// Logs are not captured, attach the output of the failing command run with
+    /// RUST_LOG=debug alongside
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<std::path::PathBuf>,
+
+    /// Restore the cache and config from a file written by --snapshot and exit
+    #[arg(long, value_name = "FILE", conflicts_with = "snapshot")]
+    load_snapshot: Option<std::path::PathBuf>,
+
+    /// Fetch and show the image labels (CI build links, versions, ...) of the nightlies
+    /// selected with --build-sha, the labels are cached
+    #[arg(long, default_value_t = false, conflicts_with = "format")]
+    labels: bool,
+
+    /// Check that the image of a nightly (build sha, @N, tag, digest,
+    /// date, #PR or git ref) was built from the commit its
+    /// tag names, by its commit label and by the digests its tags were first seen with
+    /// Exits with code 8 on a mismatch
+    #[arg(long, value_name = "IDENTIFIER", conflicts_with = "cache_only")]
+    provenance: Option<String>,
+
+    /// Hide weekend builds, deciding by the push or commit day of each nightly
+    #[arg(long, value_enum, default_value_t = WeekendFilter::Off)]
+    weekend_filter: WeekendFilter,
+
+    /// Check out the commit of a nightly (build sha, @N, tag, digest,
+    /// date, #PR or git ref) in a worktree and run the
+    /// build command from the config there
+    #[arg(long, value_name = "IDENTIFIER")]
+    build_local: Option<String>,
+
+    /// Print where the debug symbols of a nightly (build sha, @N, tag, digest,
+    /// date, #PR or git ref) are, from the symbol
+    /// URL templates in the config
+    #[arg(long, value_name = "IDENTIFIER")]
+    symbols: Option<String>,
+
+    /// With --symbols, download the symbols into the given directory
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "symbols",
+        conflicts_with = "cache_only"
+    )]
+    download_symbols: Option<std::path::PathBuf>,
+
+    /// List the files that both the given local branch and the commits between two
+    /// nightlies change, ie where merging the branch may conflict
+    #[arg(long, value_name = "BRANCH")]
+    conflicts_with: Option<String>,
+
+    /// With --conflicts-with, the nightlies to compare as FROM..TO, each identified like
+    /// for --build-local [default: @1..@0, or base..@0 when a base nightly is set]
+    #[arg(long, value_name = "FROM..TO", requires = "conflicts_with")]
+    range: Option<String>,
+
+    /// Print the nightly that was the latest at the given time, by push time
+    /// Add --include-archived to consider nightlies since deleted from the registry
+    #[arg(long, value_parser = parse_datetime, value_name = "DATETIME")]
+    as_of: Option<DateTime<Utc>>,
+
+    /// Only show images of this agent flavor
+    #[arg(long, value_enum, default_value_t = Flavor::Agent)]
+    flavor: Flavor,
+
+    /// Print each nightly on one line through a template, e.g. '{sha} {pushed} {gh_url}'
+    /// Placeholders: sha, tag, image, digest, pushed, committed, commits, pulls,
+    /// go_version, gh_url, archived; '{{' and '}}' are literal braces
+    /// '\t' and '\n' are a tab and a newline
+    #[arg(long, value_name = "TEMPLATE")]
+    format: Option<NightlyTemplate>,
+
+    /// Only show nightlies matching the expression, e.g.
+    /// 'pushed > 2024-07-01 && !weekend && commits >= 10'
+    /// Fields: pushed, committed, commits, pulls, sha, tag, weekend, archived, resolved
+    #[arg(long, value_name = "EXPR")]
+    filter: Option<Filter>,
+
+    /// Render a static website of the cached nightlies into the given directory: a
+    /// timeline, commit trends and a page per nightly with its changes
+    #[arg(long, value_name = "DIR")]
+    site: Option<std::path::PathBuf>,
+
+    /// Investigate a symptom first noticed at the given date (its start, in UTC) or RFC3339
+    /// instant: find the latest nightly by then and the one before it, rank the commits
+    /// between them and write it all up as Markdown with next steps
+    #[arg(long, value_name = "DATE", value_parser = parse_datetime)]
+    investigate: Option<DateTime<Utc>>,
+
+    /// With --investigate, commits whose subject mentions this rank first
+    #[arg(long, value_name = "KEYWORD", requires = "investigate")]
+    query: Option<String>,
+
+    /// With --investigate, where to write the Markdown document
+    /// [default: investigation-<bad nightly sha>.md]
+    #[arg(long, value_name = "FILE", requires = "investigate")]
+    investigation_doc: Option<std::path::PathBuf>,
+
+    /// Write an Atom feed of the newest nightlies and their changes to the given file
+    #[arg(long, value_name = "FILE")]
+    feed: Option<std::path::PathBuf>,
+
+    /// Write the publish times of recent nightlies, and when the next one is expected, as
+    /// calendar events to the given .ics file
+    #[arg(long, value_name = "FILE")]
+    ical: Option<std::path::PathBuf>,
+
+    /// With --ical, how many days of nightlies to export
+    #[arg(long, value_name = "DAYS", default_value_t = 60, requires = "ical")]
+    ical_days: i64,
+
+    /// Refresh the cache from the registry and exit silently, run in the background when
+    /// the config enables background refreshes
+    #[arg(long, default_value_t = false, hide = true)]
+    background_refresh: bool,
+
+    /// Time containment and enrichment against the datadog-agent checkout (or fixture
+    /// repo) at the given path and exit, to compare implementations
+    #[arg(long, value_name = "REPO", hide = true)]
+    bench: Option<std::path::PathBuf>,
+}
+
+impl Args {
+    fn glyphs(&self) -> Glyphs {
+        if self.ascii {
+            Glyphs::ASCII
+        } else {
+            Glyphs::UNICODE
+        }
+    }
+}
+
+/// Counts the warnings for `--strict`, which fails once the output is written if any
+/// was logged
+/// Everything that degrades instead of failing (no agent checkout, unreadable cache,
+/// unparseable tags, ...) warns, so this covers them all in one place
+/// It has its own filter, so warnings are counted even when `RUST_LOG` hides them
+struct FailOnWarning;
+
+/// Warnings logged while `--strict` was given
+static STRICT_WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+impl<S: Subscriber> Layer<S> for FailOnWarning {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() == Level::WARN {
+            STRICT_WARNINGS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Starts `nightlies --background-refresh` as a detached process, so the cache gets
+/// refreshed without the current command waiting on the registry
+fn spawn_background_refresh(args: &Args) -> std::io::Result<()> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .arg("--background-refresh")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    if let Some(num_pages) = args.num_registry_pages {
+        command.args(["--num-registry-pages", &num_pages.to_string()]);
+    }
+    command.spawn()?;
+    debug!("Refreshing the cache in the background");
+    Ok(())
+}
+
+/// The commits (short sha and subject) each nightly adds over its predecessor, for the
+/// nightlies whose commits can be listed from the datadog-agent checkout
+fn changes_over_predecessors<'a>(
+    client: &NightliesClient,
+    nightlies: impl IntoIterator<Item = &'a Nightly>,
+    ignore: &IgnorePaths,
+) -> HashMap<String, Vec<String>> {
+    let mut changes = HashMap::new();
+    let Some(repo) = client.open_agent_repo() else {
+        warn!("No datadog-agent checkout, changes are only linked to");
+        return changes;
+    };
+    for nightly in nightlies {
+        let Some(prev) = &nightly.predecessor_sha else {
+            continue;
+        };
+        match list_commit_range(&repo, prev, &nightly.sha, ignore) {
+            Ok(commits) => {
+                changes.insert(nightly.sha.clone(), commits);
+            }
+            Err(e) => debug!("Could not list the commits of {}: {}", nightly.sha, e),
+        }
+    }
+    changes
+}
+
+/// Asks a question on the terminal and returns the trimmed answer, None once stdin is
+/// closed
+fn prompt(question: &str) -> std::io::Result<Option<String>> {
+    eprint!("{question}");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        eprintln!();
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
+
+/// Makes sure there is a datadog-agent checkout at `path` for the commands that need one
+/// At a terminal it offers to clone one or to use an existing one, otherwise it fails
+/// with a git error explaining how to set one up
+/// Returns the path of the checkout to use from then on
+fn bootstrap_agent_repo(path: &std::path::Path) -> anyhow::Result<std::path::PathBuf> {
+    if is_git_repo(path) {
+        return Ok(path.to_path_buf());
+    }
+    let help = format!(
+        "No datadog-agent checkout at {}, clone one there or set the path of an existing \
+         one in the [repo] section of the config",
+        path.display()
+    );
+    if !std::io::stdin().is_terminal() {
+        return Err(NightlyError::GitError(help).into());
+    }
+
+    eprintln!(
+        "No datadog-agent checkout at {}\n  \
+         1) Clone github.com/DataDog/datadog-agent there\n  \
+         2) Use an existing checkout\n  \
+         3) Continue without one",
+        path.display()
+    );
+    // Running out of input counts as continuing without a checkout
+    loop {
+        match prompt("Choice [1-3]: ")?.as_deref() {
+            Some("1") => {
+                clone_agent_repo(path).map_err(git_error)?;
+                return Ok(path.to_path_buf());
+            }
+            Some("2") => {
+                let Some(checkout) = prompt("Path of the checkout: ")? else {
+                    return Ok(path.to_path_buf());
+                };
+                let checkout = std::path::PathBuf::from(checkout);
+                if !is_git_repo(&checkout) {
+                    eprintln!("{} is not a git repository", checkout.display());
+                    continue;
+                }
+                let config = save_repo_path(&checkout)?;
+                eprintln!("Saved the checkout's path in {}", config.display());
+                return Ok(checkout);
+            }
+            Some("3") | None => return Ok(path.to_path_buf()),
+            Some(_) => {}
+        }
+    }
+}
+
+/// Adds the registry entries that aren't nightly tags to the quarantine, warning about
+/// the ones that weren't quarantined before
+fn record_quarantine(client: &NightliesClient, found: Vec<QuarantinedEntry>) {
+    if found.is_empty() {
+        return;
+    }
+    let mut quarantine = match client.load_quarantine() {
+        Ok(quarantine) => quarantine,
+        Err(e) => {
+            warn!("Error reading the quarantined registry entries: {}", e);
+            return;
+        }
+    };
+    let num_new = merge_quarantine(&mut quarantine, found);
+    if num_new > 0 {
+        warn!("Quarantined {num_new} registry entries that aren't nightly tags, see --quarantine");
+    }
+    if let Err(e) = client.save_quarantine(&quarantine) {
+        warn!("Error saving the quarantined registry entries: {}", e);
+    }
+}
+
+/// Checks the local state that every command depends on, returning a one line summary
+fn ping(client: &NightliesClient) -> Result<String, NightlyError> {
+    load_config()?;
+    let nightlies = client.load_cache()?;
+    Ok(format!(
+        "ok: nightlies {}, {} cached nightlies",
+        env!("CARGO_PKG_VERSION"),
+        nightlies.len()
+    ))
+}
+
+/// The default listing covers this many days...
+const DEFAULT_WINDOW_DAYS: i64 = 7;
+/// ...and is widened up to this many days when it would be empty
+const MAX_WINDOW_DAYS: i64 = 56;
+
+/// --trend covers this many days unless --from-date is given
+const TREND_DAYS: i64 = 30;
+/// Length of the longest --trend bar
+const TREND_WIDTH: usize = 40;
+
+/// Exit codes and their meaning, as documented in the man page and README
+const EXIT_CODES: &[(i32, &str)] = &[
+    (exit_code::OK, "Success"),
+    (exit_code::FAILURE, "Any other failure"),
+    (exit_code::USAGE, "Invalid arguments or configuration"),
+    (
+        exit_code::NOT_FOUND,
+        "A requested nightly or commit doesn't exist",
+    ),
+    (
+        exit_code::NETWORK,
+        "The docker registry could not be reached",
+    ),
+    (
+        exit_code::GIT,
+        "The datadog-agent checkout could not be used",
+    ),
+    (exit_code::CACHE, "The cache could not be read or written"),
+    (exit_code::DEGRADED, "--strict ran into a warning"),
+    (
+        exit_code::PROVENANCE,
+        "--provenance found an image that may not match its tag",
+    ),
+    (
+        exit_code::OUTPUT,
+        "A file asked for (--site, --feed, ...) could not be written",
+    ),
+];
+
+/// Completion candidates must be produced within this budget to keep shells responsive
+const COMPLETION_BUDGET: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// How many times --bench runs each code path, the average is printed
+const BENCH_RUNS: u32 = 5;
+
+/// Writes a file asked for on the command line, creating its directory
+fn write_output(path: &std::path::Path, contents: impl AsRef<[u8]>) -> Result<(), NightlyError> {
+    let output_error = |e| NightlyError::OutputError(path.display().to_string(), e);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(output_error)?;
+    }
+    std::fs::write(path, contents).map_err(output_error)
+}
+
+/// Errors from the agent checkout are git errors, unless they already are more specific
+fn git_error(e: anyhow::Error) -> anyhow::Error {
+    if e.is::<NightlyError>() {
+        e
+    } else {
+        NightlyError::GitError(format!("{e:#}")).into()
+    }
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let env_filter = EnvFilter::builder()
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {e:?}");
+        let code = e
+            .downcast_ref::<NightlyError>()
+            .map_or(exit_code::FAILURE, NightlyError::exit_code);
+        std::process::exit(code);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    run_command().await?;
+    match STRICT_WARNINGS.load(Ordering::Relaxed) {
+        0 => Ok(()),
+        warnings => Err(NightlyError::Degraded(warnings).into()),
+    }
+}
+
+async fn run_command() -> anyhow::Result<()> {
+    install_panic_hook();
+    let mut args = Args::parse();
+    let mut client = NightliesClient {
+        freshness: if args.refresh {
+            Freshness::Refresh
+        } else if args.cache_only {
+            Freshness::CacheOnly
+        } else {
+            Freshness::Hybrid
+        },
+        ..NightliesClient::from_env()
+    };
+
+    if let Some(dir) = &args.generate_man {
+        let page = render_man_page(Args::command(), EXIT_CODES);
+        let path = dir.join("nightlies.1");
+        write_output(&path, page)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(path) = &args.bench {
+        let repo = gix::open(path).map_err(|e| git_error(e.into()))?;
+        for timing in nightlies::bench::run(&repo, BENCH_RUNS).map_err(git_error)? {
+            println!("{}: {:?}", timing.name, timing.average);
+        }
+        return Ok(());
+    }
+
+    if args.crash_report_last {
+        match last_crash_report()? {
+            Some((path, report)) => print!("{}:\n{report}", path.display()),
+            None => println!("No crash reports found"),
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &args.snapshot {
+        let snapshot = write_snapshot(&client, path)?;
+        println!(
+            "Wrote {} nightlies{}{} to {}",
+            snapshot.nightlies.len(),
+            if snapshot.config.is_some() {
+                ", the config"
+            } else {
+                ""
+            },
+            if snapshot.crash_report.is_some() {
+                ", the latest crash report"
+            } else {
+                ""
+            },
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if let Some(path) = &args.load_snapshot {
+        let snapshot = load_snapshot(&client, path)?;
+        println!(
+            "Restored {} nightlies{} from a snapshot taken by nightlies {} at {}",
+            snapshot.nightlies.len(),
+            if snapshot.config.is_some() {
+                " and the config"
+            } else {
+                ""
+            },
+            snapshot.version,
+            snapshot.created.to_rfc3339()
+        );
+        return Ok(());
+    }
+
+    // Completion runs before logging is set up so nothing but candidates reaches stdout
+    if let Some(word) = &args.complete {
+        let deadline = std::time::Instant::now() + COMPLETION_BUDGET;
+        let nightlies = client.load_cache().unwrap_or_default();
+        for candidate in completion_candidates(&nightlies, word, deadline) {
+            println!("{candidate}");
+        }
+        return Ok(());
+    }
+
+    if args.context_show {
+        let context = client.load_context()?;
+        match (&context.base, context.set_at) {
+            (Some(base), Some(set_at)) => {
+                println!("Base nightly: {base} (set at {})", set_at.to_rfc3339());
+            }
+            (Some(base), None) => println!("Base nightly: {base}"),
+            (None, _) => println!("No base nightly is set, see --context-set-base"),
+        }
+        return Ok(());
+    }
+
+    if args.context_clear {
+        client.clear_context()?;
+        println!("Cleared the session context");
+        return Ok(());
+    }
+
+    if args.ping {
+        match ping(&client) {
+            Ok(summary) => println!("{summary}"),
+            Err(e) => {
+                eprintln!("unhealthy: {e}");
+                std::process::exit(e.exit_code());
+            }
+        }
+        return Ok(());
+    }
+
+    let mut env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .from_env_lossy();
+    if args.trace_http {
+        env_filter = env_filter.add_directive(format!("{HTTP_LOG_TARGET}=trace").parse()?);
+    }
 
+    // --format output is read by scripts, so logs stay out of it
+    let writer = if args.format.is_some() {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
     tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(env_filter)
+        .with(
+            fmt::layer()
+                .with_ansi(!args.plain)
+                .with_writer(writer)
+                .with_filter(env_filter),
+        )
+        .with(
+            args.strict
+                .then(|| FailOnWarning.with_filter(LevelFilter::WARN)),
+        )
         .init();
 
     info!("Hello, world!");
-    let args = Args::parse();
+    let config = load_config()?;
+    args.ascii |= config.output.ascii;
+
+    // Pruning only touches the cache, nothing is fetched or looked up first
+    if args.prune_cache {
+        let mut nightlies = client.load_cache()?;
+        let num_pruned =
+            prune_nightlies(&mut nightlies, config.retention.nightlies_days, Utc::now());
+        client.save_cache(&nightlies)?;
+        println!(
+            "Pruned {num_pruned} nightlies older than {} days",
+            config.retention.nightlies_days
+        );
+        return Ok(());
+    }
+
+    let needs_repo = args.agent_sha.is_some()
+        || args.pending
+        || args.grep.is_some()
+        || args.conflicts_with.is_some()
+        || args.build_local.is_some()
+        || args.investigate.is_some()
+        || args.force_fetch;
+    if let Some(path) = &config.repo.path {
+        client.repo_path.clone_from(path);
+    }
+    if needs_repo {
+        client.repo_path = bootstrap_agent_repo(&client.repo_path)?;
+    }
+
+    if args.force_fetch || client.freshness == Freshness::Refresh {
+        fetch_agent_repo(&client.repo_path).map_err(git_error)?;
+    }
 
     // TODO the way this should work is that we query pages until we are able to
     // find the target_sha and/or find results from the 'from_date'
@@ -103,41 +1026,151 @@ async fn main() -> anyhow::Result<()> {
     // If you don't see the dates you're looking for, try increasing the number of pages
     let num_pages = args.num_registry_pages.unwrap_or(1);
 
+    // With background refreshes the registry is left to a separate process, and the cache
+    // is answered from as is
+    let from_cache = config.refresh.background
+        && client.freshness == Freshness::Hybrid
+        && !args.background_refresh;
+    if from_cache {
+        let interval = Duration::minutes(i64::from(config.refresh.interval_minutes));
+        match client.claim_background_refresh(interval) {
+            Ok(true) => {
+                if let Err(e) = spawn_background_refresh(&args) {
+                    warn!("Error starting a background refresh: {}", e);
+                }
+            }
+            Ok(false) => debug!("The cache was refreshed recently, not refreshing it again"),
+            Err(e) => warn!("Error checking when the cache was last refreshed: {}", e),
+        }
+    }
+
     // Fetch tags from docker registry and load from cache file in parallel
+    let fetch_client = client.clone();
+    let cache_client = client.clone();
     let (live_tags, file_nightlies) = tokio::join!(
         tokio::spawn(async move {
-            let tags = fetch_docker_registry_tags(num_pages).await?;
-            Ok::<_, crate::NightlyError>(tags)
+            if from_cache {
+                return Ok((Vec::new(), Vec::new()));
+            }
+            let fetched = fetch_client.fetch_tags_quarantining(num_pages).await?;
+            Ok::<_, crate::NightlyError>(fetched)
         }),
         tokio::spawn(async move {
-            let nightlies = load_db_from_cache()?;
+            let nightlies = cache_client.load_cache()?;
             Ok::<_, crate::NightlyError>(nightlies)
         })
     );
-    let live_tags = live_tags??;
+    let (live_tags, mut quarantined) = live_tags??;
+    if let Some(advisory) = tag_format_advisory(&live_tags) {
+        warn!("{advisory}");
+    }
     let mut nightlies = file_nightlies??;
+    dedupe(&mut nightlies);
 
-    enrich_nightlies(&live_tags, &mut nightlies)?;
+    let rejected = enrich_nightlies(&client.repo_path, &live_tags, &mut nightlies);
+    quarantined.extend(quarantine_tags(&live_tags, Utc::now()));
+    quarantined.extend(quarantine_rejected(&rejected, Utc::now()));
+    record_quarantine(&client, quarantined);
+    refresh_pull_metadata(&live_tags, &mut nightlies);
+    link_predecessors(&mut nightlies);
+    enrich_incremental_commit_counts(&client.repo_path, &mut nightlies);
+    enrich_go_versions(&client.repo_path, &mut nightlies);
+    if let Err(e) = client
+        .archive_deleted_nightlies(&live_tags, &mut nightlies)
+        .await
+    {
+        warn!("Error checking for deleted nightlies: {}", e);
+    }
 
-    let to_save = nightlies.clone();
-    tokio::spawn(async move {
-        match save_db_to_cache(&to_save) {
-            Ok(_) => {}
+    prune_nightlies(&mut nightlies, config.retention.nightlies_days, Utc::now());
+
+    // Saved before answering, a task still saving when the command returns would be
+    // dropped along with the runtime
+    // The background refresh owns the cache, saving here could overwrite its result
+    if !from_cache {
+        match client.save_cache(&nightlies) {
+            Ok(()) if args.background_refresh => {
+                if let Err(e) = client.record_refresh() {
+                    warn!("Error recording the background refresh: {}", e);
+                }
+            }
+            Ok(()) => {}
             Err(e) => warn!("Error saving db: {}", e),
         }
+    }
+    if args.background_refresh {
+        return Ok(());
+    }
+
+    // Archived nightlies can't be pulled, so keep them out of the output by default
+    if !args.include_archived {
+        nightlies.retain(|n| !n.archived);
+    }
+    nightlies.retain(|n| {
+        !args.weekend_filter.excludes(n) && !config.exclude.iter().any(|w| w.excludes(n))
+    });
+    nightlies = nightlies
+        .iter()
+        .filter_map(|n| n.only_flavor(args.flavor))
+        .collect();
+    if let Some(filter) = &args.filter {
+        nightlies.retain(|n| filter.matches(n));
+    }
+
+    let ignore = IgnorePaths::new(&args.ignore_paths)?;
+    // A damaged context only loses the base, --context-show reports the error instead
+    let mut context = client.load_context().unwrap_or_else(|e| {
+        warn!("Ignoring the session context, it could not be read: {}", e);
+        SessionContext::default()
     });
+    if let Some(identifier) = &args.context_set_base {
+        let base = find_identified_nightly(&client, &nightlies, &context, identifier)?;
+        context.base = Some(base.sha.clone());
+        context.set_at = Some(Utc::now());
+        client.save_context(&context)?;
+        println!("Base nightly set to {}", base.sha);
+        return Ok(());
+    }
 
     let mut tw = TabWriter::new(vec![]);
+    let mut not_found: Vec<String> = Vec::new();
+    if args.quarantine {
+        let quarantine = client.load_quarantine()?;
+        if quarantine.is_empty() {
+            writeln!(&mut tw, "No registry entries were quarantined")
+                .expect("Error writing to tabwriter");
+        } else {
+            writeln!(&mut tw, "Entry\tReason\tFirst seen\tLast seen")
+                .expect("Error writing to tabwriter");
+        }
+        for entry in &quarantine {
+            let name = entry
+                .name()
+                .map_or_else(|| entry.entry.to_string(), String::from);
+            writeln!(
+                &mut tw,
+                "{name}\t{}\t{}\t{}",
+                entry.reason,
+                entry.first_seen.to_rfc3339(),
+                entry.last_seen.to_rfc3339()
+            )
+            .expect("Error writing to tabwriter");
+        }
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        return Ok(());
+    }
+
     if args.latest_only {
-        let latest = nightlies.iter().max_by_key(|n| n.sha_timestamp);
-        if let Some(latest) = latest {
+        if let Some(latest) = nth_latest(&nightlies, 0) {
             writeln!(
                 &mut tw,
                 "{}",
                 latest
                     .py3
                     .as_ref()
-                    .expect("No py3 image found for latest nightly, something is wrong...")
+                    .or_else(|| latest.first_valid_tag())
+                    .expect("No image found for latest nightly, something is wrong...")
                     .name
             )
             .expect("Error writing to tabwriter");
@@ -147,18 +1180,15 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
     if args.prev_latest_only {
-        // get the 2nd most recent by sha_timestamp
-        let mut nightlies = nightlies.clone();
-        nightlies.sort_by(|a, b| a.sha_timestamp.cmp(&b.sha_timestamp));
-        let prev_latest = nightlies.get(nightlies.len() - 2);
-        if let Some(prev_latest) = prev_latest {
+        if let Some(prev_latest) = nth_latest(&nightlies, 1) {
             writeln!(
                 &mut tw,
                 "{}",
                 prev_latest
                     .py3
                     .as_ref()
-                    .expect("No py3 image found for 2nd latest nightly, something is wrong...")
+                    .or_else(|| prev_latest.first_valid_tag())
+                    .expect("No image found for 2nd latest nightly, something is wrong...")
                     .name
             )
             .expect("Error writing to tabwriter");
@@ -168,6 +1198,333 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
+    if let Some(instant) = args.as_of {
+        let nightly = latest_as_of(&nightlies, instant)
+            .ok_or_else(|| NightlyError::NightlyNotFound(format!("'{}'", instant.to_rfc3339())))?;
+        if let Some(template) = &args.format {
+            print_all_formatted(&[nightly], template);
+            return Ok(());
+        }
+        writeln!(
+            &mut tw,
+            "The latest nightly at {} was:",
+            instant.to_rfc3339()
+        )
+        .expect("Error writing to tabwriter");
+        print_nightly(&mut tw, nightly, &args);
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        return Ok(());
+    }
+
+    if let Some(path) = &args.ical {
+        let now = Utc::now();
+        let calendar = render_calendar(
+            &nightlies,
+            estimate_next_nightly(&nightlies, now),
+            now - Duration::days(args.ical_days),
+            now,
+        );
+        write_output(path, calendar)?;
+        println!("Wrote the calendar to {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(since) = &args.investigate {
+        let bad = latest_as_of(&nightlies, *since)
+            .ok_or_else(|| NightlyError::NightlyNotFound(format!("by {}", since.to_rfc3339())))?;
+        let good = predecessor(&nightlies, bad).ok_or_else(|| {
+            NightlyError::NightlyNotFound(format!("the nightly before {}", bad.sha))
+        })?;
+        let repo = client.open_agent_repo().ok_or_else(|| {
+            NightlyError::GitError(String::from("--investigate needs a datadog-agent checkout"))
+        })?;
+        let commits = list_commit_range(&repo, &good.sha, &bad.sha, &ignore).map_err(git_error)?;
+        let investigation = Investigation {
+            symptom_since: since.to_rfc3339(),
+            good,
+            bad,
+            commits,
+            query: args.query.clone(),
+        };
+
+        let path = args
+            .investigation_doc
+            .clone()
+            .unwrap_or_else(|| format!("investigation-{}.md", bad.sha).into());
+        write_output(&path, investigation.render_markdown())?;
+        println!("Last good nightly: {}", good.sha);
+        println!("First bad nightly: {}", bad.sha);
+        let suspects = investigation.suspects();
+        println!("{} commits in between, likeliest suspects:", suspects.len());
+        for suspect in suspects.iter().take(TOP_SUSPECTS) {
+            println!("  {} {}", suspect.sha, suspect.subject);
+        }
+        println!("Wrote the investigation to {}", path.display());
+        println!("Next steps:");
+        for step in investigation.next_steps() {
+            println!("  {step}");
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &args.feed {
+        // Only the nightlies with an entry need their commits listed
+        let entries = feed_nightlies(&nightlies);
+        let changes = changes_over_predecessors(&client, entries, &ignore);
+        write_output(path, render_feed(&nightlies, &changes, Utc::now()))?;
+        println!("Wrote the feed to {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(dir) = &args.site {
+        let changes = changes_over_predecessors(&client, &nightlies, &ignore);
+        let files = render_site(&nightlies, &changes);
+        for file in &files {
+            write_output(&dir.join(&file.path), &file.html)?;
+        }
+        println!("Wrote {} pages to {}", files.len(), dir.display());
+        return Ok(());
+    }
+
+    if args.orphans {
+        let orphans = orphans(&nightlies);
+        if orphans.is_empty() {
+            writeln!(&mut tw, "Every nightly's commit was found")
+                .expect("Error writing to tabwriter");
+        } else {
+            writeln!(&mut tw, "Tag\tPushed\tLookup attempts").expect("Error writing to tabwriter");
+        }
+        for orphan in orphans {
+            let tag = orphan
+                .first_valid_tag()
+                .expect("Nightlies have at least one tag");
+            writeln!(
+                &mut tw,
+                "datadog/agent-dev:{}\t{}\t{}",
+                tag.name,
+                orphan.estimated_last_pushed.to_rfc3339(),
+                orphan.resolve_attempts
+            )
+            .expect("Error writing to tabwriter");
+        }
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        return Ok(());
+    }
+
+    if args.trend {
+        let to = args.to_date.unwrap_or_else(Utc::now).date_naive();
+        let from = args
+            .from_date
+            .map_or(to - Duration::days(TREND_DAYS - 1), |from| {
+                from.date_naive()
+            });
+        let days = daily_trend(&nightlies, args.metric, from, to);
+        let max = days
+            .iter()
+            .filter_map(|(_, value)| *value)
+            .max()
+            .unwrap_or(0);
+        for (day, value) in days {
+            let line = match (value, args.plain) {
+                (Some(value), true) => format!("{}: {value} {}", day_heading(day), args.metric),
+                (None, true) => format!("{}: no nightly", day_heading(day)),
+                (value, false) => format!(
+                    "{}\t{}\t{}",
+                    day.format("%Y-%m-%d %a"),
+                    value.map_or_else(|| String::from("-"), |v| v.to_string()),
+                    bar(value.unwrap_or(0), max, TREND_WIDTH, args.glyphs())
+                ),
+            };
+            writeln!(&mut tw, "{line}").expect("Error writing to tabwriter");
+        }
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        return Ok(());
+    }
+
+    if args.next {
+        match estimate_next_nightly(&nightlies, Utc::now()) {
+            Some(estimate) => {
+                println!("{}", format_next_nightly(&estimate, Utc::now(), &args))
+            }
+            None => warn!("Not enough nightlies to estimate the next publish time"),
+        }
+        return Ok(());
+    }
+
+    if let Some(identifier) = &args.build_local {
+        let nightly = find_identified_nightly(&client, &nightlies, &context, identifier)?;
+        let worktree = build_local(&client.repo_path, nightly, &config.build).map_err(git_error)?;
+        println!("Built nightly {} in {}", nightly.sha, worktree.display());
+        return Ok(());
+    }
+
+    if let Some(identifier) = &args.provenance {
+        let nightly = find_identified_nightly(&client, &nightlies, &context, identifier)?;
+        let tag = nightly
+            .first_valid_tag()
+            .expect("Nightlies have at least one tag");
+        let mut labels = nightly.labels.clone();
+        if labels.is_none() {
+            match client.fetch_image_labels(&tag.name).await {
+                Ok(fetched) => labels = Some(fetched),
+                Err(e) => warn!("Could not fetch the labels of {}: {}", tag.name, e),
+            }
+        }
+        let mut live_tags = Vec::new();
+        for tag in nightly.tags() {
+            match client.fetch_tag(&tag.name).await? {
+                Some(live) => live_tags.push(live),
+                None => warn!("Tag {} is no longer in the registry", tag.name),
+            }
+        }
+
+        let issues = check_provenance(nightly, labels.as_ref(), &live_tags);
+        writeln!(&mut tw, "Nightly:\tdatadog/agent-dev:{}", tag.name)
+            .expect("Error writing to tabwriter");
+        let revision = labels
+            .as_ref()
+            .and_then(revision_from_labels)
+            .map_or("unknown", |(_, revision)| revision);
+        writeln!(&mut tw, "Commit label:\t{revision}").expect("Error writing to tabwriter");
+        writeln!(
+            &mut tw,
+            "Digests checked:\t{} of {} tags",
+            live_tags.len(),
+            nightly.tags().count()
+        )
+        .expect("Error writing to tabwriter");
+        if issues.is_empty() {
+            writeln!(&mut tw, "The image matches commit {}", nightly.sha)
+                .expect("Error writing to tabwriter");
+        }
+        for issue in &issues {
+            writeln!(&mut tw, "Mismatch: {issue}").expect("Error writing to tabwriter");
+        }
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        if !issues.is_empty() {
+            return Err(NightlyError::ProvenanceMismatch(nightly.sha.clone()).into());
+        }
+        return Ok(());
+    }
+
+    if let Some(identifier) = &args.symbols {
+        let nightly = find_identified_nightly(&client, &nightlies, &context, identifier)?;
+        if config.symbols.urls.is_empty() {
+            return Err(NightlyError::GenericError(String::from(
+                "No symbol URLs are configured, add them as [symbols] urls in the config",
+            ))
+            .into());
+        }
+        let mut labels = nightly.labels.clone();
+        if labels.is_none() && config.symbols.urls.iter().any(|u| u.contains("{pipeline}")) {
+            let tag = nightly
+                .first_valid_tag()
+                .expect("Nightlies have at least one tag");
+            match client.fetch_image_labels(&tag.name).await {
+                Ok(fetched) => labels = Some(fetched),
+                Err(e) => warn!("Could not fetch the labels of {}: {}", tag.name, e),
+            }
+        }
+        let pipeline_id = labels.as_ref().and_then(pipeline_id_from_labels);
+        for template in &config.symbols.urls {
+            let url = render_symbol_url(template, nightly, pipeline_id)?;
+            match &args.download_symbols {
+                Some(dir) => {
+                    let path = download_symbols(&url, dir).await?;
+                    println!("Downloaded {url} to {}", path.display());
+                }
+                None => println!("{url}"),
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(branch) = &args.conflicts_with {
+        let default_range = if context.base.is_some() {
+            "base..@0"
+        } else {
+            "@1..@0"
+        };
+        let range = args.range.as_deref().unwrap_or(default_range);
+        let (from, to) = range.split_once("..").ok_or_else(|| {
+            NightlyError::GenericError(format!("'{range}' is not a FROM..TO range"))
+        })?;
+        let from = find_identified_nightly(&client, &nightlies, &context, from)?;
+        let to = find_identified_nightly(&client, &nightlies, &context, to)?;
+        let repo = client.open_agent_repo().ok_or_else(|| {
+            NightlyError::GitError(String::from(
+                "--conflicts-with needs a datadog-agent checkout",
+            ))
+        })?;
+        let overlap =
+            branch_overlap(&repo, &from.sha, &to.sha, branch, &ignore).map_err(git_error)?;
+
+        if overlap.files.is_empty() {
+            writeln!(
+                &mut tw,
+                "No file changed by {branch} is changed between nightlies {} and {}",
+                from.sha, to.sha
+            )
+            .expect("Error writing to tabwriter");
+        } else {
+            writeln!(
+                &mut tw,
+                "Files changed by {branch} and between nightlies {} and {}:",
+                from.sha, to.sha
+            )
+            .expect("Error writing to tabwriter");
+            for file in &overlap.files {
+                writeln!(&mut tw, "  {file}").expect("Error writing to tabwriter");
+            }
+            writeln!(&mut tw, "Commits changing them:").expect("Error writing to tabwriter");
+            for (commit, files) in &overlap.commits {
+                writeln!(&mut tw, "  {commit}\t{}", files.join(", "))
+                    .expect("Error writing to tabwriter");
+            }
+        }
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        return Ok(());
+    }
+
+    if args.pending {
+        let latest = nth_latest(&nightlies, 0)
+            .ok_or_else(|| NightlyError::NightlyNotFound(String::from("'@0'")))?;
+        let pending =
+            get_pending_commits(&client.repo_path, &latest.sha, &ignore).map_err(git_error)?;
+
+        writeln!(
+            &mut tw,
+            "Commits on main not yet in a nightly (latest nightly: {}):\t{}",
+            latest.sha, pending.num_commits
+        )
+        .expect("Error writing to tabwriter");
+        let authors: Vec<String> = pending
+            .authors
+            .iter()
+            .take(5)
+            .map(|(author, count)| format!("{author} ({count})"))
+            .collect();
+        writeln!(&mut tw, "Top authors:\t{}", authors.join(", "))
+            .expect("Error writing to tabwriter");
+        let paths: Vec<String> = pending
+            .paths
+            .iter()
+            .take(5)
+            .map(|(path, count)| format!("{path} ({count})"))
+            .collect();
+        writeln!(&mut tw, "Notable paths:\t{}", paths.join(", "))
+            .expect("Error writing to tabwriter");
+
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        print!("{}", written);
+        return Ok(());
+    }
+
     // If dates are specified, lets look at that range
     if let Some(from) = args.from_date {
         info!(
@@ -175,37 +1532,171 @@ async fn main() -> anyhow::Result<()> {
             from,
             args.to_date.unwrap_or(Utc::now())
         );
-        let mut nightlies: Vec<&nightlies::nightly::Nightly> =
-            query_range(&nightlies, from, args.to_date).collect();
-        nightlies.sort_by(|a, b| a.sha_timestamp.cmp(&b.sha_timestamp));
-        for n in nightlies {
-            print(&mut tw, n, args.all_tags, args.print_digest);
-        }
-    } else if let Some(build_sha) = args.build_sha {
-        let nightly = find_nightly_by_build_sha(&nightlies, &build_sha);
-        if let Some(nightly) = nightly {
-            print(&mut tw, nightly, args.all_tags, args.print_digest);
-        } else {
-            warn!("Could not find nightly for build sha: {}", build_sha)
+        let mut listed: Vec<&Nightly> = query_range(&nightlies, from, args.to_date).collect();
+        sort_oldest_first(&mut listed, args.sort_by);
+        let matches = match &args.grep {
+            Some(pattern) => grep_listed(&client, &mut listed, pattern, &ignore)?,
+            None => HashMap::new(),
+        };
+        if let Some(template) = &args.format {
+            print_all_formatted(&listed, template);
+            return Ok(());
         }
-    } else if let Some(sha) = args.agent_sha {
-        let nightly = get_first_nightly_containing_change(&nightlies, &sha)?;
+        writeln!(
+            &mut tw,
+            "Ordered by {} timestamp, oldest first",
+            args.sort_by
+        )
+        .expect("Error writing to tabwriter");
+        let cuts = listed_release_cuts(&client, &listed);
+        let go_bumps = go_version_changes(&nightlies);
+        print_grouped_by_day(
+            &mut tw,
+            &listed,
+            &matches,
+            &cuts,
+            &go_bumps,
+            args.sort_by,
+            &args,
+        );
+        print_footer(&mut tw, &nightlies);
+    } else if let Some(build_sha) = &args.build_sha {
+        let mut fetched_labels = HashMap::new();
+        let resolvers = Resolvers::accepting(ResolverClass::BUILDS);
+        for build_sha in read_identifiers(build_sha)? {
+            let Some(nightly) = resolvers.resolve(&nightlies, &build_sha).nightly() else {
+                warn!("Could not find nightly for build sha: {}", build_sha);
+                not_found.push(format!("'{build_sha}'"));
+                continue;
+            };
+            if let Some(template) = &args.format {
+                print_all_formatted(&[nightly], template);
+                continue;
+            }
+            if !args.labels {
+                print_nightly(&mut tw, nightly, &args);
+                continue;
+            }
 
-        writeln!(&mut tw, "The first nightly containing the target sha is:")
-            .expect("Error writing to tabwriter");
-        print(&mut tw, &nightly, args.all_tags, args.print_digest);
+            let mut nightly = nightly.clone();
+            if nightly.labels.is_none() {
+                let tag = nightly
+                    .first_valid_tag()
+                    .expect("Nightlies have at least one tag");
+                match client.fetch_image_labels(&tag.name).await {
+                    Ok(labels) => {
+                        fetched_labels.insert(nightly.sha.clone(), labels.clone());
+                        nightly.labels = Some(labels);
+                    }
+                    Err(e) => warn!("Could not fetch the labels of {}: {}", tag.name, e),
+                }
+            }
+            print_nightly(&mut tw, &nightly, &args);
+            print_labels(&mut tw, &nightly);
+        }
+
+        if !fetched_labels.is_empty() {
+            let mut cached = client.load_cache()?;
+            for nightly in &mut cached {
+                if let Some(labels) = fetched_labels.remove(&nightly.sha) {
+                    nightly.labels = Some(labels);
+                }
+            }
+            client.save_cache(&cached)?;
+        }
+    } else if let Some(sha) = &args.agent_sha {
+        let shas = read_identifiers(sha)?;
+        if let [sha] = shas.as_slice() {
+            let nightly = get_first_nightly_containing_change(&client.repo_path, &nightlies, sha)
+                .map_err(git_error)?;
+
+            if let Some(template) = &args.format {
+                print_all_formatted(&[&nightly], template);
+            } else {
+                writeln!(&mut tw, "The first nightly containing the target sha is:")
+                    .expect("Error writing to tabwriter");
+                print_nightly(&mut tw, &nightly, &args);
+            }
+        } else {
+            let results =
+                get_first_nightlies_containing_changes(&client.repo_path, &nightlies, &shas)
+                    .map_err(git_error)?;
+            for (sha, result) in shas.iter().zip(results) {
+                match result {
+                    Ok(nightly) => {
+                        if let Some(template) = &args.format {
+                            print_all_formatted(&[&nightly], template);
+                        } else {
+                            writeln!(&mut tw, "The first nightly containing {sha} is:")
+                                .expect("Error writing to tabwriter");
+                            print_nightly(&mut tw, &nightly, &args);
+                        }
+                    }
+                    Err(e) => warn!("Could not find a nightly containing {}: {}", sha, e),
+                }
+            }
+        }
     } else {
-        // default is to just display the most recent 7 days
-        let mut nightlies: Vec<&nightlies::nightly::Nightly> =
-            query_range(&nightlies, Utc::now() - Duration::days(7), None).collect();
-        nightlies.sort_by(|a, b| a.sha_timestamp.cmp(&b.sha_timestamp));
-        for n in nightlies {
-            print(&mut tw, n, args.all_tags, args.print_digest);
+        // default is to just display the most recent 7 days, looking further back when
+        // that's empty (e.g. after the holidays) so the list doesn't look broken
+        let now = Utc::now();
+        let days = adaptive_window_days(&nightlies, now, DEFAULT_WINDOW_DAYS, MAX_WINDOW_DAYS);
+        let mut listed: Vec<&Nightly> =
+            query_range(&nightlies, now - Duration::days(days), None).collect();
+        sort_oldest_first(&mut listed, args.sort_by);
+        if days > DEFAULT_WINDOW_DAYS && args.format.is_none() {
+            let found = if listed.is_empty() {
+                "none found"
+            } else {
+                "showing"
+            };
+            writeln!(
+                &mut tw,
+                "No nightlies in the last {DEFAULT_WINDOW_DAYS} days, {found} in the last {days} days"
+            )
+            .expect("Error writing to tabwriter");
+        }
+        let matches = match &args.grep {
+            Some(pattern) => grep_listed(&client, &mut listed, pattern, &ignore)?,
+            None => HashMap::new(),
+        };
+        if let Some(template) = &args.format {
+            print_all_formatted(&listed, template);
+            return Ok(());
+        }
+        writeln!(
+            &mut tw,
+            "Ordered by {} timestamp, oldest first",
+            args.sort_by
+        )
+        .expect("Error writing to tabwriter");
+        let cuts = listed_release_cuts(&client, &listed);
+        let go_bumps = go_version_changes(&nightlies);
+        print_grouped_by_day(
+            &mut tw,
+            &listed,
+            &matches,
+            &cuts,
+            &go_bumps,
+            args.sort_by,
+            &args,
+        );
+        print_footer(&mut tw, &nightlies);
+        if let Some(estimate) = estimate_next_nightly(&nightlies, Utc::now()) {
+            writeln!(
+                &mut tw,
+                "{}",
+                format_next_nightly(&estimate, Utc::now(), &args)
+            )
+            .expect("Error writing to tabwriter");
         }
     }
 
     let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
     print!("{}", written);
 
+    if !not_found.is_empty() {
+        return Err(NightlyError::NightlyNotFound(not_found.join(", ")).into());
+    }
     Ok(())
 }
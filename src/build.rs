@@ -0,0 +1,51 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Result;
+use tracing::info;
+
+use crate::{config::Build, nightly::Nightly, repo::add_agent_worktree, NightlyError};
+
+/// Where worktrees go when the config doesn't say, if a home directory can be found
+#[must_use]
+pub fn default_worktree_dir() -> Option<PathBuf> {
+    let home = home::home_dir().filter(|path| !path.as_os_str().is_empty())?;
+    Some(Path::new(&home).join(".cache/nightlies/worktrees"))
+}
+
+/// Checks out the commit of the nightly in its own worktree of the datadog-agent checkout
+/// at `repo_path` and runs the configured build command there, so the local binary
+/// matches the nightly
+/// Returns the worktree the build ran in
+///
+/// # Errors
+/// - Errors if no worktree location can be found
+/// - Errors if the worktree cannot be created
+/// - Errors if the build command cannot be run or fails
+pub fn build_local(repo_path: &Path, nightly: &Nightly, build: &Build) -> Result<PathBuf> {
+    let dir = build
+        .worktree_dir
+        .clone()
+        .or_else(default_worktree_dir)
+        .ok_or_else(|| NightlyError::GenericError(String::from("no home directory")))?;
+    std::fs::create_dir_all(&dir)?;
+    let worktree = dir.join(format!("nightly-{}", nightly.sha));
+    add_agent_worktree(repo_path, &nightly.sha, &worktree)?;
+
+    info!("Running '{}' in {}", build.command, worktree.display());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(&build.command)
+        .current_dir(&worktree)
+        .status()?;
+    if !status.success() {
+        return Err(NightlyError::GenericError(format!(
+            "'{}' exited with {status}",
+            build.command
+        ))
+        .into());
+    }
+    Ok(worktree)
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc, Weekday};
+use serde::Deserialize;
+
+use crate::{nightly::Nightly, query::Clock};
+
+// Rules for which nightlies count as built on a business day, kept in one place so that
+// every view of the nightlies agrees on them
+
+/// Whether the given time falls on a Saturday or Sunday (UTC)
+#[must_use]
+pub fn is_weekend(time: DateTime<Utc>) -> bool {
+    matches!(time.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// Which timestamp decides whether a nightly is a weekend build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "client", derive(clap::ValueEnum))]
+pub enum WeekendFilter {
+    /// Hide nightlies pushed on a weekend
+    Push,
+    /// Hide nightlies whose commit was made on a weekend
+    Commit,
+    /// Keep weekend builds
+    Off,
+}
+
+impl WeekendFilter {
+    /// Whether the nightly is a weekend build that this filter hides
+    /// The commit filter falls back to the push time for nightlies without a commit timestamp
+    #[must_use]
+    pub fn excludes(self, nightly: &Nightly) -> bool {
+        let clock = match self {
+            WeekendFilter::Push => Clock::Push,
+            WeekendFilter::Commit => Clock::Commit,
+            WeekendFilter::Off => return false,
+        };
+        is_weekend(clock.timestamp(nightly))
+    }
+}
+
+/// A recurring window of time whose builds are hidden, configured as `[[exclude]]`
+/// tables in the config file
+///
+/// A nightly falls in the window when its timestamp (by `clock`, in `utc_offset`) is on
+/// one of `days` and within `hours`. Leaving out `days` or `hours` matches any day or hour
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawExclusionWindow")]
+pub struct ExclusionWindow {
+    pub days: Vec<Weekday>,
+    /// Start (inclusive) and end (exclusive) hour, a start after the end wraps past midnight
+    pub hours: Option<(u32, u32)>,
+    pub clock: Clock,
+    pub utc_offset: FixedOffset,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawExclusionWindow {
+    #[serde(default)]
+    days: Vec<Weekday>,
+    hours: Option<String>,
+    #[serde(default = "default_window_clock")]
+    clock: Clock,
+    utc_offset: Option<String>,
+}
+
+fn default_window_clock() -> Clock {
+    Clock::Push
+}
+
+fn parse_hours(hours: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid hours '{hours}', expected a range like '22-6'");
+    let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+    let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+    let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+    if start > 23 || end > 24 {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+impl TryFrom<RawExclusionWindow> for ExclusionWindow {
+    type Error = String;
+
+    fn try_from(raw: RawExclusionWindow) -> Result<Self, Self::Error> {
+        let hours = raw.hours.as_deref().map(parse_hours).transpose()?;
+        let utc_offset = match raw.utc_offset {
+            Some(offset) => offset
+                .parse()
+                .map_err(|_| format!("invalid utc_offset '{offset}', expected e.g. '+02:00'"))?,
+            None => FixedOffset::east_opt(0).expect("UTC is a valid offset"),
+        };
+        Ok(Self {
+            days: raw.days,
+            hours,
+            clock: raw.clock,
+            utc_offset,
+        })
+    }
+}
+
+impl ExclusionWindow {
+    /// Whether the nightly falls within this window
+    #[must_use]
+    pub fn excludes(&self, nightly: &Nightly) -> bool {
+        let local = self
+            .clock
+            .timestamp(nightly)
+            .with_timezone(&self.utc_offset);
+        if !self.days.is_empty() && !self.days.contains(&local.weekday()) {
+            return false;
+        }
+        match self.hours {
+            None => true,
+            Some((start, end)) if start <= end => (start..end).contains(&local.hour()),
+            Some((start, end)) => local.hour() >= start || local.hour() < end,
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    image::{self, DEFAULT_OCI_URL},
+    nightly::{self, Nightly, Tag, DEFAULT_URL},
+    quarantine::QuarantinedEntry,
+    repo, NightlyError,
+};
+
+// Everything that used to be read from process wide statics (registry endpoints, the
+// cache location, the datadog-agent checkout) lives here, so differently configured
+// clients can share a process. What the repo module remembers between lookups is keyed by
+// checkout, so it isn't shared between clients with different `repo_path`s either
+
+/// How fresh the data a command answers from has to be
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Fetch new tags from the registry and merge them into the cache, or refresh the
+    /// cache in the background when the config asks for it
+    #[default]
+    Hybrid,
+    /// Always sync with the registry and the datadog-agent remote before answering
+    Refresh,
+    /// Never touch the network, answer from the cache and the local checkout only
+    CacheOnly,
+}
+
+/// The working context of an investigation, kept between runs until cleared so that
+/// commands can leave out the nightly everything is compared against
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionContext {
+    /// Sha of the base nightly, the `base` identifier
+    pub base: Option<String>,
+    pub set_at: Option<DateTime<Utc>>,
+}
+
+/// Where nightlies are fetched from and cached to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NightliesClient {
+    /// The docker hub tags endpoint of the agent-dev repository
+    pub registry_url: String,
+    /// The OCI distribution endpoint of the agent-dev repository, for image labels
+    pub oci_registry_url: String,
+    /// The json file nightlies are cached in between runs
+    pub cache_file: PathBuf,
+    /// The datadog-agent checkout commits are looked up in
+    pub repo_path: PathBuf,
+    pub freshness: Freshness,
+}
+
+impl Default for NightliesClient {
+    /// The public registry, cached in the temp dir, with the checkout at its default
+    /// location
+    fn default() -> Self {
+        Self {
+            registry_url: DEFAULT_URL.to_string(),
+            oci_registry_url: DEFAULT_OCI_URL.to_string(),
+            cache_file: default_cache_file(),
+            repo_path: repo::default_agent_repo_path(),
+            freshness: Freshness::default(),
+        }
+    }
+}
+
+/// A 'stable' temp dir path that can be used to cache the results from previous runs
+fn default_cache_file() -> PathBuf {
+    std::env::temp_dir().join("agent_nightlies.json")
+}
+
+impl NightliesClient {
+    /// The client the CLI uses, `NIGHTLIES_REGISTRY_URL` and `NIGHTLIES_OCI_REGISTRY_URL`
+    /// override the registry endpoints to point at a mirror or at a fake registry in tests
+    #[must_use]
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            registry_url: std::env::var("NIGHTLIES_REGISTRY_URL").unwrap_or(default.registry_url),
+            oci_registry_url: std::env::var("NIGHTLIES_OCI_REGISTRY_URL")
+                .unwrap_or(default.oci_registry_url),
+            cache_file: default.cache_file,
+            repo_path: default.repo_path,
+            freshness: default.freshness,
+        }
+    }
+
+    /// Opens the datadog-agent checkout, None (after a warning) if it isn't usable
+    #[must_use]
+    pub fn open_agent_repo(&self) -> Option<gix::Repository> {
+        repo::open_agent_repo(&self.repo_path)
+    }
+
+    /// Errors when the network is off limits
+    fn check_network(&self, what: &str) -> Result<(), NightlyError> {
+        if self.freshness == Freshness::CacheOnly {
+            return Err(NightlyError::GenericError(format!(
+                "Not {what}, the network is off limits in cache-only mode"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fetches the first `num_pages` of nightly tags from the registry
+    /// In cache-only mode there are no new tags, and nothing is fetched
+    ///
+    /// # Errors
+    /// - Errors if there is a problem fetching data from the docker registry api
+    pub async fn fetch_tags(&self, num_pages: usize) -> Result<Vec<Tag>, NightlyError> {
+        Ok(self.fetch_tags_quarantining(num_pages).await?.0)
+    }
+
+    /// Like [`NightliesClient::fetch_tags`], also returning the registry entries that
+    /// could not be parsed as tags
+    ///
+    /// # Errors
+    /// - Errors if there is a problem fetching data from the docker registry api
+    pub async fn fetch_tags_quarantining(
+        &self,
+        num_pages: usize,
+    ) -> Result<(Vec<Tag>, Vec<QuarantinedEntry>), NightlyError> {
+        if self.freshness == Freshness::CacheOnly {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        nightly::fetch_registry_tags(&self.registry_url, num_pages).await
+    }
+
+    /// Checks whether the given tag is still served by the registry
+    ///
+    /// # Errors
+    /// - Errors if there is a problem reaching the docker registry api
+    /// - Errors in cache-only mode
+    pub async fn tag_exists(&self, tag_name: &str) -> Result<bool, NightlyError> {
+        self.check_network("checking the registry for a tag")?;
+        nightly::registry_tag_exists(&self.registry_url, tag_name).await
+    }
+
+    /// Fetches the given tag as the registry currently serves it, None if it was deleted
+    ///
+    /// # Errors
+    /// - Errors if there is a problem reaching the docker registry api
+    /// - Errors in cache-only mode
+    pub async fn fetch_tag(&self, tag_name: &str) -> Result<Option<Tag>, NightlyError> {
+        self.check_network("fetching a tag from the registry")?;
+        nightly::registry_tag(&self.registry_url, tag_name).await
+    }
+
+    /// Marks cached nightlies whose tags have been deleted from the registry as archived,
+    /// see [`nightly::archive_deleted_nightlies`]
+    ///
+    /// # Errors
+    /// - Errors if a check of the registry panics
+    pub async fn archive_deleted_nightlies(
+        &self,
+        live_tags: &[Tag],
+        nightlies: &mut [Nightly],
+    ) -> Result<usize, NightlyError> {
+        nightly::archive_deleted(self, live_tags, nightlies).await
+    }
+
+    /// Fetches the labels of the image config behind the given tag
+    ///
+    /// # Errors
+    /// - Errors if there is a problem reaching the registry or authenticating with it
+    /// - Errors if the manifests don't lead to an image config
+    /// - Errors in cache-only mode
+    pub async fn fetch_image_labels(
+        &self,
+        tag_name: &str,
+    ) -> Result<BTreeMap<String, String>, NightlyError> {
+        self.check_network("fetching image labels")?;
+        image::fetch_labels(&self.oci_registry_url, tag_name).await
+    }
+
+    /// Saves the given nightlies to the cache file
+    ///
+    /// # Errors
+    /// - Errors if the cache file cannot be written to
+    /// - Errors if the nightlies cannot be serialized to json
+    pub fn save_cache(&self, nightlies: &[Nightly]) -> Result<(), NightlyError> {
+        nightly::save_nightlies(&self.cache_file, nightlies)
+    }
+
+    /// Loads nightlies from the cache file, none when there is no cache yet
+    ///
+    /// # Errors
+    /// - Errors if the cache file cannot be read
+    /// - Errors if the nightlies cannot be deserialized from json
+    pub fn load_cache(&self) -> Result<Vec<Nightly>, NightlyError> {
+        nightly::load_nightlies(&self.cache_file)
+    }
+
+    /// The file quarantined registry entries are kept in, next to the cache
+    fn quarantine_file(&self) -> PathBuf {
+        self.cache_file.with_extension("quarantine.json")
+    }
+
+    /// Saves the quarantined registry entries
+    ///
+    /// # Errors
+    /// - Errors if the quarantine file cannot be written to
+    pub fn save_quarantine(&self, quarantine: &[QuarantinedEntry]) -> Result<(), NightlyError> {
+        fs::write(
+            self.quarantine_file(),
+            serde_json::to_string_pretty(quarantine)?,
+        )?;
+        Ok(())
+    }
+
+    /// Loads the quarantined registry entries, none when nothing was quarantined yet
+    ///
+    /// # Errors
+    /// - Errors if the quarantine file cannot be read or deserialized
+    pub fn load_quarantine(&self) -> Result<Vec<QuarantinedEntry>, NightlyError> {
+        match fs::read_to_string(self.quarantine_file()) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The file the session context is kept in, next to the cache
+    fn context_file(&self) -> PathBuf {
+        self.cache_file.with_extension("context.json")
+    }
+
+    /// Saves the session context
+    ///
+    /// # Errors
+    /// - Errors if the context file cannot be written to
+    pub fn save_context(&self, context: &SessionContext) -> Result<(), NightlyError> {
+        fs::write(self.context_file(), serde_json::to_string_pretty(context)?)?;
+        Ok(())
+    }
+
+    /// Loads the session context, an empty one when none was set
+    ///
+    /// # Errors
+    /// - Errors if the context file cannot be read or deserialized
+    pub fn load_context(&self) -> Result<SessionContext, NightlyError> {
+        match fs::read_to_string(self.context_file()) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SessionContext::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Forgets the session context
+    ///
+    /// # Errors
+    /// - Errors if the context file exists but cannot be removed
+    pub fn clear_context(&self) -> Result<(), NightlyError> {
+        match fs::remove_file(self.context_file()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records that a background refresh is starting, unless the cache was refreshed less
+    /// than `interval` ago or a refresh started less than [`REFRESH_RETRY_DELAY_MINUTES`] ago,
+    /// returning whether the caller should go ahead with it
+    /// A refresh that fails is retried after the delay rather than after `interval`
+    ///
+    /// # Errors
+    /// - Errors if the time of the last refresh cannot be read or recorded
+    pub fn claim_background_refresh(&self, interval: Duration) -> Result<bool, NightlyError> {
+        let now = Utc::now();
+        let refreshed = read_stamp(&self.cache_file.with_extension("refreshed"))?;
+        if refreshed.is_some_and(|last| now - last < interval) {
+            return Ok(false);
+        }
+        let attempt = self.cache_file.with_extension("refresh-attempted");
+        let attempted = read_stamp(&attempt)?;
+        let retry_delay = Duration::minutes(REFRESH_RETRY_DELAY_MINUTES).min(interval);
+        if attempted.is_some_and(|last| now - last < retry_delay) {
+            return Ok(false);
+        }
+        fs::write(&attempt, now.to_rfc3339())?;
+        Ok(true)
+    }
+
+    /// Records that the cache was just refreshed, see
+    /// [`NightliesClient::claim_background_refresh`]
+    ///
+    /// # Errors
+    /// - Errors if the time cannot be recorded
+    pub fn record_refresh(&self) -> Result<(), NightlyError> {
+        let stamp = self.cache_file.with_extension("refreshed");
+        fs::write(stamp, Utc::now().to_rfc3339())?;
+        Ok(())
+    }
+}
+
+/// How long a background refresh is given before another one is started, in case it
+/// failed
+pub const REFRESH_RETRY_DELAY_MINUTES: i64 = 5;
+
+/// The time written in a stamp file, None if there is none or it can't be parsed
+fn read_stamp(path: &Path) -> Result<Option<DateTime<Utc>>, NightlyError> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(DateTime::parse_from_rfc3339(content.trim())
+            .map(|last| last.with_timezone(&Utc))
+            .ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{business_day::ExclusionWindow, NightlyError};
+
+/// How long cached data is kept around before being pruned
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Retention {
+    /// Nightlies last pushed more than this many days ago are dropped from the cache
+    pub nightlies_days: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            nightlies_days: 365,
+        }
+    }
+}
+
+/// How output is rendered
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Output {
+    /// Only print ASCII characters, same as `--ascii`
+    pub ascii: bool,
+}
+
+/// How `--build-local` builds a nightly's commit
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Build {
+    /// Shell command run from the root of the worktree
+    pub command: String,
+    /// Where worktrees are created, defaults to `~/.cache/nightlies/worktrees`
+    pub worktree_dir: Option<PathBuf>,
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Self {
+            command: String::from("dda inv agent.build"),
+            worktree_dir: None,
+        }
+    }
+}
+
+/// Where debug symbols of nightlies can be found, see `symbols::render_symbol_url`
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Symbols {
+    /// URL templates, `{sha}`, `{tag}` and `{pipeline}` are filled in per nightly
+    pub urls: Vec<String>,
+}
+
+/// When the registry is asked for new nightlies
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Refresh {
+    /// Answer every command from the cache and refresh it in a background process
+    /// instead of waiting on the registry
+    pub background: bool,
+    /// Minimum number of minutes between two background refreshes
+    pub interval_minutes: u32,
+}
+
+impl Default for Refresh {
+    fn default() -> Self {
+        Self {
+            background: false,
+            interval_minutes: 15,
+        }
+    }
+}
+
+/// Where the datadog-agent checkout is
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Repo {
+    /// Path of the checkout, defaults to `~/go/src/github.com/DataDog/datadog-agent`
+    pub path: Option<PathBuf>,
+}
+
+/// User configuration, read from `~/.config/nightlies/config.toml`
+///
+/// Every field is optional, a missing file or section falls back to the defaults
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    pub retention: Retention,
+    /// Builds falling in any of these windows are hidden
+    pub exclude: Vec<ExclusionWindow>,
+    pub output: Output,
+    pub build: Build,
+    pub symbols: Symbols,
+    pub refresh: Refresh,
+    pub repo: Repo,
+}
+
+/// Returns the location of the config file, if a home directory can be found
+#[must_use]
+pub fn get_config_path() -> Option<PathBuf> {
+    let home = home::home_dir().filter(|path| !path.as_os_str().is_empty())?;
+    Some(Path::new(&home).join(".config/nightlies/config.toml"))
+}
+
+/// Replaces every `${NAME}` in `content` with the value `lookup` gives for `NAME`,
+/// so that secrets can be kept out of the config file
+///
+/// # Errors
+/// - Errors if a referenced variable has no value
+/// - Errors if a `${` is never closed
+pub fn interpolate_env_vars<F>(content: &str, lookup: F) -> Result<String, NightlyError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut interpolated = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("${") {
+        interpolated.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| NightlyError::GenericError(String::from("Unclosed '${' in config")))?;
+        let name = &after[..end];
+        let value = lookup(name).ok_or_else(|| NightlyError::MissingEnvVar(name.to_string()))?;
+        interpolated.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+/// Loads the user configuration, `${NAME}` is replaced by the `NAME` environment variable
+///
+/// # Errors
+/// - Errors if the config file exists but cannot be read
+/// - Errors if the config file references an unset environment variable
+/// - Errors if the config file is not valid toml
+pub fn load_config() -> Result<Config, NightlyError> {
+    let Some(path) = get_config_path() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            debug!("Reading config from {path}", path = path.display());
+            let content = interpolate_env_vars(&content, |name| std::env::var(name).ok())?;
+            Ok(toml::from_str(&content)?)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Records the path of the datadog-agent checkout in the config file, creating it if needed
+///
+/// # Errors
+/// - Errors if no home directory can be found
+/// - Errors if the config already has a `[repo]` section, which is left for the user to edit
+/// - Errors if the config file cannot be read or written
+pub fn save_repo_path(repo_path: &Path) -> Result<PathBuf, NightlyError> {
+    let path = get_config_path()
+        .ok_or_else(|| NightlyError::GenericError(String::from("Could not find home directory")))?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if content.lines().any(|line| line.trim() == "[repo]") {
+        return Err(NightlyError::GenericError(format!(
+            "{} already has a [repo] section, set its path to {}",
+            path.display(),
+            repo_path.display()
+        )));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let value = toml::Value::String(repo_path.to_string_lossy().into_owned());
+    let separator = if content.is_empty() || content.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    fs::write(
+        &path,
+        format!("{content}{separator}\n[repo]\npath = {value}\n"),
+    )?;
+    Ok(path)
+}
@@ -0,0 +1,116 @@
+use std::{
+    backtrace::Backtrace,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+
+use crate::{nightly::redact_url, NightlyError};
+
+// Crash reports stay on disk, nothing is ever sent anywhere. They are meant to be
+// attached to bug reports by hand
+
+/// Where crash reports are written, if a home directory can be found
+#[must_use]
+pub fn get_crash_report_dir() -> Option<PathBuf> {
+    let home = home::home_dir().filter(|path| !path.as_os_str().is_empty())?;
+    Some(Path::new(&home).join(".local/share/nightlies/crash-reports"))
+}
+
+/// Replaces the home directory with `~` so reports don't leak user names
+pub(crate) fn redact(text: &str) -> String {
+    match home::home_dir().filter(|path| !path.as_os_str().is_empty()) {
+        Some(home) => text.replace(&*home.to_string_lossy(), "~"),
+        None => text.to_string(),
+    }
+}
+
+/// Hides credentials in URL-shaped args, e.g. a registry mirror given with its user and
+/// password, whether the url is its own arg or the value of a `--flag=`
+fn redact_arg(arg: &str) -> String {
+    let (flag, value) = match arg.split_once('=') {
+        Some((flag, value)) if flag.starts_with("--") => (Some(flag), value),
+        _ => (None, arg),
+    };
+    if !value.contains("://") {
+        return arg.to_string();
+    }
+    match flag {
+        Some(flag) => format!("{flag}={}", redact_url(value)),
+        None => redact_url(value),
+    }
+}
+
+/// The crash report of a run with the given args, with the home directory and any
+/// credentials in the args redacted
+#[must_use]
+pub fn render_crash_report(args: &[String], panic_message: &str) -> String {
+    let args: Vec<String> = args.iter().map(|arg| redact_arg(arg)).collect();
+    let report = format!(
+        "nightlies {} ({} {})\nargs: {}\n\n{}\n\nbacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        args.join(" "),
+        panic_message,
+        Backtrace::force_capture()
+    );
+    redact(&report)
+}
+
+fn write_crash_report(panic_message: &str) -> Result<PathBuf, NightlyError> {
+    let dir = get_crash_report_dir()
+        .ok_or_else(|| NightlyError::GenericError(String::from("no home directory")))?;
+    fs::create_dir_all(&dir)?;
+
+    let args: Vec<String> = std::env::args().collect();
+    let path = dir.join(format!(
+        "crash-{}.txt",
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    fs::write(&path, render_crash_report(&args, panic_message))?;
+    Ok(path)
+}
+
+/// Installs a panic hook that writes a crash report after the usual panic message
+/// and tells the user where to find it
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        match write_crash_report(&info.to_string()) {
+            Ok(path) => eprintln!(
+                "A crash report was written to {}, please attach it when reporting this bug",
+                path.display()
+            ),
+            Err(e) => eprintln!("Could not write a crash report: {e}"),
+        }
+    }));
+}
+
+/// Returns the most recent crash report, if there is one
+///
+/// # Errors
+/// - Errors if the crash report directory or the report cannot be read
+pub fn last_crash_report() -> Result<Option<(PathBuf, String)>, NightlyError> {
+    let Some(dir) = get_crash_report_dir() else {
+        return Ok(None);
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // Report names start with their creation time, so the last one by name is the newest
+    let Some(path) = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .max()
+    else {
+        return Ok(None);
+    };
+    let report = fs::read_to_string(&path)?;
+    Ok(Some((path, report)))
+}
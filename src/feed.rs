@@ -0,0 +1,85 @@
+use std::{collections::HashMap, fmt::Write, hash::BuildHasher};
+
+use chrono::{DateTime, Utc};
+
+use crate::{nightly::Nightly, site::escape};
+
+// An Atom feed of the newest nightlies, so new ones show up in feed readers
+
+/// Number of most recent nightlies in the feed
+const FEED_SIZE: usize = 50;
+
+/// The nightlies the feed has an entry for, newest first
+#[must_use]
+pub fn feed_nightlies(nightlies: &[Nightly]) -> Vec<&Nightly> {
+    let mut newest_first: Vec<&Nightly> = nightlies.iter().collect();
+    newest_first.sort_by_key(|n| std::cmp::Reverse(n.estimated_last_pushed));
+    newest_first.truncate(FEED_SIZE);
+    newest_first
+}
+
+/// Renders an Atom feed of the newest nightlies, `changes` maps a nightly's sha to the
+/// commits (short sha and subject) it adds over its predecessor, when they are known
+/// `updated` is used as the feed's timestamp when there are no nightlies
+///
+/// # Panics
+/// - If writing to a string fails, which it doesn't
+#[must_use]
+pub fn render_feed<S: BuildHasher>(
+    nightlies: &[Nightly],
+    changes: &HashMap<String, Vec<String>, S>,
+    updated: DateTime<Utc>,
+) -> String {
+    let newest_first = feed_nightlies(nightlies);
+    let updated = newest_first
+        .first()
+        .map_or(updated, |n| n.estimated_last_pushed);
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <id>urn:nightlies:datadog-agent</id>\n\
+         <title>Agent nightlies</title>\n\
+         <updated>{}</updated>\n",
+        updated.to_rfc3339()
+    );
+    for nightly in newest_first {
+        let name = nightly
+            .first_valid_tag()
+            .map_or(nightly.sha.as_str(), |t| t.name.as_str());
+        let mut summary = match nightly.incremental_commits {
+            Some(count) => format!("{count} new commits"),
+            None => String::from("New nightly"),
+        };
+        if let Some(prev) = &nightly.predecessor_sha {
+            write!(summary, " since {prev}").unwrap();
+        }
+        let mut content = format!("<p>{}</p>", escape(&summary));
+        if let Some(commits) = changes.get(&nightly.sha) {
+            content.push_str("<ul>");
+            for commit in commits {
+                write!(content, "<li>{}</li>", escape(commit)).unwrap();
+            }
+            content.push_str("</ul>");
+        }
+        writeln!(
+            feed,
+            "<entry>\n\
+             <id>urn:nightlies:{sha}</id>\n\
+             <title>{}</title>\n\
+             <updated>{}</updated>\n\
+             <link href=\"https://github.com/DataDog/datadog-agent/tree/{sha}\"/>\n\
+             <author><name>datadog-agent CI</name></author>\n\
+             <summary>{}</summary>\n\
+             <content type=\"html\">{}</content>\n\
+             </entry>",
+            escape(name),
+            nightly.estimated_last_pushed.to_rfc3339(),
+            escape(&summary),
+            escape(&content),
+            sha = escape(&nightly.sha),
+        )
+        .unwrap();
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
@@ -0,0 +1,62 @@
+use chrono::{Datelike, NaiveDate};
+
+// Date rendering shared by everything that prints dates for people rather than machines
+
+/// English ordinal suffix for a day of the month, e.g. "st" for 1 and "th" for 11
+#[must_use]
+pub fn ordinal_suffix(day: u32) -> &'static str {
+    match (day % 10, day % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// A date as a heading, e.g. "Thursday, July 4th"
+#[must_use]
+pub fn day_heading(date: NaiveDate) -> String {
+    format!(
+        "{}{}",
+        date.format("%A, %B %-d"),
+        ordinal_suffix(date.day())
+    )
+}
+
+/// The non-letter characters that decorate the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyphs {
+    /// Surrounds day headings
+    pub rule: &'static str,
+    pub plus_minus: &'static str,
+    /// Repeated to draw the bars of charts
+    pub bar: &'static str,
+}
+
+impl Glyphs {
+    pub const UNICODE: Glyphs = Glyphs {
+        rule: "──",
+        plus_minus: "±",
+        bar: "█",
+    };
+
+    /// For terminals and log systems that mangle anything outside of ASCII
+    pub const ASCII: Glyphs = Glyphs {
+        rule: "--",
+        plus_minus: "+/-",
+        bar: "#",
+    };
+}
+
+/// A horizontal bar for `value` on a scale up to `max`, at most `width` glyphs long
+/// Any non-zero value gets at least one glyph so it can't be mistaken for zero
+#[must_use]
+pub fn bar(value: usize, max: usize, width: usize, glyphs: Glyphs) -> String {
+    if value == 0 || max == 0 {
+        return String::new();
+    }
+    glyphs
+        .bar
+        .repeat((value * width).div_ceil(max).clamp(1, width))
+}
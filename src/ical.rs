@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+
+use crate::nightly::{NextNightlyEstimate, Nightly};
+
+// An iCalendar (RFC 5545) export of nightly publish times, to overlay on planning calendars
+
+/// Escapes text for an iCalendar TEXT value
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line into lines of at most 75 octets, continuation lines start with
+/// a space, and terminates it with CRLF
+fn push_line(calendar: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            calendar.push_str("\r\n ");
+            width = 1;
+        }
+        calendar.push(c);
+        width += c.len_utf8();
+    }
+    calendar.push_str("\r\n");
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Renders the nightlies pushed since `since` as calendar events, plus the predicted next
+/// nightly as an event spanning its accuracy bounds
+#[must_use]
+pub fn render_calendar(
+    nightlies: &[Nightly],
+    next: Option<NextNightlyEstimate>,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> String {
+    let mut oldest_first: Vec<&Nightly> = nightlies
+        .iter()
+        .filter(|n| n.estimated_last_pushed >= since)
+        .collect();
+    oldest_first.sort_by_key(|n| n.estimated_last_pushed);
+
+    let mut calendar = String::new();
+    push_line(&mut calendar, "BEGIN:VCALENDAR");
+    push_line(&mut calendar, "VERSION:2.0");
+    push_line(
+        &mut calendar,
+        concat!(
+            "PRODID:-//nightlies//nightlies ",
+            env!("CARGO_PKG_VERSION"),
+            "//EN"
+        ),
+    );
+    let stamp = format_time(now);
+    for nightly in oldest_first {
+        let name = nightly
+            .first_valid_tag()
+            .map_or(nightly.sha.as_str(), |t| t.name.as_str());
+        let description = match nightly.incremental_commits {
+            Some(count) => format!("datadog/agent-dev:{name}\n{count} new commits"),
+            None => format!("datadog/agent-dev:{name}"),
+        };
+        push_line(&mut calendar, "BEGIN:VEVENT");
+        push_line(&mut calendar, &format!("UID:{}@nightlies", nightly.sha));
+        push_line(&mut calendar, &format!("DTSTAMP:{stamp}"));
+        push_line(
+            &mut calendar,
+            &format!("DTSTART:{}", format_time(nightly.estimated_last_pushed)),
+        );
+        push_line(
+            &mut calendar,
+            &format!("SUMMARY:Nightly {}", escape(&nightly.sha)),
+        );
+        push_line(
+            &mut calendar,
+            &format!("DESCRIPTION:{}", escape(&description)),
+        );
+        push_line(
+            &mut calendar,
+            &format!(
+                "URL:https://github.com/DataDog/datadog-agent/tree/{}",
+                nightly.sha
+            ),
+        );
+        push_line(&mut calendar, "END:VEVENT");
+    }
+    if let Some(next) = next {
+        push_line(&mut calendar, "BEGIN:VEVENT");
+        // The same UID on every export, so calendars move the expected nightly rather
+        // than piling up a new event each time the estimate changes
+        push_line(&mut calendar, "UID:next-nightly@nightlies");
+        push_line(&mut calendar, &format!("DTSTAMP:{stamp}"));
+        push_line(
+            &mut calendar,
+            &format!("DTSTART:{}", format_time(next.expected_at - next.std_dev)),
+        );
+        push_line(
+            &mut calendar,
+            &format!("DTEND:{}", format_time(next.expected_at + next.std_dev)),
+        );
+        push_line(&mut calendar, "SUMMARY:Expected nightly");
+        push_line(
+            &mut calendar,
+            &format!(
+                "DESCRIPTION:Predicted from recent publish times\\, expected at {}",
+                next.expected_at.format("%H:%M UTC")
+            ),
+        );
+        push_line(&mut calendar, "END:VEVENT");
+    }
+    push_line(&mut calendar, "END:VCALENDAR");
+    calendar
+}
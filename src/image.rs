@@ -0,0 +1,143 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::nightly::{Nightly, Tag};
+
+#[cfg(feature = "client")]
+mod oci;
+#[cfg(feature = "client")]
+#[allow(deprecated)]
+pub use oci::fetch_image_labels;
+#[cfg(feature = "client")]
+pub(crate) use oci::fetch_labels;
+#[cfg(feature = "client")]
+pub use oci::DEFAULT_OCI_URL;
+
+/// The id of the CI pipeline that built an image, from whichever of its labels name one
+#[must_use]
+pub fn pipeline_id_from_labels(labels: &BTreeMap<String, String>) -> Option<&str> {
+    labels
+        .iter()
+        .find(|(key, value)| {
+            let key = key.to_lowercase();
+            key.contains("pipeline")
+                && (key.ends_with("id") || key.ends_with("pipeline"))
+                && !value.is_empty()
+                && value.chars().all(|c| c.is_ascii_digit())
+        })
+        .map(|(_, value)| value.as_str())
+}
+
+/// Describes the CI pipeline that built an image, from whichever of its labels name one,
+/// e.g. `pipeline 42 (https://gitlab.example.com/pipelines/42)`
+#[must_use]
+pub fn pipeline_from_labels(labels: &BTreeMap<String, String>) -> Option<String> {
+    let pipeline_labels = || {
+        labels
+            .iter()
+            .filter(|(key, _)| key.to_lowercase().contains("pipeline"))
+    };
+    let url = pipeline_labels()
+        .find(|(_, value)| value.starts_with("https://") || value.starts_with("http://"))
+        .map(|(_, value)| value);
+    match (pipeline_id_from_labels(labels), url) {
+        (Some(id), Some(url)) => Some(format!("pipeline {id} ({url})")),
+        (Some(id), None) => Some(format!("pipeline {id}")),
+        (None, Some(url)) => Some(format!("pipeline {url}")),
+        (None, None) => None,
+    }
+}
+
+/// The label recording the commit an image was built from and its value, e.g.
+/// `org.opencontainers.image.revision`
+#[must_use]
+pub fn revision_from_labels(labels: &BTreeMap<String, String>) -> Option<(&str, &str)> {
+    labels
+        .iter()
+        .find(|(key, value)| {
+            let key = key.to_lowercase();
+            ["revision", "commit", "commit_sha", "git_sha", "git.sha"]
+                .iter()
+                .any(|suffix| key.ends_with(suffix))
+                && value.len() >= 7
+                && value.chars().all(|c| c.is_ascii_hexdigit())
+        })
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+}
+
+/// A reason to doubt that an image was built from the commit its tag names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvenanceIssue {
+    /// The image's commit label names another commit than the tag
+    RevisionMismatch { label: String, revision: String },
+    /// None of the image's labels records the commit it was built from
+    NoRevisionLabel,
+    /// The registry serves other content under the tag than when it was first seen
+    DigestChanged {
+        tag: String,
+        first_seen: String,
+        live: String,
+    },
+}
+
+impl fmt::Display for ProvenanceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProvenanceIssue::RevisionMismatch { label, revision } => {
+                write!(f, "the image's {label} label names commit {revision}")
+            }
+            ProvenanceIssue::NoRevisionLabel => {
+                write!(f, "no image label records the commit it was built from")
+            }
+            ProvenanceIssue::DigestChanged {
+                tag,
+                first_seen,
+                live,
+            } => write!(
+                f,
+                "{tag} was re-pushed, its digest changed from {first_seen} to {live}"
+            ),
+        }
+    }
+}
+
+/// Checks that a nightly's image was built from the commit its tags name: the image's
+/// commit label, when `labels` are known, has to agree with the tag's sha, and each
+/// tag in `live_tags` has to still point at the digest it was first seen with
+#[must_use]
+pub fn check_provenance(
+    nightly: &Nightly,
+    labels: Option<&BTreeMap<String, String>>,
+    live_tags: &[Tag],
+) -> Vec<ProvenanceIssue> {
+    let mut issues = Vec::new();
+    match labels.map(revision_from_labels) {
+        Some(Some((label, revision))) => {
+            let (short, long) = if revision.len() < nightly.sha.len() {
+                (revision, nightly.sha.as_str())
+            } else {
+                (nightly.sha.as_str(), revision)
+            };
+            if !long.to_lowercase().starts_with(&short.to_lowercase()) {
+                issues.push(ProvenanceIssue::RevisionMismatch {
+                    label: label.to_string(),
+                    revision: revision.to_string(),
+                });
+            }
+        }
+        Some(None) => issues.push(ProvenanceIssue::NoRevisionLabel),
+        None => {}
+    }
+    for tag in nightly.tags() {
+        let Some(live) = live_tags.iter().find(|t| t.name == tag.name) else {
+            continue;
+        };
+        if live.digest != tag.digest {
+            issues.push(ProvenanceIssue::DigestChanged {
+                tag: tag.name.clone(),
+                first_seen: tag.digest.clone(),
+                live: live.digest.clone(),
+            });
+        }
+    }
+    issues
+}
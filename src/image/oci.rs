@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use reqwest::{header, StatusCode};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{client::NightliesClient, nightly::http_send, NightlyError};
+
+// Image metadata lives behind the OCI distribution API of the registry rather than the
+// docker hub API that lists the tags, and needs a (anonymous) bearer token
+
+/// The OCI distribution endpoint of the agent-dev repository
+pub const DEFAULT_OCI_URL: &str = "https://registry-1.docker.io/v2/datadog/agent-dev";
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.v2+json";
+
+/// Parses the parameters of a `WWW-Authenticate: Bearer realm="..",service=".."` challenge
+fn parse_bearer_challenge(challenge: &str) -> Option<BTreeMap<String, String>> {
+    let mut rest = challenge.strip_prefix("Bearer ")?;
+    let mut params = BTreeMap::new();
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim_start_matches([',', ' ']);
+        let value_start = eq + 2;
+        let value_len = rest[value_start..].find('"')?;
+        params.insert(
+            key.to_string(),
+            rest[value_start..value_start + value_len].to_string(),
+        );
+        rest = &rest[value_start + value_len + 1..];
+    }
+    Some(params)
+}
+
+/// Talks to the OCI registry, fetching a token the first time one is asked for
+struct OciClient<'a> {
+    client: reqwest::Client,
+    base_url: &'a str,
+    token: Option<String>,
+}
+
+impl<'a> OciClient<'a> {
+    fn new(base_url: &'a str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url,
+            token: None,
+        }
+    }
+
+    async fn send(&self, url: &str) -> Result<reqwest::Response, NightlyError> {
+        let mut request = self
+            .client
+            .get(url)
+            .header(header::ACCEPT, MANIFEST_MEDIA_TYPES);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        Ok(http_send(&self.client, request.build()?).await?)
+    }
+
+    async fn fetch_token(&self, challenge: &str) -> Result<String, NightlyError> {
+        let params = parse_bearer_challenge(challenge).ok_or_else(|| {
+            NightlyError::GenericError(format!("Unsupported registry auth: {challenge}"))
+        })?;
+        let realm = params.get("realm").ok_or_else(|| {
+            NightlyError::GenericError(format!("Registry auth without realm: {challenge}"))
+        })?;
+        let mut url = reqwest::Url::parse(realm)
+            .map_err(|e| NightlyError::GenericError(format!("Invalid auth realm: {e}")))?;
+        for key in ["service", "scope"] {
+            if let Some(value) = params.get(key) {
+                url.query_pairs_mut().append_pair(key, value);
+            }
+        }
+        let response: Value = http_send(&self.client, self.client.get(url).build()?)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        response["token"]
+            .as_str()
+            .or(response["access_token"].as_str())
+            .map(ToString::to_string)
+            .ok_or_else(|| NightlyError::GenericError(String::from("Registry sent no token")))
+    }
+
+    async fn get_json(&mut self, path: &str) -> Result<Value, NightlyError> {
+        let url = format!("{}/{path}", self.base_url);
+        let mut response = self.send(&url).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && self.token.is_none() {
+            let challenge = response
+                .headers()
+                .get(header::WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            self.token = Some(self.fetch_token(&challenge).await?);
+            response = self.send(&url).await?;
+        }
+        Ok(response.error_for_status()?.json().await?)
+    }
+}
+
+/// Picks the linux/amd64 image out of a multi-platform index, or the first one
+fn pick_platform_manifest(index: &Value) -> Option<&str> {
+    let manifests = index["manifests"].as_array()?;
+    manifests
+        .iter()
+        .find(|m| m["platform"]["os"] == "linux" && m["platform"]["architecture"] == "amd64")
+        .or(manifests.first())?["digest"]
+        .as_str()
+}
+
+/// Fetches the labels of the image config behind the given tag
+///
+/// # Errors
+/// - Errors if there is a problem reaching the registry or authenticating with it
+/// - Errors if the manifests don't lead to an image config
+#[deprecated(note = "use `NightliesClient::fetch_image_labels`")]
+pub async fn fetch_image_labels(tag_name: &str) -> Result<BTreeMap<String, String>, NightlyError> {
+    NightliesClient::from_env()
+        .fetch_image_labels(tag_name)
+        .await
+}
+
+pub(crate) async fn fetch_labels(
+    base_url: &str,
+    tag_name: &str,
+) -> Result<BTreeMap<String, String>, NightlyError> {
+    let mut client = OciClient::new(base_url);
+    let mut manifest = client.get_json(&format!("manifests/{tag_name}")).await?;
+    if manifest["manifests"].is_array() {
+        let digest = pick_platform_manifest(&manifest)
+            .ok_or_else(|| NightlyError::GenericError(format!("Empty image index for {tag_name}")))?
+            .to_string();
+        debug!("Using manifest {digest} of {tag_name}");
+        manifest = client.get_json(&format!("manifests/{digest}")).await?;
+    }
+    let config_digest = manifest["config"]["digest"].as_str().ok_or_else(|| {
+        NightlyError::GenericError(format!("No image config in the manifest of {tag_name}"))
+    })?;
+    let config = client.get_json(&format!("blobs/{config_digest}")).await?;
+
+    let labels = config["config"]["Labels"]
+        .as_object()
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(labels)
+}
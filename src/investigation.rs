@@ -0,0 +1,147 @@
+use std::fmt::Write;
+
+use crate::nightly::Nightly;
+
+// A regression noticed at some date, written up for a ticket: the nightlies around it,
+// the commits in between with the likeliest suspects first, and what to try next
+
+/// Number of suspects worth reading before the rest of the range
+pub const TOP_SUSPECTS: usize = 5;
+
+/// The nightlies bracketing a symptom and the commits between them
+#[derive(Debug, Clone)]
+pub struct Investigation<'a> {
+    /// When the symptom was first noticed, as given
+    pub symptom_since: String,
+    /// The nightly before `bad`, the latest one expected to be free of the symptom
+    pub good: &'a Nightly,
+    /// The latest nightly when the symptom was noticed
+    pub bad: &'a Nightly,
+    /// The commits `bad` adds over `good` (short sha and subject), newest first
+    pub commits: Vec<String>,
+    /// What the symptom is about, commits whose subject mentions it rank first
+    pub query: Option<String>,
+}
+
+/// A commit of the range, and whether it mentions the query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suspect<'a> {
+    pub sha: &'a str,
+    pub subject: &'a str,
+    pub matches_query: bool,
+}
+
+fn tree_url(sha: &str) -> String {
+    format!("https://github.com/DataDog/datadog-agent/tree/{sha}")
+}
+
+fn tag_name(nightly: &Nightly) -> &str {
+    nightly
+        .first_valid_tag()
+        .map_or(nightly.sha.as_str(), |t| t.name.as_str())
+}
+
+impl Investigation<'_> {
+    /// The commits of the range, the ones mentioning the query (ignoring case) first and
+    /// otherwise newest first
+    #[must_use]
+    pub fn suspects(&self) -> Vec<Suspect<'_>> {
+        let query = self.query.as_deref().map(str::to_lowercase);
+        let mut suspects: Vec<Suspect> = self
+            .commits
+            .iter()
+            .map(|commit| {
+                let (sha, subject) = commit.split_once(' ').unwrap_or((commit, ""));
+                Suspect {
+                    sha,
+                    subject,
+                    matches_query: query
+                        .as_deref()
+                        .is_some_and(|q| subject.to_lowercase().contains(q)),
+                }
+            })
+            .collect();
+        // Stable, so the newest first order holds within both groups
+        suspects.sort_by_key(|s| !s.matches_query);
+        suspects
+    }
+
+    /// Commands to dig further, most useful first
+    #[must_use]
+    pub fn next_steps(&self) -> Vec<String> {
+        let (good, bad) = (&self.good.sha, &self.bad.sha);
+        let mut steps = vec![
+            format!("nightlies --build-local {good} and nightlies --build-local {bad} to reproduce both sides locally"),
+            format!("nightlies --provenance {bad} to rule out an image that doesn't match its commit"),
+            format!("nightlies --symbols {bad} to get the debug symbols of the bad nightly"),
+            format!("nightlies --conflicts-with <your branch> --range {good}..{bad} to see what the range shares with your work"),
+            format!("nightlies --context-set-base {good} to keep comparing against the good nightly"),
+        ];
+        if self.query.is_some() && !self.suspects().iter().any(|s| s.matches_query) {
+            steps.insert(
+                0,
+                String::from("No commit mentions the query, try a broader one or none"),
+            );
+        }
+        steps
+    }
+
+    /// The investigation as a Markdown document, with links to the nightlies and commits
+    ///
+    /// # Panics
+    /// - If writing to a string fails, which it doesn't
+    #[must_use]
+    pub fn render_markdown(&self) -> String {
+        let (good, bad) = (&self.good.sha, &self.bad.sha);
+        let mut doc = format!("# Investigation: symptom since {}\n\n", self.symptom_since);
+        for (label, nightly) in [
+            ("Last good nightly", self.good),
+            ("First bad nightly", self.bad),
+        ] {
+            writeln!(
+                doc,
+                "- {label}: [`{}`]({}), pushed {}",
+                tag_name(nightly),
+                tree_url(&nightly.sha),
+                nightly.estimated_last_pushed.to_rfc3339()
+            )
+            .unwrap();
+        }
+        writeln!(
+            doc,
+            "- Changes: [{good}...{bad}](https://github.com/DataDog/datadog-agent/compare/{good}...{bad}), {} commits",
+            self.commits.len()
+        )
+        .unwrap();
+
+        doc.push_str("\n## Suspects\n\n");
+        if let Some(query) = &self.query {
+            writeln!(doc, "Commits mentioning `{query}` come first.\n").unwrap();
+        }
+        if self.commits.is_empty() {
+            doc.push_str("No commits between the two nightlies.\n");
+        }
+        for (i, suspect) in self.suspects().iter().enumerate() {
+            writeln!(
+                doc,
+                "{}. [`{}`](https://github.com/DataDog/datadog-agent/commit/{}) {}{}",
+                i + 1,
+                suspect.sha,
+                suspect.sha,
+                suspect.subject,
+                if suspect.matches_query {
+                    " (matches the query)"
+                } else {
+                    ""
+                }
+            )
+            .unwrap();
+        }
+
+        doc.push_str("\n## Next steps\n\n");
+        for step in self.next_steps() {
+            writeln!(doc, "- {step}").unwrap();
+        }
+        doc
+    }
+}
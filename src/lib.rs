@@ -1,10 +1,12 @@
 #![warn(clippy::pedantic)]
 
 use thiserror::Error;
+#[cfg(feature = "client")]
 use tokio::task::JoinError;
 
 #[derive(Error, Debug)]
 pub enum NightlyError {
+    #[cfg(feature = "client")]
     #[error("Error while fetching tags from docker registry: {0}")]
     FetchError(#[from] reqwest::Error),
 
@@ -14,6 +16,14 @@ pub enum NightlyError {
     #[error("Json error: {0}")]
     JSONError(#[from] serde_json::Error),
 
+    #[cfg(feature = "client")]
+    #[error("Config error: {0}")]
+    ConfigError(#[from] toml::de::Error),
+
+    #[error("Config references the unset environment variable '{0}'")]
+    MissingEnvVar(String),
+
+    #[cfg(feature = "client")]
     #[error("Join error: {0}")]
     JoinError(#[from] JoinError),
 
@@ -25,7 +35,116 @@ pub enum NightlyError {
 
     #[error("Git Error: {0}")]
     GitError(String),
+
+    #[error("commit '{0}' not found on 'main'")]
+    CommitNotFound(String),
+
+    #[error("no nightly found for {0}")]
+    NightlyNotFound(String),
+
+    #[error("Invalid filter: {0}")]
+    FilterError(String),
+
+    #[error("Invalid format template: {0}")]
+    TemplateError(String),
+
+    #[error("Invalid path glob: {0}")]
+    GlobError(String),
+
+    #[error(transparent)]
+    InvalidNightly(#[from] nightly::RejectedNightly),
+
+    #[error("the image of {0} may not have been built from its commit")]
+    ProvenanceMismatch(String),
+
+    #[error("{0} warning(s) were logged and --strict was given")]
+    Degraded(usize),
+
+    #[error("Could not write {0}: {1}")]
+    OutputError(String, #[source] std::io::Error),
+}
+
+/// Exit codes of the nightlies binary, wrappers can rely on these staying the same
+pub mod exit_code {
+    pub const OK: i32 = 0;
+    /// Anything not covered below
+    pub const FAILURE: i32 = 1;
+    /// Invalid arguments or configuration
+    pub const USAGE: i32 = 2;
+    /// A requested nightly or commit doesn't exist
+    pub const NOT_FOUND: i32 = 3;
+    /// The docker registry could not be reached
+    pub const NETWORK: i32 = 4;
+    /// The datadog-agent checkout could not be used
+    pub const GIT: i32 = 5;
+    /// The cache (or another local file) could not be read or written
+    pub const CACHE: i32 = 6;
+    /// `--strict` ran into a warning
+    pub const DEGRADED: i32 = 7;
+    /// `--provenance` found an image that may not match its tag
+    pub const PROVENANCE: i32 = 8;
+    /// A file asked for on the command line (`--site`, `--feed`, ...) could not be written
+    pub const OUTPUT: i32 = 9;
+}
+
+impl NightlyError {
+    /// The exit code the binary uses when failing with this error
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            #[cfg(feature = "client")]
+            NightlyError::FetchError(_) => exit_code::NETWORK,
+            NightlyError::FileError(_) | NightlyError::JSONError(_) => exit_code::CACHE,
+            #[cfg(feature = "client")]
+            NightlyError::ConfigError(_) => exit_code::USAGE,
+            NightlyError::MissingEnvVar(_)
+            | NightlyError::DateParseError(_)
+            | NightlyError::FilterError(_)
+            | NightlyError::TemplateError(_)
+            | NightlyError::GlobError(_) => exit_code::USAGE,
+            NightlyError::GitError(_) => exit_code::GIT,
+            NightlyError::CommitNotFound(_) | NightlyError::NightlyNotFound(_) => {
+                exit_code::NOT_FOUND
+            }
+            #[cfg(feature = "client")]
+            NightlyError::JoinError(_) => exit_code::FAILURE,
+            NightlyError::GenericError(_) | NightlyError::InvalidNightly(_) => exit_code::FAILURE,
+            NightlyError::ProvenanceMismatch(_) => exit_code::PROVENANCE,
+            NightlyError::Degraded(_) => exit_code::DEGRADED,
+            NightlyError::OutputError(..) => exit_code::OUTPUT,
+        }
+    }
 }
 
+// The data model, tag parsing and report structuring, free of network, git and
+// filesystem access so a WASM dashboard can share them
+pub mod business_day;
+pub mod feed;
+pub mod format;
+pub mod ical;
+pub mod image;
+pub mod investigation;
 pub mod nightly;
+pub mod quarantine;
+pub mod query;
+pub mod resolve;
+pub mod site;
+
+#[cfg(feature = "client")]
+pub mod bench;
+#[cfg(feature = "client")]
+pub mod build;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "client")]
+pub mod config;
+#[cfg(feature = "client")]
+pub mod crash_report;
+#[cfg(feature = "client")]
+pub mod man;
+#[cfg(feature = "client")]
 pub mod repo;
+#[cfg(feature = "client")]
+pub mod snapshot;
+#[cfg(feature = "client")]
+pub mod symbols;
@@ -0,0 +1,34 @@
+use clap_mangen::Man;
+use roff::{roman, Roff};
+
+// The man page is rendered by clap_mangen from the clap definition of the CLI, with an
+// exit status section clap doesn't know about
+
+/// Renders a section 1 man page for the given command, listing the given exit codes
+///
+/// # Panics
+/// - If writing to a buffer fails, which it doesn't
+#[must_use]
+pub fn render_man_page(cmd: clap::Command, exit_codes: &[(i32, &str)]) -> String {
+    let title = cmd.get_name().to_uppercase();
+    let man = Man::new(cmd).title(title);
+    let mut page = Vec::new();
+    man.render_title(&mut page).unwrap();
+    man.render_name_section(&mut page).unwrap();
+    man.render_synopsis_section(&mut page).unwrap();
+    man.render_description_section(&mut page).unwrap();
+    man.render_options_section(&mut page).unwrap();
+    if !exit_codes.is_empty() {
+        let mut exit_status = Roff::new();
+        exit_status.control("SH", ["EXIT STATUS"]);
+        for (code, meaning) in exit_codes {
+            exit_status
+                .control("TP", [])
+                .text([roman(code.to_string())])
+                .text([roman(*meaning)]);
+        }
+        exit_status.to_writer(&mut page).unwrap();
+    }
+    man.render_version_section(&mut page).unwrap();
+    String::from_utf8(page).unwrap()
+}
@@ -1,17 +1,45 @@
-use crate::{repo::get_commit_timestamp, NightlyError};
-use chrono::{DateTime, Utc};
-use once_cell::sync::Lazy;
-use reqwest;
+use crate::{image::pipeline_from_labels, NightlyError};
+use chrono::{DateTime, Duration, NaiveTime, Timelike, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::{
-    collections::HashMap,
-    fs,
-    path::{Path, PathBuf},
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "client")]
+use std::{collections::HashSet, time::Instant};
+use tracing::{debug, info};
+
+mod builder;
+#[cfg(feature = "client")]
+mod cache;
+#[cfg(feature = "client")]
+mod enrich;
+#[cfg(feature = "client")]
+mod registry;
+mod template;
+
+pub use builder::{NightlyBuilder, RejectedNightly, ValidationError};
+#[cfg(feature = "client")]
+#[allow(deprecated)]
+pub use cache::{load_db_from_cache, save_db_to_cache};
+#[cfg(feature = "client")]
+pub(crate) use cache::{load_nightlies, save_nightlies};
+#[cfg(feature = "client")]
+pub use enrich::{
+    enrich_go_versions, enrich_incremental_commit_counts, enrich_nightlies, tags_to_nightlies,
+    tags_to_nightlies_checked, tags_to_nightlies_in, tags_to_nightlies_parallel_in,
 };
-use tracing::{debug, info, warn};
+#[cfg(feature = "client")]
+pub(crate) use registry::{
+    archive_deleted, fetch_registry_tags, http_send, redact_url, registry_tag, registry_tag_exists,
+};
+#[cfg(feature = "client")]
+#[allow(deprecated)]
+pub use registry::{
+    archive_deleted_nightlies, fetch_docker_registry_tags, tag_exists, DEFAULT_URL,
+};
+
+pub use template::NightlyTemplate;
 
-const URL: &str = "https://hub.docker.com/v2/repositories/datadog/agent-dev/tags";
+/// Log target of the registry requests, `--trace-http` enables it at trace level
+pub const HTTP_LOG_TARGET: &str = "nightlies::http";
 
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 pub struct Tag {
@@ -19,10 +47,41 @@ pub struct Tag {
     #[serde(rename = "tag_last_pushed")]
     pub last_pushed: DateTime<Utc>,
     pub digest: String,
+    /// When the image was last pulled, refreshed on every registry fetch
+    #[serde(rename = "tag_last_pulled", default)]
+    pub last_pulled: Option<DateTime<Utc>>,
+    /// How many times the image was pulled, the registry only sometimes tells
+    #[serde(default)]
+    pub pull_count: Option<u64>,
+}
+
+/// Agent build flavor of a nightly image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "client", derive(clap::ValueEnum))]
+pub enum Flavor {
+    /// The regular agent, in its python and JMX variants
+    Agent,
+    Iot,
+    Dogstatsd,
+    Heroku,
 }
 
 impl Tag {
-    fn get_sha(&self) -> Option<&str> {
+    /// The flavor of the image, from the suffixes after the sha, e.g. `nightly-main-<sha>-iot`
+    #[must_use]
+    pub fn flavor(&self) -> Flavor {
+        let mut suffixes = self.name.split('-').skip(3);
+        suffixes
+            .find_map(|suffix| match suffix {
+                "iot" => Some(Flavor::Iot),
+                "dogstatsd" => Some(Flavor::Dogstatsd),
+                "heroku" => Some(Flavor::Heroku),
+                _ => None,
+            })
+            .unwrap_or(Flavor::Agent)
+    }
+
+    pub(crate) fn get_sha(&self) -> Option<&str> {
         if let Some(sha) = self.name.split('-').nth(2) {
             if sha.len() == 8 {
                 return Some(sha);
@@ -43,13 +102,100 @@ pub struct Nightly {
     pub py3_jmx: Option<Tag>,
     pub py2_jmx: Option<Tag>,
     pub jmx: Option<Tag>,
+
+    /// Set once the registry no longer serves this nightly's tags
+    /// The metadata is kept around, but the image can no longer be pulled
+    #[serde(default)]
+    pub archived: bool,
+
+    /// How many times looking up this nightly's commit in the agent repo failed
+    #[serde(default)]
+    pub resolve_attempts: u32,
+
+    /// Sha of the nightly built right before this one, see `query::link_predecessors`
+    #[serde(default)]
+    pub predecessor_sha: Option<String>,
+
+    /// Number of commits this nightly adds over its predecessor
+    #[serde(default)]
+    pub incremental_commits: Option<usize>,
+
+    /// Labels of the image config, only fetched on request, see `image::fetch_image_labels`
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+
+    /// Tags of the other agent flavors (iot, dogstatsd, heroku) built from the same commit
+    #[serde(default)]
+    pub flavor_tags: Vec<Tag>,
+
+    /// Go toolchain version at this nightly's commit, from `.go-version` or go.mod
+    #[serde(default)]
+    pub go_version: Option<String>,
 }
 
-static CACHE_FILE: Lazy<PathBuf> = Lazy::new(|| {
-    // get a 'stable' temp dir that can be used to cache the results from previous runs
-    let dir = std::env::temp_dir();
-    dir.join("agent_nightlies.json")
-});
+impl Nightly {
+    /// The first tag that exists for this nightly, in order of preference
+    #[must_use]
+    pub fn first_valid_tag(&self) -> Option<&Tag> {
+        self.py3
+            .as_ref()
+            .or(self.py2.as_ref())
+            .or(self.py3_jmx.as_ref())
+            .or(self.py2_jmx.as_ref())
+            .or(self.jmx.as_ref())
+            .or(self.flavor_tags.first())
+    }
+
+    /// All tags of this nightly, the agent's in order of preference and then the other
+    /// flavors'
+    pub fn tags(&self) -> impl Iterator<Item = &Tag> {
+        [
+            &self.py3,
+            &self.py2,
+            &self.py3_jmx,
+            &self.py2_jmx,
+            &self.jmx,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&self.flavor_tags)
+    }
+
+    /// Whether an image of the given flavor was built for this nightly
+    #[must_use]
+    pub fn has_flavor(&self, flavor: Flavor) -> bool {
+        match flavor {
+            Flavor::Agent => [
+                &self.py3,
+                &self.py2,
+                &self.py3_jmx,
+                &self.py2_jmx,
+                &self.jmx,
+            ]
+            .into_iter()
+            .any(Option::is_some),
+            _ => self.flavor_tags.iter().any(|t| t.flavor() == flavor),
+        }
+    }
+
+    /// This nightly with only the images of the given flavor, None if it has none
+    #[must_use]
+    pub fn only_flavor(&self, flavor: Flavor) -> Option<Nightly> {
+        if !self.has_flavor(flavor) {
+            return None;
+        }
+        let mut nightly = self.clone();
+        nightly.flavor_tags.retain(|t| t.flavor() == flavor);
+        if flavor != Flavor::Agent {
+            nightly.py3 = None;
+            nightly.py2 = None;
+            nightly.py3_jmx = None;
+            nightly.py2_jmx = None;
+            nightly.jmx = None;
+        }
+        Some(nightly)
+    }
+}
 
 pub fn find_nightly_by_build_sha<'a, 'b>(
     nightlies: &'a [Nightly],
@@ -75,212 +221,225 @@ where
     tags.iter().filter(move |t| t.name.contains(build_sha))
 }
 
-/// Given a list of tags, find any tags that represent nightlies
-/// not already tracked in 'nightlies' and add them to 'nightlies'
-///
-/// # Errors
-/// - Errors if any of the tags cannot be parsed into a nightly
-/// - Errors if any of the tags are missing a sha
-/// - Errors if any of the tags are missing a timestamp
-pub fn enrich_nightlies(tags: &[Tag], nightlies: &mut Vec<Nightly>) -> Result<(), NightlyError> {
-    let initial_nightlies_len = nightlies.len();
-    let mut nightlies_from_tags: HashMap<String, Vec<Tag>> = HashMap::new();
-    for tag in tags {
-        let Some(sha) = tag.get_sha() else {
-            continue;
-        };
-        let entry = nightlies_from_tags
-            .entry(sha.to_string())
-            .or_insert_with(|| vec![]);
-        entry.push(tag.clone());
-    }
-
-    for (nightly_sha, tags_for_sha) in &nightlies_from_tags {
-        if !nightlies.iter_mut().any(|n| n.sha == *nightly_sha) {
-            let new_nightly = sha_and_tags_to_nightly(nightly_sha, tags_for_sha)?;
-            nightlies.push(new_nightly);
+/// Updates the pull metadata of the tracked nightlies' tags from freshly fetched tags
+pub fn refresh_pull_metadata(tags: &[Tag], nightlies: &mut [Nightly]) {
+    let fetched: HashMap<&str, &Tag> = tags.iter().map(|t| (t.name.as_str(), t)).collect();
+    for nightly in nightlies.iter_mut() {
+        let tracked = [
+            &mut nightly.py3,
+            &mut nightly.py2,
+            &mut nightly.py3_jmx,
+            &mut nightly.py2_jmx,
+            &mut nightly.jmx,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(nightly.flavor_tags.iter_mut());
+        for tag in tracked {
+            if let Some(fresh) = fetched.get(tag.name.as_str()) {
+                tag.last_pulled = fresh.last_pulled.or(tag.last_pulled);
+                tag.pull_count = fresh.pull_count.or(tag.pull_count);
+            }
         }
     }
+}
 
-    debug!(
-        "Added {} new nightlies from tags",
-        nightlies.len() - initial_nightlies_len
-    );
+/// Builds a nightly out of the tags of its sha, without a commit timestamp
+///
+/// # Errors
+/// - Errors if the tags don't make a valid nightly, see [`NightlyBuilder::build`]
+pub fn nightly_from_tags(sha: &str, tags: &[Tag]) -> Result<Nightly, NightlyError> {
+    Ok(Nightly::builder(sha).tags(tags.iter().cloned()).build()?)
+}
 
-    Ok(())
+/// Drops nightlies last pushed more than `retention_days` before `now`
+///
+/// Returns the number of nightlies that were removed
+pub fn prune_nightlies(
+    nightlies: &mut Vec<Nightly>,
+    retention_days: u32,
+    now: DateTime<Utc>,
+) -> usize {
+    let cutoff = now - Duration::days(i64::from(retention_days));
+    let initial_nightlies_len = nightlies.len();
+    nightlies.retain(|n| n.estimated_last_pushed >= cutoff);
+
+    let num_pruned = initial_nightlies_len - nightlies.len();
+    debug!("Pruned {num_pruned} nightlies older than {retention_days} days");
+    num_pruned
 }
 
-fn sha_and_tags_to_nightly(sha: &str, tags: &[Tag]) -> Result<Nightly, NightlyError> {
-    let mut py3 = None;
-    let mut py2 = None;
-    let mut py3_jmx = None;
-    let mut py2_jmx = None;
-    let mut jmx = None;
-    for tag in tags {
-        if tag.name.ends_with("-py3") {
-            py3 = Some(tag);
-        } else if tag.name.ends_with("-py2") {
-            py2 = Some(tag);
-        } else if tag.name.ends_with("-py3-jmx") {
-            py3_jmx = Some(tag);
-        } else if tag.name.ends_with("-py2-jmx") {
-            py2_jmx = Some(tag);
-        } else if tag.name.ends_with("-jmx") {
-            jmx = Some(tag);
-        }
-    }
-    let first_some = py3.or(py2).or(py3_jmx).or(py2_jmx).or(jmx);
-    if let Some(tag) = first_some {
-        let estimated_last_pushed = tag.last_pushed;
-
-        let sha_timestamp = match get_commit_timestamp(sha) {
-            Ok(timestamp) => Some(timestamp),
-            Err(e) => {
-                warn!("Error getting commit timestamp for nightly sha: {}", e);
-                None
-            }
-        };
-
-        Ok(Nightly {
-            sha: sha.to_string(),
-            estimated_last_pushed,
-            sha_timestamp,
-            py3: py3.cloned(),
-            py2: py2.cloned(),
-            py3_jmx: py3_jmx.cloned(),
-            py2_jmx: py2_jmx.cloned(),
-            jmx: jmx.cloned(),
-        })
-    } else {
-        Err(NightlyError::GenericError(format!(
-            "Missing tags for sha: {sha}"
-        )))
-    }
+/// When the next nightly is expected to be published, based on past publish times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NextNightlyEstimate {
+    pub expected_at: DateTime<Utc>,
+    /// Standard deviation of past publish times, used as the accuracy bound
+    pub std_dev: Duration,
 }
 
+/// Number of seconds in a day, the period of publish times
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Number of recent nightlies used to estimate the publish cadence
+const CADENCE_SAMPLE_SIZE: usize = 30;
+
+/// Estimates when the next nightly will be published from the time of day that
+/// the most recent nightlies were pushed at
+///
+/// Returns None if there are not enough nightlies to estimate from
 #[must_use]
-pub fn tags_to_nightlies(tags: &[Tag]) -> Vec<Nightly> {
-    let mut nightlies: HashMap<String, Vec<Tag>> = HashMap::new();
-    for tag in tags {
-        let Some(sha) = tag.get_sha() else {
-            continue;
-        };
-        let entry = nightlies.entry(sha.to_string()).or_insert_with(|| vec![]);
-        entry.push(tag.clone());
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn estimate_next_nightly(
+    nightlies: &[Nightly],
+    now: DateTime<Utc>,
+) -> Option<NextNightlyEstimate> {
+    let mut pushes: Vec<DateTime<Utc>> =
+        nightlies.iter().map(|n| n.estimated_last_pushed).collect();
+    pushes.sort_unstable_by(|a, b| b.cmp(a));
+    pushes.truncate(CADENCE_SAMPLE_SIZE);
+    if pushes.len() < 2 {
+        return None;
     }
 
-    let mut nightlies = nightlies
-        .into_iter()
-        .filter_map(|(sha, tags)| match sha_and_tags_to_nightly(&sha, &tags) {
-            Ok(nightly) => Some(nightly),
-            Err(e) => {
-                warn!("Error parsing nightly: {}", e);
-                None
-            }
+    // Times of day wrap around at midnight, so they are averaged as angles on a clock
+    // face: pushes at 23:50 and 00:10 average to midnight rather than to noon
+    let day = f64::from(SECONDS_PER_DAY);
+    let angles: Vec<f64> = pushes
+        .iter()
+        .map(|p| f64::from(p.num_seconds_from_midnight()) / day * std::f64::consts::TAU)
+        .collect();
+    let (sin, cos) = angles
+        .iter()
+        .fold((0.0, 0.0), |(sin, cos), a| (sin + a.sin(), cos + a.cos()));
+    let mean = (sin.atan2(cos) / std::f64::consts::TAU * day).rem_euclid(day);
+    // Spread of the shortest distances around the clock to the mean
+    let count = angles.len() as f64;
+    let variance = angles
+        .iter()
+        .map(|a| {
+            let offset = (a / std::f64::consts::TAU * day - mean + day / 2.0).rem_euclid(day);
+            (offset - day / 2.0).powi(2)
         })
-        .collect::<Vec<Nightly>>();
+        .sum::<f64>()
+        / count;
+    let std_dev = Duration::seconds(variance.sqrt() as i64);
+
+    let mean_time =
+        NaiveTime::from_num_seconds_from_midnight_opt((mean as u32) % SECONDS_PER_DAY, 0)?;
+    let latest_push = pushes[0];
+    let mut expected_at = (latest_push.date_naive() + Duration::days(1))
+        .and_time(mean_time)
+        .and_utc();
+    // A nightly that is later than usual is still expected until the bounds have passed
+    while expected_at + std_dev < now {
+        expected_at += Duration::days(1);
+    }
 
-    nightlies.sort_by(|a, b| b.estimated_last_pushed.cmp(&a.estimated_last_pushed));
+    Some(NextNightlyEstimate {
+        expected_at,
+        std_dev,
+    })
+}
 
-    nightlies
+/// Returns true if every character of `word` appears in `candidate`, in order
+#[cfg(feature = "client")]
+fn is_subsequence(word: &str, candidate: &str) -> bool {
+    let mut candidate_chars = candidate.chars();
+    word.chars().all(|c| candidate_chars.any(|cc| cc == c))
 }
 
-/// Fetches the first `num_pages` of results from the docker registry API
-/// Page size is hardcoded to 100
-///
-/// # Panics
-/// - Panics if unexpected data is returned from the docker registry api
+/// Produces shell completion candidates (tag names and shas) for the given word
 ///
-/// # Errors
-/// - Errors if there is a problem fetching data from the docker registry api
-pub async fn fetch_docker_registry_tags(num_pages: usize) -> Result<Vec<Tag>, NightlyError> {
-    let mut url = format!("{URL}?page_size=100&name=nightly-main-");
-
-    let mut tags: Vec<Tag> = Vec::new();
-    let mut num_pages_fetched = 0;
-    loop {
-        if num_pages_fetched >= num_pages {
+/// Candidates starting with `word` come first, followed by fuzzy matches that
+/// contain the characters of `word` in order. Matching stops once `deadline` has
+/// passed, returning whatever was found so far.
+#[cfg(feature = "client")]
+#[must_use]
+pub fn completion_candidates(nightlies: &[Nightly], word: &str, deadline: Instant) -> Vec<String> {
+    let mut prefix_matches = Vec::new();
+    let mut fuzzy_matches = Vec::new();
+    let mut seen = HashSet::new();
+    for nightly in nightlies {
+        if Instant::now() >= deadline {
+            debug!("Completion deadline reached, returning partial results");
             break;
         }
-
-        let response: Value = reqwest::get(&url).await?.json().await?;
-        let results = response["results"].as_array().unwrap();
-        let mut tag_results: Vec<Tag> = results
-            .iter()
-            .filter_map(|t| match serde_json::from_value::<Tag>(t.clone()) {
-                Ok(tag) => {
-                    if let Some(sha) = tag.name.split('-').nth(2) {
-                        // Skip the 'main' tag that has no sha
-                        // This floats around and isn't useful to us
-                        if sha.is_empty() {
-                            return None;
-                        }
-                    }
-
-                    Some(tag)
-                }
-                Err(e) => {
-                    warn!("Error parsing tag: {}", e);
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        tags.append(&mut tag_results);
-
-        if response["next"].is_null() {
-            break;
+        let tag_names = [
+            &nightly.py3,
+            &nightly.py2,
+            &nightly.py3_jmx,
+            &nightly.py2_jmx,
+            &nightly.jmx,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&nightly.flavor_tags)
+        .map(|t| t.name.as_str());
+        for candidate in std::iter::once(nightly.sha.as_str()).chain(tag_names) {
+            if !seen.insert(candidate) {
+                continue;
+            }
+            if candidate.starts_with(word) {
+                prefix_matches.push(candidate.to_string());
+            } else if is_subsequence(word, candidate) {
+                fuzzy_matches.push(candidate.to_string());
+            }
         }
-        url = response["next"].as_str().unwrap().to_string();
-        num_pages_fetched += 1;
     }
 
-    Ok(tags)
-}
-
-pub fn query_range(
-    nightlies: &[Nightly],
-    from_date: DateTime<Utc>,
-    to_date: Option<DateTime<Utc>>,
-) -> impl Iterator<Item = &Nightly> + '_ {
-    let r = nightlies.iter().filter(move |n| {
-        let nightly_timestamp = n.sha_timestamp.unwrap_or(n.estimated_last_pushed);
-        if let Some(to_date) = to_date {
-            nightly_timestamp <= to_date && nightly_timestamp >= from_date
-        } else {
-            nightly_timestamp >= from_date
-        }
-    });
-
-    r
+    prefix_matches.append(&mut fuzzy_matches);
+    prefix_matches
 }
 
 /// Print the given nightly and optionally all tags
 ///
-/// # Panics:
+/// # Panics
 /// - If the writer encounters an error
 /// - If the nightly is missing a valid image
 pub fn print<W>(mut writer: W, nightly: &Nightly, all_tags: bool, print_digest: bool)
 where
     W: std::io::Write,
 {
-    let first_valid_image = nightly
-        .py3
-        .as_ref()
-        .or(nightly.py2.as_ref())
-        .or(nightly.py3_jmx.as_ref())
-        .or(nightly.py2_jmx.as_ref())
-        .or(nightly.jmx.as_ref())
-        .unwrap();
-    writeln!(
+    let first_valid_image = nightly.first_valid_tag().unwrap();
+    write!(
         writer,
         "Nightly: datadog/agent-dev:{},\t",
         first_valid_image.name
     )
     .expect("Error writing to writer");
-    if let Some(sha_timestamp) = nightly.sha_timestamp {
-        writeln!(writer, "SHA Timestamp: {}\t", sha_timestamp.to_rfc3339())
+    if let Some(count) = nightly.incremental_commits {
+        write!(writer, "+{count} commits").expect("Error writing to writer");
+    }
+    writeln!(writer).expect("Error writing to writer");
+    if nightly.archived {
+        writeln!(writer, "Archived: tags were deleted from the registry\t")
+            .expect("Error writing nightly to writer");
+    }
+    let commit_timestamp = nightly
+        .sha_timestamp
+        .map_or_else(|| String::from("unknown"), |t| t.to_rfc3339());
+    writeln!(writer, "Commit Timestamp: {commit_timestamp}\t")
+        .expect("Error writing nightly to writer");
+    writeln!(
+        writer,
+        "Push Timestamp: {}\t",
+        nightly.estimated_last_pushed.to_rfc3339()
+    )
+    .expect("Error writing nightly to writer");
+    if let Some(go_version) = &nightly.go_version {
+        writeln!(writer, "Go Version: {go_version}\t").expect("Error writing nightly to writer");
+    }
+    if let Some(last_pulled) = first_valid_image.last_pulled {
+        writeln!(writer, "Last Pulled: {}\t", last_pulled.to_rfc3339())
             .expect("Error writing nightly to writer");
     }
+    if let Some(pull_count) = first_valid_image.pull_count {
+        writeln!(writer, "Pulls: {pull_count}\t").expect("Error writing nightly to writer");
+    }
     writeln!(
         writer,
         "GitHub URL: https://github.com/DataDog/datadog-agent/tree/{}",
@@ -304,6 +463,113 @@ where
         if let Some(tag) = &nightly.py2 {
             print_tag(&mut writer, tag, all_tags, print_digest);
         }
+        for tag in &nightly.flavor_tags {
+            print_tag(&mut writer, tag, all_tags, print_digest);
+        }
+    }
+}
+
+/// Prints a nightly on a single line through a `--format` template
+///
+/// # Panics
+/// - If writing to the writer fails
+pub fn print_formatted<W>(mut writer: W, nightly: &Nightly, template: &NightlyTemplate)
+where
+    W: std::io::Write,
+{
+    writeln!(writer, "{}", template.render(nightly)).expect("Error writing to writer");
+}
+
+/// Prints the nightly as one labeled line per fact, without alignment or punctuation
+/// that screen readers would read out
+///
+/// # Panics
+/// - If the writer encounters an error
+/// - If the nightly is missing a valid image
+pub fn print_plain<W>(mut writer: W, nightly: &Nightly, all_tags: bool, print_digest: bool)
+where
+    W: std::io::Write,
+{
+    let first_valid_image = nightly.first_valid_tag().unwrap();
+    writeln!(writer, "URI: datadog/agent-dev:{}", first_valid_image.name)
+        .expect("Error writing nightly to writer");
+    if let Some(count) = nightly.incremental_commits {
+        writeln!(writer, "Commits added: {count}").expect("Error writing nightly to writer");
+    }
+    if nightly.archived {
+        writeln!(writer, "Archived: tags were deleted from the registry")
+            .expect("Error writing nightly to writer");
+    }
+    let commit_timestamp = nightly
+        .sha_timestamp
+        .map_or_else(|| String::from("unknown"), |t| t.to_rfc3339());
+    writeln!(writer, "Committed: {commit_timestamp}").expect("Error writing nightly to writer");
+    writeln!(
+        writer,
+        "Pushed: {}",
+        nightly.estimated_last_pushed.to_rfc3339()
+    )
+    .expect("Error writing nightly to writer");
+    if let Some(go_version) = &nightly.go_version {
+        writeln!(writer, "Go version: {go_version}").expect("Error writing nightly to writer");
+    }
+    if let Some(last_pulled) = first_valid_image.last_pulled {
+        writeln!(writer, "Last pulled: {}", last_pulled.to_rfc3339())
+            .expect("Error writing nightly to writer");
+    }
+    if let Some(pull_count) = first_valid_image.pull_count {
+        writeln!(writer, "Pulls: {pull_count}").expect("Error writing nightly to writer");
+    }
+    writeln!(
+        writer,
+        "GitHub URL: https://github.com/DataDog/datadog-agent/tree/{}",
+        nightly.sha,
+    )
+    .expect("Error writing nightly to writer");
+
+    if all_tags {
+        for tag in [
+            &nightly.jmx,
+            &nightly.py3_jmx,
+            &nightly.py2_jmx,
+            &nightly.py3,
+            &nightly.py2,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&nightly.flavor_tags)
+        {
+            writeln!(writer, "Tag: datadog/agent-dev:{}", tag.name)
+                .expect("Error writing tag to writer");
+            writeln!(writer, "Tag pushed: {}", tag.last_pushed.to_rfc3339())
+                .expect("Error writing tag to writer");
+            if print_digest {
+                writeln!(writer, "Tag digest: {}", tag.digest)
+                    .expect("Error writing tag to writer");
+            }
+        }
+    }
+}
+
+/// Prints the image labels of the nightly, if they were fetched
+///
+/// # Panics
+/// - If the writer encounters an error
+pub fn print_labels<W>(mut writer: W, nightly: &Nightly)
+where
+    W: std::io::Write,
+{
+    let Some(labels) = &nightly.labels else {
+        return;
+    };
+    if let Some(pipeline) = pipeline_from_labels(labels) {
+        writeln!(writer, "Built by {pipeline}").expect("Error writing labels to writer");
+    }
+    if labels.is_empty() {
+        writeln!(writer, "Labels: none").expect("Error writing labels to writer");
+    }
+    for (key, value) in labels {
+        writeln!(writer, "Label {key}:\t{value}").expect("Error writing labels to writer");
     }
 }
 
@@ -327,42 +593,3 @@ where
         writeln!(writer).expect("Error writing tag to writer");
     }
 }
-
-/// Saves the given nightlies to a cache file
-///
-/// # Errors
-/// - Errors if the cache file cannot be written to
-/// - Errors if the nightlies cannot be serialized to json
-pub fn save_db_to_cache(nightlies: &[Nightly]) -> Result<(), crate::NightlyError> {
-    let file: &Path = CACHE_FILE.as_path();
-    fs::write(file, serde_json::to_string_pretty(&nightlies)?)?;
-    debug!("Updated nightlies saved to {file}", file = file.display());
-    Ok(())
-}
-
-/// Loads nightlies from a cache file
-///
-/// # Errors
-/// - Errors if the cache file cannot be read
-/// - Errors if the nightlies cannot be deserialized from json
-pub fn load_db_from_cache() -> Result<Vec<Nightly>, crate::NightlyError> {
-    let file: &Path = CACHE_FILE.as_path();
-    debug!(
-        "Reading cached nightlies from {file}",
-        file = file.display()
-    );
-    match fs::read_to_string(file) {
-        Ok(file_content) => {
-            let tags: Vec<Nightly> = serde_json::from_str(&file_content)?;
-            Ok(tags)
-        }
-        Err(e) => {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                // No cache file found, this is not a concerning error
-            } else {
-                warn!("Cache file reading error: {}", e);
-            }
-            Ok(Vec::new())
-        }
-    }
-}
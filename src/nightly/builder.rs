@@ -0,0 +1,156 @@
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
+
+use super::{Flavor, Nightly, Tag};
+
+/// How many hours later than its push a nightly's commit may claim to be, as committer
+/// clocks can be off
+const MAX_COMMIT_CLOCK_SKEW_HOURS: i64 = 24;
+
+/// Why tags could not be turned into a nightly
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationError {
+    #[error("'{0}' is not an 8 character hex sha")]
+    InvalidSha(String),
+
+    #[error("tag {0} is not a tag of this sha")]
+    ForeignTag(String),
+
+    #[error("none of the tags is a nightly image")]
+    NoImage,
+
+    #[error("committed at {committed}, after the image was pushed at {pushed}")]
+    CommittedAfterPush {
+        committed: DateTime<Utc>,
+        pushed: DateTime<Utc>,
+    },
+}
+
+/// Tags that failed to make a nightly, and why
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid nightly {sha}: {error}")]
+pub struct RejectedNightly {
+    pub sha: String,
+    /// Names of the tags the nightly was built from
+    pub tags: Vec<String>,
+    pub error: ValidationError,
+}
+
+/// Builds a [`Nightly`] out of the tags of a sha, validating them, see [`Nightly::builder`]
+#[derive(Debug, Clone)]
+pub struct NightlyBuilder {
+    sha: String,
+    tags: Vec<Tag>,
+    sha_timestamp: Option<DateTime<Utc>>,
+}
+
+impl Nightly {
+    /// Starts building the nightly of the given sha
+    #[must_use]
+    pub fn builder(sha: impl Into<String>) -> NightlyBuilder {
+        NightlyBuilder {
+            sha: sha.into(),
+            tags: Vec::new(),
+            sha_timestamp: None,
+        }
+    }
+}
+
+impl NightlyBuilder {
+    #[must_use]
+    pub fn tag(mut self, tag: Tag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+
+    #[must_use]
+    pub fn tags(mut self, tags: impl IntoIterator<Item = Tag>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    /// When the nightly's commit was made, unknown by default
+    #[must_use]
+    pub fn sha_timestamp(mut self, sha_timestamp: DateTime<Utc>) -> Self {
+        self.sha_timestamp = Some(sha_timestamp);
+        self
+    }
+
+    fn reject(&self, error: ValidationError) -> RejectedNightly {
+        RejectedNightly {
+            sha: self.sha.clone(),
+            tags: self.tags.iter().map(|t| t.name.clone()).collect(),
+            error,
+        }
+    }
+
+    /// Builds the nightly, tags with an unknown suffix are left out
+    ///
+    /// # Errors
+    /// - Errors if the sha is not 8 hex characters
+    /// - Errors if a tag names another sha
+    /// - Errors if none of the tags is a nightly image
+    /// - Errors if the commit is much newer than the image
+    pub fn build(self) -> Result<Nightly, RejectedNightly> {
+        if self.sha.len() != 8 || !self.sha.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.reject(ValidationError::InvalidSha(self.sha.clone())));
+        }
+        if let Some(foreign) = self
+            .tags
+            .iter()
+            .find(|t| t.get_sha() != Some(self.sha.as_str()))
+        {
+            return Err(self.reject(ValidationError::ForeignTag(foreign.name.clone())));
+        }
+
+        let mut nightly = Nightly {
+            sha: self.sha.clone(),
+            estimated_last_pushed: DateTime::<Utc>::MIN_UTC,
+            sha_timestamp: self.sha_timestamp,
+            py3: None,
+            py2: None,
+            py3_jmx: None,
+            py2_jmx: None,
+            jmx: None,
+            archived: false,
+            resolve_attempts: 0,
+            predecessor_sha: None,
+            incremental_commits: None,
+            labels: None,
+            flavor_tags: Vec::new(),
+            go_version: None,
+        };
+        for tag in &self.tags {
+            let slot = if tag.flavor() != Flavor::Agent {
+                if !nightly.flavor_tags.iter().any(|t| t.name == tag.name) {
+                    nightly.flavor_tags.push(tag.clone());
+                }
+                continue;
+            } else if tag.name.ends_with("-py3") {
+                &mut nightly.py3
+            } else if tag.name.ends_with("-py2") {
+                &mut nightly.py2
+            } else if tag.name.ends_with("-py3-jmx") {
+                &mut nightly.py3_jmx
+            } else if tag.name.ends_with("-py2-jmx") {
+                &mut nightly.py2_jmx
+            } else if tag.name.ends_with("-jmx") {
+                &mut nightly.jmx
+            } else {
+                continue;
+            };
+            *slot = Some(tag.clone());
+        }
+
+        let Some(pushed) = nightly.first_valid_tag().map(|t| t.last_pushed) else {
+            return Err(self.reject(ValidationError::NoImage));
+        };
+        nightly.estimated_last_pushed = pushed;
+        if let Some(committed) = self.sha_timestamp {
+            if committed > pushed + Duration::hours(MAX_COMMIT_CLOCK_SKEW_HOURS) {
+                return Err(self.reject(ValidationError::CommittedAfterPush { committed, pushed }));
+            }
+        }
+        Ok(nightly)
+    }
+}
@@ -0,0 +1,58 @@
+use std::{fs, path::Path};
+
+use tracing::{debug, warn};
+
+use super::Nightly;
+use crate::{client::NightliesClient, NightlyError};
+
+/// Saves the given nightlies to a cache file
+///
+/// # Errors
+/// - Errors if the cache file cannot be written to
+/// - Errors if the nightlies cannot be serialized to json
+#[deprecated(note = "use `NightliesClient::save_cache`")]
+pub fn save_db_to_cache(nightlies: &[Nightly]) -> Result<(), crate::NightlyError> {
+    NightliesClient::from_env().save_cache(nightlies)
+}
+
+pub(crate) fn save_nightlies(file: &Path, nightlies: &[Nightly]) -> Result<(), NightlyError> {
+    // Written aside and moved in place, so a concurrent reader never sees half a cache
+    // The name is unique to the process, so a background refresh saving at the same time
+    // can't write into the same file
+    let partial = file.with_extension(format!("json.{}.partial", std::process::id()));
+    fs::write(&partial, serde_json::to_string_pretty(&nightlies)?)?;
+    fs::rename(&partial, file)?;
+    debug!("Updated nightlies saved to {file}", file = file.display());
+    Ok(())
+}
+
+/// Loads nightlies from a cache file
+///
+/// # Errors
+/// - Errors if the cache file cannot be read
+/// - Errors if the nightlies cannot be deserialized from json
+#[deprecated(note = "use `NightliesClient::load_cache`")]
+pub fn load_db_from_cache() -> Result<Vec<Nightly>, crate::NightlyError> {
+    NightliesClient::from_env().load_cache()
+}
+
+pub(crate) fn load_nightlies(file: &Path) -> Result<Vec<Nightly>, NightlyError> {
+    debug!(
+        "Reading cached nightlies from {file}",
+        file = file.display()
+    );
+    match fs::read_to_string(file) {
+        Ok(file_content) => {
+            let tags: Vec<Nightly> = serde_json::from_str(&file_content)?;
+            Ok(tags)
+        }
+        Err(e) => {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                // No cache file found, this is not a concerning error
+            } else {
+                warn!("Cache file reading error: {}", e);
+            }
+            Ok(Vec::new())
+        }
+    }
+}
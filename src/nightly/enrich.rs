@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use tracing::{debug, warn};
+
+use super::{Nightly, RejectedNightly, Tag};
+use crate::{
+    query::group_untracked_tags,
+    repo::{
+        count_commits_between, default_agent_repo_path, find_commit_timestamp_in,
+        get_commit_timestamp_in, go_version_at, open_agent_repo, warm_main_graph,
+    },
+};
+
+/// Given a list of tags, find any tags that represent nightlies
+/// not already tracked in 'nightlies' and add them to 'nightlies'
+/// Tracked nightlies whose commit wasn't found before are looked up again, in the
+/// checkout at `repo_path`
+/// Returns the nightlies whose tags failed validation, they are left out
+pub fn enrich_nightlies(
+    repo_path: &Path,
+    tags: &[Tag],
+    nightlies: &mut Vec<Nightly>,
+) -> Vec<RejectedNightly> {
+    let initial_nightlies_len = nightlies.len();
+    let nightlies_from_tags = group_untracked_tags(tags, nightlies);
+    let has_unresolved = nightlies.iter().any(|n| n.sha_timestamp.is_none());
+    if nightlies_from_tags.is_empty() && !has_unresolved {
+        return Vec::new();
+    }
+
+    let repo = open_agent_repo(repo_path);
+    if let Some(repo) = &repo {
+        retry_unresolved_nightlies(repo, nightlies);
+    }
+    let mut rejected = Vec::new();
+    let groups: Vec<_> = nightlies_from_tags.into_iter().collect();
+    for result in shas_and_tags_to_nightlies_parallel(repo.as_ref(), &groups) {
+        match result {
+            Ok(new_nightly) => nightlies.push(new_nightly),
+            Err(e) => rejected.push(e),
+        }
+    }
+
+    debug!(
+        "Added {} new nightlies from tags, rejected {}",
+        nightlies.len() - initial_nightlies_len,
+        rejected.len()
+    );
+
+    rejected
+}
+
+/// Computes how many commits each nightly adds over its linked predecessor, for the
+/// nightlies that don't have a count yet, from the checkout at `repo_path`
+pub fn enrich_incremental_commit_counts(repo_path: &Path, nightlies: &mut [Nightly]) {
+    let missing: Vec<(usize, String)> = nightlies
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| n.incremental_commits.is_none())
+        .filter_map(|(i, n)| n.predecessor_sha.clone().map(|prev| (i, prev)))
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    let Some(repo) = open_agent_repo(repo_path) else {
+        return;
+    };
+    for (i, prev_sha) in missing {
+        match count_commits_between(&repo, &prev_sha, &nightlies[i].sha) {
+            Ok(count) => nightlies[i].incremental_commits = Some(count),
+            Err(e) => debug!(
+                "Could not count commits of nightly {}: {}",
+                nightlies[i].sha, e
+            ),
+        }
+    }
+}
+
+/// Reads the Go toolchain version of the resolved nightlies that don't have one yet, from
+/// the checkout at `repo_path`
+pub fn enrich_go_versions(repo_path: &Path, nightlies: &mut [Nightly]) {
+    let missing = |n: &&mut Nightly| n.go_version.is_none() && n.sha_timestamp.is_some();
+    if !nightlies.iter_mut().any(|n| missing(&n)) {
+        return;
+    }
+
+    let Some(repo) = open_agent_repo(repo_path) else {
+        return;
+    };
+    for nightly in nightlies.iter_mut().filter(missing) {
+        match go_version_at(&repo, &nightly.sha) {
+            Ok(version) => nightly.go_version = version,
+            Err(e) => debug!(
+                "Could not read the Go version of nightly {}: {}",
+                nightly.sha, e
+            ),
+        }
+    }
+}
+
+/// Retries looking up the commit timestamp of nightlies that don't have one, e.g. because
+/// the agent checkout was stale when they were first seen
+/// Failed lookups are counted in `resolve_attempts` to surface nightlies that never resolve
+fn retry_unresolved_nightlies(repo: &gix::Repository, nightlies: &mut [Nightly]) {
+    for nightly in nightlies.iter_mut().filter(|n| n.sha_timestamp.is_none()) {
+        match find_commit_timestamp_in(repo, &nightly.sha) {
+            Ok(Some(timestamp)) => {
+                debug!("Resolved commit of nightly {}", nightly.sha);
+                nightly.sha_timestamp = Some(timestamp);
+            }
+            Ok(None) => nightly.resolve_attempts += 1,
+            Err(e) => warn!("Error looking up commit of nightly {}: {}", nightly.sha, e),
+        }
+    }
+}
+
+/// Builds a nightly out of its tags, looking up the commit timestamp if a repo is given
+fn sha_and_tags_to_nightly(
+    repo: Option<&gix::Repository>,
+    sha: &str,
+    tags: &[Tag],
+) -> Result<Nightly, RejectedNightly> {
+    let mut builder = Nightly::builder(sha).tags(tags.iter().cloned());
+    let mut resolve_attempts = 0;
+    if let Some(repo) = repo {
+        match get_commit_timestamp_in(repo, sha) {
+            Ok(timestamp) => builder = builder.sha_timestamp(timestamp),
+            Err(e) => {
+                warn!("Error getting commit timestamp for nightly sha: {}", e);
+                resolve_attempts = 1;
+            }
+        }
+    }
+    let mut nightly = builder.build()?;
+    nightly.resolve_attempts = resolve_attempts;
+    Ok(nightly)
+}
+
+/// Like [`sha_and_tags_to_nightly`] for each sha and its tags, spreading the commit
+/// lookups over threads when there is a repo, each thread with its own handle on it
+fn shas_and_tags_to_nightlies_parallel(
+    repo: Option<&gix::Repository>,
+    groups: &[(String, Vec<Tag>)],
+) -> Vec<Result<Nightly, RejectedNightly>> {
+    let Some(repo) = repo.filter(|_| groups.len() > 1) else {
+        return groups
+            .iter()
+            .map(|(sha, tags)| sha_and_tags_to_nightly(repo, sha, tags))
+            .collect();
+    };
+    if let Err(e) = warm_main_graph(repo) {
+        debug!("Could not build the graph of main ahead of lookups: {}", e);
+    }
+
+    let shared = repo.clone().into_sync();
+    let workers = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+    let chunk_size = groups.len().div_ceil(workers).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = groups
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let shared = &shared;
+                scope.spawn(move || {
+                    let repo = shared.to_thread_local();
+                    chunk
+                        .iter()
+                        .map(|(sha, tags)| sha_and_tags_to_nightly(Some(&repo), sha, tags))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+/// Builds the nightlies of the given tags, newest first, warning about the tags that
+/// don't make a valid nightly, see [`tags_to_nightlies_checked`]
+/// Commits are looked up in the checkout at its default location
+#[must_use]
+pub fn tags_to_nightlies(tags: &[Tag]) -> Vec<Nightly> {
+    let repo = open_agent_repo(&default_agent_repo_path());
+    warn_rejected(tags_to_nightlies_with(repo.as_ref(), tags, false))
+}
+
+/// Like [`tags_to_nightlies`], looking up commits in the given checkout
+#[must_use]
+pub fn tags_to_nightlies_in(repo: &gix::Repository, tags: &[Tag]) -> Vec<Nightly> {
+    warn_rejected(tags_to_nightlies_with(Some(repo), tags, false))
+}
+
+/// Like [`tags_to_nightlies_in`], looking up the commits of several nightlies at once
+#[must_use]
+pub fn tags_to_nightlies_parallel_in(repo: &gix::Repository, tags: &[Tag]) -> Vec<Nightly> {
+    warn_rejected(tags_to_nightlies_with(Some(repo), tags, true))
+}
+
+fn warn_rejected((nightlies, rejected): (Vec<Nightly>, Vec<RejectedNightly>)) -> Vec<Nightly> {
+    for rejected in rejected {
+        warn!("Error parsing nightly: {}", rejected);
+    }
+    nightlies
+}
+
+/// Builds the nightlies of the given tags, newest first, along with the nightlies whose
+/// tags failed validation, looking up commits in the checkout at its default location
+#[must_use]
+pub fn tags_to_nightlies_checked(tags: &[Tag]) -> (Vec<Nightly>, Vec<RejectedNightly>) {
+    tags_to_nightlies_with(
+        open_agent_repo(&default_agent_repo_path()).as_ref(),
+        tags,
+        false,
+    )
+}
+
+fn tags_to_nightlies_with(
+    repo: Option<&gix::Repository>,
+    tags: &[Tag],
+    parallel: bool,
+) -> (Vec<Nightly>, Vec<RejectedNightly>) {
+    let groups: Vec<_> = group_untracked_tags(tags, &[]).into_iter().collect();
+    let results = if parallel {
+        shas_and_tags_to_nightlies_parallel(repo, &groups)
+    } else {
+        groups
+            .iter()
+            .map(|(sha, tags)| sha_and_tags_to_nightly(repo, sha, tags))
+            .collect()
+    };
+    let mut nightlies = Vec::new();
+    let mut rejected = Vec::new();
+    for result in results {
+        match result {
+            Ok(nightly) => nightlies.push(nightly),
+            Err(e) => rejected.push(e),
+        }
+    }
+    nightlies.sort_by_key(|n| std::cmp::Reverse(n.estimated_last_pushed));
+    rejected.sort_by(|a, b| a.sha.cmp(&b.sha));
+
+    (nightlies, rejected)
+}
@@ -0,0 +1,303 @@
+use std::{collections::HashSet, sync::Arc, time::Instant};
+
+use chrono::Utc;
+use serde_json::Value;
+use tracing::{debug, info, trace, warn};
+
+use super::{Nightly, Tag, HTTP_LOG_TARGET};
+use crate::{client::NightliesClient, quarantine::QuarantinedEntry, NightlyError};
+
+/// Pages fetched at the same time, so many pages don't flood the registry
+const MAX_CONCURRENT_PAGES: usize = 8;
+
+/// Tags checked at the same time when looking for deleted nightlies
+const MAX_CONCURRENT_TAG_CHECKS: usize = 8;
+
+/// The docker hub tags endpoint of the agent-dev repository
+pub const DEFAULT_URL: &str = "https://hub.docker.com/v2/repositories/datadog/agent-dev/tags";
+
+/// Hides any credentials embedded in the url
+pub(crate) fn redact_url(url: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(url) else {
+        return String::from("<unparseable url>");
+    };
+    if !url.username().is_empty() {
+        let _ = url.set_username("REDACTED");
+    }
+    if url.password().is_some() {
+        let _ = url.set_password(Some("REDACTED"));
+    }
+    url.to_string()
+}
+
+/// Sends the request, tracing its outcome and timing
+/// Headers are never traced, so authorization tokens stay out of the logs
+pub(crate) async fn http_send(
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let start = Instant::now();
+    let response = client.execute(request).await;
+    match &response {
+        Ok(response) => trace!(
+            target: HTTP_LOG_TARGET,
+            "{method} {} -> {} in {:?}",
+            redact_url(&url),
+            response.status(),
+            start.elapsed()
+        ),
+        Err(e) => trace!(
+            target: HTTP_LOG_TARGET,
+            "{method} {} failed in {:?}: {}",
+            redact_url(&url),
+            start.elapsed(),
+            e.to_string().replace(&url, &redact_url(&url))
+        ),
+    }
+    response
+}
+
+/// GETs the url, tracing the request's outcome and timing
+async fn http_get(url: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let client = reqwest::Client::new();
+    let request = client.get(url).build()?;
+    http_send(&client, request).await
+}
+
+/// Fetches the first `num_pages` of results from the docker registry API
+/// Page size is hardcoded to 100, pages after the first are fetched concurrently, at most
+/// 8 at a time
+///
+/// # Panics
+/// - Panics if unexpected data is returned from the docker registry api
+///
+/// # Errors
+/// - Errors if there is a problem fetching data from the docker registry api
+#[deprecated(note = "use `NightliesClient::fetch_tags`")]
+pub async fn fetch_docker_registry_tags(num_pages: usize) -> Result<Vec<Tag>, NightlyError> {
+    NightliesClient::from_env().fetch_tags(num_pages).await
+}
+
+pub(crate) async fn fetch_registry_tags(
+    base_url: &str,
+    num_pages: usize,
+) -> Result<(Vec<Tag>, Vec<QuarantinedEntry>), NightlyError> {
+    if num_pages == 0 {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let first_url = format!("{base_url}?page_size=100&name=nightly-main-");
+    let first: Value = http_get(&first_url).await?.json().await?;
+    let (mut tags, mut quarantined) = tags_from_page(&first);
+    let Some(next) = first["next"].as_str().filter(|_| num_pages > 1) else {
+        return Ok((tags, quarantined));
+    };
+
+    // The page urls only differ in their page number, so once the first page tells how
+    // many there are the rest are fetched concurrently, a few at a time
+    let page_size = first["results"].as_array().map_or(0, Vec::len).max(1);
+    let last_page = first["count"]
+        .as_u64()
+        .and_then(|count| usize::try_from(count).ok())
+        .map_or(num_pages, |count| count.div_ceil(page_size).min(num_pages));
+    let next = reqwest::Url::parse(next).map_err(|e| {
+        NightlyError::GenericError(format!("Unexpected next page url '{next}': {e}"))
+    })?;
+    let permits = Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_PAGES));
+    let pages: Vec<_> = (2..=last_page)
+        .map(|page| {
+            let url = page_url(&next, page);
+            let permits = Arc::clone(&permits);
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await.map_err(|e| {
+                    NightlyError::GenericError(format!("Could not fetch page {page}: {e}"))
+                })?;
+                let response = http_get(url.as_str()).await?;
+                // Past the last page, e.g. when tags were deleted since the count
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok((Vec::new(), Vec::new()));
+                }
+                let page: Value = response.error_for_status()?.json().await?;
+                Ok::<_, NightlyError>(tags_from_page(&page))
+            })
+        })
+        .collect();
+    for page in pages {
+        let (mut page_tags, mut page_quarantined) = page.await??;
+        tags.append(&mut page_tags);
+        quarantined.append(&mut page_quarantined);
+    }
+
+    Ok((tags, quarantined))
+}
+
+/// The url of the given page of results, from the url of another page
+fn page_url(url: &reqwest::Url, page: usize) -> reqwest::Url {
+    let mut url = url.clone();
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| key != "page")
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(query)
+        .append_pair("page", &page.to_string());
+    url
+}
+
+/// The nightly tags in a page of results, and the entries that aren't tags
+///
+/// # Panics
+/// - Panics if unexpected data is returned from the docker registry api
+fn tags_from_page(page: &Value) -> (Vec<Tag>, Vec<QuarantinedEntry>) {
+    let results = page["results"].as_array().unwrap();
+    let mut tags = Vec::new();
+    let mut quarantined = Vec::new();
+    for entry in results {
+        match serde_json::from_value::<Tag>(entry.clone()) {
+            Ok(tag) => {
+                // Skip the 'main' tag that has no sha
+                // This floats around and isn't useful to us
+                if tag.name.split('-').nth(2).is_some_and(str::is_empty) {
+                    continue;
+                }
+                tags.push(tag);
+            }
+            Err(e) => {
+                debug!("Quarantining unparseable registry entry: {}", e);
+                quarantined.push(QuarantinedEntry::new(
+                    entry.clone(),
+                    e.to_string(),
+                    Utc::now(),
+                ));
+            }
+        }
+    }
+    (tags, quarantined)
+}
+
+/// Checks whether the given tag is still served by the docker registry
+///
+/// # Errors
+/// - Errors if there is a problem reaching the docker registry api
+#[deprecated(note = "use `NightliesClient::tag_exists`")]
+pub async fn tag_exists(tag_name: &str) -> Result<bool, NightlyError> {
+    NightliesClient::from_env().tag_exists(tag_name).await
+}
+
+pub(crate) async fn registry_tag_exists(
+    base_url: &str,
+    tag_name: &str,
+) -> Result<bool, NightlyError> {
+    let response = http_get(&format!("{base_url}/{tag_name}")).await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    response.error_for_status()?;
+    Ok(true)
+}
+
+/// Fetches the given tag as the docker registry currently serves it, None if it's gone
+pub(crate) async fn registry_tag(
+    base_url: &str,
+    tag_name: &str,
+) -> Result<Option<Tag>, NightlyError> {
+    let response = http_get(&format!("{base_url}/{tag_name}")).await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.json().await?))
+}
+
+/// Marks cached nightlies whose tags have been deleted from the registry as archived
+///
+/// Only nightlies that are at least as new as the oldest live tag are considered,
+/// older nightlies fall outside of the fetched pages and their absence means nothing.
+/// Candidates are confirmed individually against the registry before being archived,
+/// those that cannot be checked are left as is.
+/// A nightly that shows up in the live tags again is un-archived.
+///
+/// Returns the number of newly archived nightlies
+///
+/// # Errors
+/// - Errors if a check of the registry panics
+#[deprecated(note = "use `NightliesClient::archive_deleted_nightlies`")]
+pub async fn archive_deleted_nightlies(
+    live_tags: &[Tag],
+    nightlies: &mut [Nightly],
+) -> Result<usize, NightlyError> {
+    archive_deleted(&NightliesClient::from_env(), live_tags, nightlies).await
+}
+
+pub(crate) async fn archive_deleted(
+    client: &NightliesClient,
+    live_tags: &[Tag],
+    nightlies: &mut [Nightly],
+) -> Result<usize, NightlyError> {
+    let Some(oldest_live) = live_tags.iter().map(|t| t.last_pushed).min() else {
+        return Ok(0);
+    };
+    let live_shas: HashSet<&str> = live_tags.iter().filter_map(Tag::get_sha).collect();
+
+    let mut candidates = Vec::new();
+    for (i, nightly) in nightlies.iter_mut().enumerate() {
+        if live_shas.contains(nightly.sha.as_str()) {
+            if nightly.archived {
+                info!("Nightly {} is available again, un-archiving", nightly.sha);
+                nightly.archived = false;
+            }
+            continue;
+        }
+        if nightly.archived || nightly.estimated_last_pushed < oldest_live {
+            continue;
+        }
+        if let Some(tag) = nightly.first_valid_tag() {
+            candidates.push((i, tag.name.clone()));
+        }
+    }
+
+    // Candidates are checked concurrently, a few at a time, and one whose check fails is
+    // left as is rather than failing the whole pass
+    let permits = Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_TAG_CHECKS));
+    let checks: Vec<_> = candidates
+        .into_iter()
+        .map(|(i, tag_name)| {
+            let client = client.clone();
+            let permits = Arc::clone(&permits);
+            tokio::spawn(async move {
+                let exists = match permits.acquire_owned().await {
+                    Ok(_permit) => client.tag_exists(&tag_name).await,
+                    Err(e) => Err(NightlyError::GenericError(format!(
+                        "Could not check tag {tag_name}: {e}"
+                    ))),
+                };
+                (i, tag_name, exists)
+            })
+        })
+        .collect();
+    let mut num_archived = 0;
+    for check in checks {
+        match check.await? {
+            (_, tag_name, Ok(true)) => trace!("Tag {} still exists", tag_name),
+            (i, tag_name, Ok(false)) => {
+                debug!("Tag {} no longer exists, archiving nightly", tag_name);
+                nightlies[i].archived = true;
+                num_archived += 1;
+            }
+            (_, tag_name, Err(e)) => {
+                warn!(
+                    "Could not check whether tag {} still exists: {}",
+                    tag_name, e
+                );
+            }
+        }
+    }
+
+    if num_archived > 0 {
+        info!("Archived {num_archived} nightlies whose tags were deleted upstream");
+    }
+
+    Ok(num_archived)
+}
@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use super::Nightly;
+use crate::NightlyError;
+
+// A `--format` template renders each nightly on a line of its own, e.g. `{sha}\t{tag}`, so
+// scripts get exactly the fields they need without parsing the pretty output
+
+/// Placeholders accepted in templates, for error messages
+const PLACEHOLDERS: &str = "sha, tag, image, digest, pushed, committed, commits, pulls, \
+    go_version, gh_url, archived";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Sha,
+    /// Name of the first valid tag
+    Tag,
+    /// The first valid tag as a full image reference
+    Image,
+    Digest,
+    Pushed,
+    /// Empty for unresolved nightlies
+    Committed,
+    Commits,
+    Pulls,
+    GoVersion,
+    GhUrl,
+    Archived,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// A parsed `--format` template
+/// `{{` and `}}` are literal braces, `\t`, `\n` and `\\` are escapes, values a nightly
+/// doesn't have render as nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NightlyTemplate(Vec<Part>);
+
+fn error(message: impl Into<String>) -> NightlyError {
+    NightlyError::TemplateError(message.into())
+}
+
+fn placeholder(name: &str) -> Result<Placeholder, NightlyError> {
+    Ok(match name {
+        "sha" => Placeholder::Sha,
+        "tag" => Placeholder::Tag,
+        "image" => Placeholder::Image,
+        "digest" => Placeholder::Digest,
+        "pushed" => Placeholder::Pushed,
+        "committed" => Placeholder::Committed,
+        "commits" => Placeholder::Commits,
+        "pulls" => Placeholder::Pulls,
+        "go_version" => Placeholder::GoVersion,
+        "gh_url" => Placeholder::GhUrl,
+        "archived" => Placeholder::Archived,
+        _ => {
+            return Err(error(format!(
+                "unknown placeholder '{{{name}}}', known are {PLACEHOLDERS}"
+            )))
+        }
+    })
+}
+
+impl FromStr for NightlyTemplate {
+    type Err = NightlyError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    literal.push(c);
+                }
+                ('\\', Some('t')) => {
+                    chars.next();
+                    literal.push('\t');
+                }
+                ('\\', Some('n')) => {
+                    chars.next();
+                    literal.push('\n');
+                }
+                ('\\', Some('\\')) => {
+                    chars.next();
+                    literal.push('\\');
+                }
+                ('{', _) => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(error(format!("unclosed '{{{name}'")));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(placeholder(name.trim())?));
+                }
+                ('}', _) => return Err(error("unmatched '}', write '}}' for a brace")),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(NightlyTemplate(parts))
+    }
+}
+
+impl NightlyTemplate {
+    /// The template filled in with the given nightly's values
+    #[must_use]
+    pub fn render(&self, nightly: &Nightly) -> String {
+        let tag = nightly.first_valid_tag();
+        let mut rendered = String::new();
+        for part in &self.0 {
+            let placeholder = match part {
+                Part::Literal(literal) => {
+                    rendered.push_str(literal);
+                    continue;
+                }
+                Part::Placeholder(placeholder) => placeholder,
+            };
+            let value = match placeholder {
+                Placeholder::Sha => nightly.sha.clone(),
+                Placeholder::Tag => tag.map(|t| t.name.clone()).unwrap_or_default(),
+                Placeholder::Image => tag
+                    .map(|t| format!("datadog/agent-dev:{}", t.name))
+                    .unwrap_or_default(),
+                Placeholder::Digest => tag.map(|t| t.digest.clone()).unwrap_or_default(),
+                Placeholder::Pushed => nightly.estimated_last_pushed.to_rfc3339(),
+                Placeholder::Committed => nightly
+                    .sha_timestamp
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default(),
+                Placeholder::Commits => nightly
+                    .incremental_commits
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+                Placeholder::Pulls => tag
+                    .and_then(|t| t.pull_count)
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+                Placeholder::GoVersion => nightly.go_version.clone().unwrap_or_default(),
+                Placeholder::GhUrl => {
+                    format!(
+                        "https://github.com/DataDog/datadog-agent/tree/{}",
+                        nightly.sha
+                    )
+                }
+                Placeholder::Archived => nightly.archived.to_string(),
+            };
+            rendered.push_str(&value);
+        }
+        rendered
+    }
+}
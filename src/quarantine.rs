@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::nightly::{RejectedNightly, Tag};
+
+// Registry entries that can't be turned into nightlies are kept aside instead of dropped,
+// so that changes to the upstream tag naming can be noticed
+
+/// A registry entry that isn't a usable nightly tag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuarantinedEntry {
+    /// The entry as the registry returned it
+    pub entry: Value,
+    /// Why it isn't a nightly tag
+    pub reason: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl QuarantinedEntry {
+    #[must_use]
+    pub fn new(entry: Value, reason: impl Into<String>, now: DateTime<Utc>) -> Self {
+        Self {
+            entry,
+            reason: reason.into(),
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+    /// The tag name of the entry, or the sha of a rejected nightly, if it has one
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.entry["name"]
+            .as_str()
+            .or_else(|| self.entry["sha"].as_str())
+    }
+
+    fn is_same_entry(&self, other: &QuarantinedEntry) -> bool {
+        self.reason == other.reason
+            && match (self.name(), other.name()) {
+                (Some(name), Some(other_name)) => name == other_name,
+                _ => self.entry == other.entry,
+            }
+    }
+}
+
+/// Quarantines the tags whose name doesn't carry a nightly sha where one is expected
+#[must_use]
+pub fn quarantine_tags(tags: &[Tag], now: DateTime<Utc>) -> Vec<QuarantinedEntry> {
+    tags.iter()
+        .filter(|t| t.get_sha().is_none())
+        .map(|t| {
+            let entry = serde_json::to_value(t).unwrap_or_else(|_| Value::from(t.name.clone()));
+            QuarantinedEntry::new(entry, "no 8 character sha after 'nightly-main-'", now)
+        })
+        .collect()
+}
+
+/// Quarantines the tags of nightlies that failed validation
+#[must_use]
+pub fn quarantine_rejected(
+    rejected: &[RejectedNightly],
+    now: DateTime<Utc>,
+) -> Vec<QuarantinedEntry> {
+    rejected
+        .iter()
+        .map(|r| {
+            let entry = serde_json::json!({ "sha": r.sha, "tags": r.tags });
+            QuarantinedEntry::new(entry, r.error.to_string(), now)
+        })
+        .collect()
+}
+
+/// Adds newly found entries to the quarantine, entries that are already quarantined only
+/// have their `last_seen` updated
+/// Returns the number of entries that weren't quarantined before
+pub fn merge_quarantine(
+    quarantine: &mut Vec<QuarantinedEntry>,
+    found: Vec<QuarantinedEntry>,
+) -> usize {
+    let mut num_new = 0;
+    for entry in found {
+        if let Some(known) = quarantine.iter_mut().find(|q| q.is_same_entry(&entry)) {
+            known.last_seen = known.last_seen.max(entry.last_seen);
+        } else {
+            quarantine.push(entry);
+            num_new += 1;
+        }
+    }
+    num_new
+}
@@ -0,0 +1,319 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::nightly::{Nightly, Tag};
+
+pub mod filter;
+
+// Everything in here is pure, the listing pipeline is built out of these so
+// that its filtering and selection rules can be tested without a registry or git repo
+
+/// Groups the given tags by the nightly sha they belong to, skipping tags without a sha
+/// and tags for shas that are already tracked in `nightlies`
+#[must_use]
+pub fn group_untracked_tags(tags: &[Tag], nightlies: &[Nightly]) -> HashMap<String, Vec<Tag>> {
+    let tracked: HashSet<&str> = nightlies.iter().map(|n| n.sha.as_str()).collect();
+    let mut grouped: HashMap<String, Vec<Tag>> = HashMap::new();
+    for tag in tags {
+        let Some(sha) = tag.get_sha() else {
+            continue;
+        };
+        if tracked.contains(sha) {
+            continue;
+        }
+        grouped
+            .entry(sha.to_string())
+            .or_default()
+            .push(tag.clone());
+    }
+    grouped
+}
+
+/// Below this many tags, unparseable ones are not worth a hint
+const TAG_FORMAT_ADVISORY_MIN_TAGS: usize = 10;
+
+/// A hint that the upstream tag naming may have changed, when most of the given tags
+/// don't carry a nightly sha where this tool expects one
+#[must_use]
+pub fn tag_format_advisory(tags: &[Tag]) -> Option<String> {
+    let num_unparseable = tags.iter().filter(|t| t.get_sha().is_none()).count();
+    if tags.len() < TAG_FORMAT_ADVISORY_MIN_TAGS || num_unparseable * 2 <= tags.len() {
+        return None;
+    }
+    let example = tags.iter().find(|t| t.get_sha().is_none())?;
+    Some(format!(
+        "{num_unparseable} of {} registry tags don't look like nightly tags (e.g. '{}'), \
+         the tag naming upstream may have changed and nightlies may need an update",
+        tags.len(),
+        example.name
+    ))
+}
+
+/// Removes nightlies with a sha that was already seen, keeping the first occurrence
+pub fn dedupe(nightlies: &mut Vec<Nightly>) {
+    let mut seen = HashSet::new();
+    nightlies.retain(|n| seen.insert(n.sha.clone()));
+}
+
+/// The timestamp used to place a nightly in time, the commit time if known and
+/// the push time otherwise
+#[must_use]
+pub fn nightly_timestamp(nightly: &Nightly) -> DateTime<Utc> {
+    nightly
+        .sha_timestamp
+        .unwrap_or(nightly.estimated_last_pushed)
+}
+
+/// Nightlies whose timestamp falls within `from_date..=to_date`, an absent `to_date` is unbounded
+pub fn query_range(
+    nightlies: &[Nightly],
+    from_date: DateTime<Utc>,
+    to_date: Option<DateTime<Utc>>,
+) -> impl Iterator<Item = &Nightly> + '_ {
+    nightlies.iter().filter(move |n| {
+        let nightly_timestamp = nightly_timestamp(n);
+        if let Some(to_date) = to_date {
+            nightly_timestamp <= to_date && nightly_timestamp >= from_date
+        } else {
+            nightly_timestamp >= from_date
+        }
+    })
+}
+
+/// The number of days to look back from `now` for the window to contain a nightly,
+/// doubling from `min_days` and capped at `max_days`
+#[must_use]
+pub fn adaptive_window_days(
+    nightlies: &[Nightly],
+    now: DateTime<Utc>,
+    min_days: i64,
+    max_days: i64,
+) -> i64 {
+    let mut days = min_days;
+    while days < max_days
+        && query_range(nightlies, now - chrono::Duration::days(days), None)
+            .next()
+            .is_none()
+    {
+        days = (days * 2).min(max_days);
+    }
+    days
+}
+
+/// What `--trend` charts per day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "client", derive(clap::ValueEnum))]
+pub enum TrendMetric {
+    /// Commits the nightlies added over their predecessors
+    Commits,
+    /// Registry pull counts of the nightlies' images
+    Pulls,
+}
+
+impl std::fmt::Display for TrendMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrendMetric::Commits => write!(f, "commits"),
+            TrendMetric::Pulls => write!(f, "pulls"),
+        }
+    }
+}
+
+/// The metric summed over the nightlies pushed each day from `from` to `to` (inclusive),
+/// in UTC, None for the days without a nightly with a known value
+/// The commit counts are the cached incremental counts and the pull counts those the
+/// registry last reported for any of a nightly's tags, nightlies without one are skipped
+#[must_use]
+pub fn daily_trend(
+    nightlies: &[Nightly],
+    metric: TrendMetric,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<(NaiveDate, Option<usize>)> {
+    let mut totals: HashMap<NaiveDate, usize> = HashMap::new();
+    for nightly in nightlies {
+        let value = match metric {
+            TrendMetric::Commits => nightly.incremental_commits,
+            TrendMetric::Pulls => pull_count(nightly),
+        };
+        if let Some(value) = value {
+            *totals
+                .entry(nightly.estimated_last_pushed.date_naive())
+                .or_default() += value;
+        }
+    }
+    from.iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| (day, totals.get(&day).copied()))
+        .collect()
+}
+
+/// The pulls of all of a nightly's images, None if the registry reported none
+fn pull_count(nightly: &Nightly) -> Option<usize> {
+    [
+        &nightly.py3,
+        &nightly.py2,
+        &nightly.py3_jmx,
+        &nightly.py2_jmx,
+        &nightly.jmx,
+    ]
+    .into_iter()
+    .flatten()
+    .chain(&nightly.flavor_tags)
+    .filter_map(|t| t.pull_count.and_then(|count| usize::try_from(count).ok()))
+    .reduce(usize::saturating_add)
+}
+
+/// Which of a nightly's timestamps is used to order it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "client", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Clock {
+    /// When the nightly's commit was made on 'main'
+    Commit,
+    /// When the nightly's image was pushed to the registry
+    Push,
+}
+
+impl std::fmt::Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Clock::Commit => write!(f, "commit"),
+            Clock::Push => write!(f, "push"),
+        }
+    }
+}
+
+impl Clock {
+    /// The nightly's timestamp according to this clock
+    /// The commit clock falls back to the push time for nightlies without a commit timestamp
+    #[must_use]
+    pub fn timestamp(self, nightly: &Nightly) -> DateTime<Utc> {
+        match self {
+            Clock::Commit => nightly_timestamp(nightly),
+            Clock::Push => nightly.estimated_last_pushed,
+        }
+    }
+}
+
+/// Sorts nightlies from oldest to newest by the given clock
+/// With the commit clock, nightlies without a commit timestamp come first
+pub fn sort_oldest_first(nightlies: &mut [&Nightly], clock: Clock) {
+    match clock {
+        Clock::Commit => nightlies.sort_by_key(|n| n.sha_timestamp),
+        Clock::Push => nightlies.sort_by_key(|n| n.estimated_last_pushed),
+    }
+}
+
+/// Selects the nth most recent nightly by commit timestamp, 0 being the latest
+#[must_use]
+pub fn nth_latest(nightlies: &[Nightly], n: usize) -> Option<&Nightly> {
+    let mut nightlies: Vec<&Nightly> = nightlies.iter().collect();
+    nightlies.sort_by_key(|n| std::cmp::Reverse(n.sha_timestamp));
+    nightlies.get(n).copied()
+}
+
+/// How many of the nightlies had any of their images pulled since `since`, to gauge
+/// which nightlies actually get used
+#[must_use]
+pub fn pulled_since(nightlies: &[Nightly], since: DateTime<Utc>) -> usize {
+    nightlies
+        .iter()
+        .filter(|n| {
+            [&n.py3, &n.py2, &n.py3_jmx, &n.py2_jmx, &n.jmx]
+                .into_iter()
+                .flatten()
+                .chain(&n.flavor_tags)
+                .any(|t| t.last_pulled.is_some_and(|pulled| pulled >= since))
+        })
+        .count()
+}
+
+/// The nightly that was the latest at `instant`, ie the last one pushed by then
+#[must_use]
+pub fn latest_as_of(nightlies: &[Nightly], instant: DateTime<Utc>) -> Option<&Nightly> {
+    nightlies
+        .iter()
+        .filter(|n| n.estimated_last_pushed <= instant)
+        .max_by_key(|n| n.estimated_last_pushed)
+}
+
+/// Nightlies whose commit could not be found in the agent repo despite looking for it,
+/// oldest push first
+#[must_use]
+pub fn orphans(nightlies: &[Nightly]) -> Vec<&Nightly> {
+    let mut orphans: Vec<&Nightly> = nightlies
+        .iter()
+        .filter(|n| n.sha_timestamp.is_none() && n.resolve_attempts > 0)
+        .collect();
+    orphans.sort_by_key(|n| n.estimated_last_pushed);
+    orphans
+}
+
+/// Parses the `@N` shorthand for "the nth most recent nightly", `@0` being the latest
+#[must_use]
+pub fn parse_index_shorthand(identifier: &str) -> Option<usize> {
+    identifier.strip_prefix('@')?.parse().ok()
+}
+
+/// The nightly built right before the given one, by commit timestamp
+/// Nightlies without a commit timestamp have no known predecessor
+#[must_use]
+pub fn predecessor<'a>(nightlies: &'a [Nightly], nightly: &Nightly) -> Option<&'a Nightly> {
+    let timestamp = nightly.sha_timestamp?;
+    nightlies
+        .iter()
+        .filter(|n| n.sha_timestamp.is_some_and(|t| t < timestamp))
+        .max_by_key(|n| n.sha_timestamp)
+}
+
+/// Records each nightly's predecessor in `predecessor_sha`
+/// A nightly whose predecessor changed, e.g. because an older nightly was discovered
+/// late, has its incremental commit count cleared so that it gets recomputed
+pub fn link_predecessors(nightlies: &mut [Nightly]) {
+    let links: Vec<Option<String>> = nightlies
+        .iter()
+        .map(|n| predecessor(nightlies, n).map(|prev| prev.sha.clone()))
+        .collect();
+    for (nightly, link) in nightlies.iter_mut().zip(links) {
+        if nightly.predecessor_sha != link {
+            nightly.predecessor_sha = link;
+            nightly.incremental_commits = None;
+        }
+    }
+}
+
+/// A Go version without its `go` prefix and with the patch version filled in, since
+/// `go 1.22` in go.mod names the same toolchain as `toolchain go1.22.0`
+fn normalize_go_version(version: &str) -> String {
+    let version = version.trim().trim_start_matches("go");
+    match version.matches('.').count() {
+        1 => format!("{version}.0"),
+        _ => version.to_string(),
+    }
+}
+
+/// The Go toolchain bumps between nightlies, as the predecessor's version and the new one
+/// keyed by the sha of the first nightly built with the new version
+#[must_use]
+pub fn go_version_changes(nightlies: &[Nightly]) -> HashMap<String, (String, String)> {
+    let versions: HashMap<&str, &str> = nightlies
+        .iter()
+        .filter_map(|n| Some((n.sha.as_str(), n.go_version.as_deref()?)))
+        .collect();
+    nightlies
+        .iter()
+        .filter_map(|n| {
+            let version = n.go_version.as_deref()?;
+            let previous = versions.get(n.predecessor_sha.as_deref()?)?;
+            (normalize_go_version(previous) != normalize_go_version(version)).then(|| {
+                (
+                    n.sha.clone(),
+                    ((*previous).to_string(), version.to_string()),
+                )
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,358 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{business_day::is_weekend, nightly::Nightly, NightlyError};
+
+// A small expression language over the fields of a nightly, e.g.
+// `pushed > 2024-07-01 && !weekend && commits >= 10`, so that field combinations don't each
+// need a flag. `&&` binds tighter than `||`, parentheses group
+
+/// Names accepted in filter expressions, for error messages
+const FIELDS: &str = "pushed, committed (dates), commits, pulls (numbers), sha, tag (text), \
+    weekend, archived, resolved (flags)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// When the image was pushed
+    Pushed,
+    /// When the commit was made, unknown for unresolved nightlies
+    Committed,
+    /// Commits added over the previous nightly
+    Commits,
+    /// Pulls of the first image
+    Pulls,
+    Sha,
+    /// Name of the first image
+    Tag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flag {
+    /// Pushed on a Saturday or Sunday
+    Weekend,
+    Archived,
+    /// Its commit was found in the datadog-agent checkout
+    Resolved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Text contains
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Time(DateTime<Utc>),
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Flag(Flag),
+    Compare(Field, Op, Value),
+}
+
+/// A parsed `--filter` expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Contains => "~",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Not => write!(f, "'!'"),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Op(op) => write!(f, "'{op}'"),
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Quoted(text) => write!(f, "'\"{text}\"'"),
+        }
+    }
+}
+
+fn error(message: impl Into<String>) -> NightlyError {
+    NightlyError::FilterError(message.into())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-:.+".contains(c)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, NightlyError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        let pair = chars.peek().map(|&next| [c, next]);
+        let double = match pair {
+            Some(['&', '&']) => Some(Token::And),
+            Some(['|', '|']) => Some(Token::Or),
+            Some(['=', '=']) => Some(Token::Op(Op::Eq)),
+            Some(['!', '=']) => Some(Token::Op(Op::Ne)),
+            Some(['<', '=']) => Some(Token::Op(Op::Le)),
+            Some(['>', '=']) => Some(Token::Op(Op::Ge)),
+            _ => None,
+        };
+        if let Some(token) = double {
+            chars.next();
+            tokens.push(token);
+            continue;
+        }
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => Token::Op(Op::Eq),
+            '<' => Token::Op(Op::Lt),
+            '>' => Token::Op(Op::Gt),
+            '~' => Token::Op(Op::Contains),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(error(format!("missing '\"' after \"{text}"))),
+                    }
+                }
+                Token::Quoted(text)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|&c| is_word_char(c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => return Err(error(format!("unexpected '{c}'"))),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_time(word: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    DateTime::parse_from_rfc3339(word)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, NightlyError> {
+        let mut filter = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            filter = Expr::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Expr, NightlyError> {
+        let mut filter = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            filter = Expr::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Expr, NightlyError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let filter = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(filter),
+                    _ => Err(error("missing ')'")),
+                }
+            }
+            Some(Token::Word(name)) => self.term(&name),
+            Some(token) => Err(error(format!("expected a field, found {token}"))),
+            None => Err(error("unexpected end of the expression")),
+        }
+    }
+
+    fn term(&mut self, name: &str) -> Result<Expr, NightlyError> {
+        let field = match name {
+            "weekend" => return Ok(Expr::Flag(Flag::Weekend)),
+            "archived" => return Ok(Expr::Flag(Flag::Archived)),
+            "resolved" => return Ok(Expr::Flag(Flag::Resolved)),
+            "pushed" => Field::Pushed,
+            "committed" => Field::Committed,
+            "commits" => Field::Commits,
+            "pulls" => Field::Pulls,
+            "sha" => Field::Sha,
+            "tag" => Field::Tag,
+            _ => return Err(error(format!("unknown field '{name}', known are {FIELDS}"))),
+        };
+        let Some(Token::Op(op)) = self.next() else {
+            return Err(error(format!("expected a comparison after '{name}'")));
+        };
+        let Some(Token::Word(value) | Token::Quoted(value)) = self.next() else {
+            return Err(error(format!("expected a value to compare '{name}' to")));
+        };
+        let value = match field {
+            Field::Pushed | Field::Committed => {
+                Value::Time(parse_time(&value).ok_or_else(|| {
+                    error(format!("'{value}' is not a YYYY-MM-DD or RFC 3339 date"))
+                })?)
+            }
+            Field::Commits | Field::Pulls => Value::Number(
+                value
+                    .parse()
+                    .map_err(|_| error(format!("'{value}' is not a number")))?,
+            ),
+            Field::Sha | Field::Tag => Value::Text(value),
+        };
+        match value {
+            Value::Text(_) if !matches!(op, Op::Eq | Op::Ne | Op::Contains) => {
+                return Err(error(format!(
+                    "'{name}' is text, compare it with =, != or ~"
+                )));
+            }
+            Value::Time(_) | Value::Number(_) if op == Op::Contains => {
+                return Err(error(format!("'{name}' can't be compared with ~")));
+            }
+            _ => {}
+        }
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+fn compare<T: PartialOrd>(left: &T, op: Op, right: &T) -> bool {
+    match op {
+        Op::Eq => left == right,
+        Op::Ne => left != right,
+        Op::Lt => left < right,
+        Op::Le => left <= right,
+        Op::Gt => left > right,
+        Op::Ge => left >= right,
+        Op::Contains => false,
+    }
+}
+
+impl Expr {
+    fn matches(&self, nightly: &Nightly) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(nightly) && right.matches(nightly),
+            Expr::Or(left, right) => left.matches(nightly) || right.matches(nightly),
+            Expr::Not(filter) => !filter.matches(nightly),
+            Expr::Flag(Flag::Weekend) => is_weekend(nightly.estimated_last_pushed),
+            Expr::Flag(Flag::Archived) => nightly.archived,
+            Expr::Flag(Flag::Resolved) => nightly.sha_timestamp.is_some(),
+            Expr::Compare(field, op, value) => {
+                let tag = nightly.first_valid_tag();
+                match (field, value) {
+                    (Field::Pushed, Value::Time(time)) => {
+                        compare(&nightly.estimated_last_pushed, *op, time)
+                    }
+                    (Field::Committed, Value::Time(time)) => nightly
+                        .sha_timestamp
+                        .is_some_and(|committed| compare(&committed, *op, time)),
+                    (Field::Commits, Value::Number(number)) => nightly
+                        .incremental_commits
+                        .is_some_and(|commits| compare(&(commits as u64), *op, number)),
+                    (Field::Pulls, Value::Number(number)) => tag
+                        .and_then(|t| t.pull_count)
+                        .is_some_and(|pulls| compare(&pulls, *op, number)),
+                    (Field::Sha | Field::Tag, Value::Text(text)) => {
+                        let actual = if *field == Field::Sha {
+                            nightly.sha.as_str()
+                        } else {
+                            tag.map_or("", |t| t.name.as_str())
+                        };
+                        match op {
+                            Op::Contains => actual.contains(text.as_str()),
+                            _ => compare(&actual, *op, &text.as_str()),
+                        }
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+impl Filter {
+    /// Whether the nightly passes the filter
+    /// Comparisons on values a nightly doesn't have, e.g. the commit time of an unresolved
+    /// nightly, are false
+    #[must_use]
+    pub fn matches(&self, nightly: &Nightly) -> bool {
+        self.0.matches(nightly)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = NightlyError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(Filter(expr)),
+            Some(token) => Err(error(format!("unexpected {token}"))),
+        }
+    }
+}
@@ -1,161 +1,975 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, LazyLock, Mutex, MutexGuard},
+};
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 //use git2::{Commit, Error, Repository};
 
-use gix::{Commit, Id, Repository};
-use tracing::{debug, warn};
+use gix::{
+    object::tree::diff::Action, traverse::commit::simple::Sorting, Commit, Id, ObjectId, Repository,
+};
+use tracing::{debug, info, warn};
 
 use crate::{nightly::Nightly, NightlyError};
 
-fn get_agent_repo_path() -> Result<PathBuf> {
-    let home = match home::home_dir() {
-        Some(path) if !path.as_os_str().is_empty() => Some(path),
-        _ => None,
-    };
-    let home = home
-        .ok_or_else(|| NightlyError::GenericError(String::from("Could not find home directory")))?;
+pub mod graph;
+mod ignore;
+mod lru;
+mod range;
+
+use graph::CommitGraph;
+pub use ignore::IgnorePaths;
+use lru::Lru;
+use range::walk_range;
 
-    Ok(Path::new(&home).join("./go/src/github.com/DataDog/datadog-agent"))
+/// Where the datadog-agent checkout is expected when none is configured,
+/// `~/go/src/github.com/DataDog/datadog-agent`, relative to the working directory when
+/// there is no home directory
+#[must_use]
+pub fn default_agent_repo_path() -> PathBuf {
+    let home = home::home_dir()
+        .filter(|path| !path.as_os_str().is_empty())
+        .unwrap_or_default();
+    home.join("go/src/github.com/DataDog/datadog-agent")
 }
 
-fn open_git_repo() -> Result<Repository> {
-    let repo = get_agent_repo_path()?;
-    gix::open(repo).map_err(|e| e.into())
+fn open_git_repo(repo_path: &Path) -> Result<Repository> {
+    gix::open(repo_path).map_err(Into::into)
 }
 
-/// Starting from the given branch, walk backwards until we find the commit with the given sha
-fn get_commit_by_sha<'a>(
-    repo: &'a Repository,
-    sha: &'a str,
-    branch: &'a Id,
-) -> Result<Option<Commit<'a>>> {
-    let commit_oid = match repo.rev_parse_single(sha) {
-        Ok(obj) => obj,
-        Err(e) => {
-            warn!("Error finding sha: {}", e);
-            return Ok(None);
-        }
-    };
+/// Explains why the given sha could not be found on 'main' and how to remediate it
+///
+/// A commit that exists locally but isn't reachable from 'main' points at a nightly built
+/// from another branch (or a rewritten 'main'), while a commit that doesn't exist at all
+/// means the checkout is stale or the commit was dropped upstream by a history rewrite.
+fn print_commit_not_found_diagnostic(repo: &Repository, target_sha: &str) {
+    let git_path = repo.work_dir().unwrap_or_else(|| repo.git_dir());
+    if repo.rev_parse_single(target_sha).is_ok() {
+        warn!(
+            "Commit {} exists in your datadog-agent checkout at {} but is not reachable from 'origin/main'",
+            target_sha,
+            git_path.display()
+        );
+        warn!("The nightly was likely built from another branch, or 'main' was rewritten after it was built");
+    } else {
+        warn!(
+            "Could not find the target commit: {} in your datadog-agent checkout at {}",
+            target_sha,
+            git_path.display()
+        );
+        warn!("Either your checkout is stale, or the commit was removed upstream by a history rewrite");
+        warn!(
+            "Consider re-running with '--force-fetch' (or 'git -C {} fetch --all --tags')",
+            git_path.display()
+        );
+        warn!("If the commit is still missing after a fetch, a mirror clone ('git clone --mirror') keeps refs that a regular clone drops");
+    }
+}
 
-    let revwalk = repo
-        .rev_walk(Some(branch.detach()))
-        .sorting(gix::traverse::commit::simple::Sorting::ByCommitTimeNewestFirst)
-        .all()?
-        .filter_map(Result::ok);
-
-    //revwalk.push(branch.id())?;
-    //revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
-    // revwalk will now walk backwards from the specified branch
-    // until we find our target commit
-
-    for rev in revwalk {
-        let cm = rev.object()?;
-        if cm.id() == commit_oid {
-            return Ok(Some(cm));
+/// Fetches all remotes of the datadog-agent checkout at `git_path`
+/// gix is built without network support, so this shells out to git
+///
+/// # Errors
+/// - If `git fetch` cannot be run or fails
+pub fn fetch_agent_repo(git_path: &Path) -> Result<()> {
+    info!("Fetching latest changes into {}", git_path.display());
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(git_path)
+        .args(["fetch", "--all", "--tags"])
+        .status()?;
+    if !status.success() {
+        return Err(NightlyError::GitError(format!("'git fetch' exited with {status}")).into());
+    }
+    Ok(())
+}
+
+/// The datadog-agent repository cloned by [`clone_agent_repo`]
+const AGENT_REPO_URL: &str = "https://github.com/DataDog/datadog-agent.git";
+
+/// Clones the datadog-agent repository into `path` without a working tree
+/// Its branches are fetched as `origin/*` like in a regular clone, as the nightlies are
+/// looked up on 'origin/main'
+/// gix is built without network support, so this shells out to git
+///
+/// # Errors
+/// - If `git clone`, `git config` or `git fetch` cannot be run or fails
+pub fn clone_agent_repo(path: &Path) -> Result<()> {
+    info!("Cloning {} into {}", AGENT_REPO_URL, path.display());
+    let path = path.to_string_lossy();
+    let steps: [(&str, &[&str]); 3] = [
+        ("clone", &["clone", "--bare", AGENT_REPO_URL, &path]),
+        (
+            "config",
+            &[
+                "-C",
+                &path,
+                "config",
+                "remote.origin.fetch",
+                "+refs/heads/*:refs/remotes/origin/*",
+            ],
+        ),
+        ("fetch", &["-C", &path, "fetch", "origin"]),
+    ];
+    for (name, args) in steps {
+        let status = Command::new("git").args(args).status()?;
+        if !status.success() {
+            return Err(
+                NightlyError::GitError(format!("'git {name}' exited with {status}")).into(),
+            );
         }
     }
+    Ok(())
+}
 
-    Ok(None)
+/// Checks out `sha` in a detached worktree of the datadog-agent checkout at `git_path`,
+/// at `path`, reusing the worktree if it is already there
+/// gix can't create worktrees, so this shells out to git
+///
+/// # Errors
+/// - If `git worktree add` cannot be run or fails
+pub fn add_agent_worktree(git_path: &Path, sha: &str, path: &Path) -> Result<()> {
+    if path.join(".git").exists() {
+        debug!("Reusing the worktree at {}", path.display());
+        return Ok(());
+    }
+    info!("Checking out {} in {}", sha, path.display());
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(git_path)
+        .args(["worktree", "add", "--detach"])
+        .arg(path)
+        .arg(sha)
+        .status()?;
+    if !status.success() {
+        return Err(
+            NightlyError::GitError(format!("'git worktree add' exited with {status}")).into(),
+        );
+    }
+    Ok(())
 }
 
-fn print_friendly_git_may_be_stale_warning(target_sha: &str) {
-    let git_path = get_agent_repo_path().expect("Could not find agent repo path");
-    warn!(
-        "Could not find the target commit: {} on 'main' of your datadog-agent checkout at {}",
-        target_sha,
-        git_path.display()
-    );
-    warn!(
-        "Consider running 'git -C {} fetch --all --tags'",
-        git_path.display()
-    );
+/// Whether there is a git repository at `path`
+#[must_use]
+pub fn is_git_repo(path: &Path) -> bool {
+    gix::open(path).is_ok()
+}
+
+/// Opens the datadog-agent checkout at `repo_path`, returning None if it isn't usable
+///
+/// Callers that can do without git data should use this and carry on with
+/// push-time-only data, a single warning is logged instead of one per lookup
+#[must_use]
+pub fn open_agent_repo(repo_path: &Path) -> Option<Repository> {
+    match open_git_repo(repo_path) {
+        Ok(repo) => Some(repo),
+        Err(e) => {
+            warn!("Could not open the datadog-agent checkout, continuing without commit timestamps: {e}");
+            None
+        }
+    }
 }
 
-/// Given a sha that exists in the 'main' branch of the datadog-agent repo
-/// return the timestamp of that commit
+/// Given a sha that exists in the 'main' branch of the datadog-agent checkout at
+/// `repo_path`, return the timestamp of that commit
 ///
 /// # Errors
 /// - If the given sha is not found on the main branch
 /// - If the git repo cannot be opened
 /// - If the commit timestamp cannot be parsed
-pub fn get_commit_timestamp(target_sha: &str) -> Result<DateTime<Utc>> {
-    let repo = open_git_repo()?;
+pub fn get_commit_timestamp(repo_path: &Path, target_sha: &str) -> Result<DateTime<Utc>> {
+    let repo = open_git_repo(repo_path)?;
+    get_commit_timestamp_in(&repo, target_sha)
+}
+
+/// Like [`get_commit_timestamp`], but using an already opened repository
+///
+/// # Errors
+/// - If the given sha is not found on the main branch
+/// - If the commit timestamp cannot be parsed
+pub fn get_commit_timestamp_in(repo: &Repository, target_sha: &str) -> Result<DateTime<Utc>> {
+    find_commit_timestamp_in(repo, target_sha)?.ok_or_else(|| {
+        print_commit_not_found_diagnostic(repo, target_sha);
+        NightlyError::CommitNotFound(target_sha.to_string()).into()
+    })
+}
+
+/// Like [`get_commit_timestamp_in`], but quiet about commits that aren't on 'main',
+/// for lookups that are expected to fail
+/// Results are remembered for the rest of the run, as the same sha is often looked up
+/// from several places
+///
+/// # Errors
+/// - If 'main' cannot be walked
+/// - If the commit timestamp cannot be parsed
+pub fn find_commit_timestamp_in(
+    repo: &Repository,
+    target_sha: &str,
+) -> Result<Option<DateTime<Utc>>> {
     let origin_main = repo
         .find_reference("refs/remotes/origin/main")?
-        .into_fully_peeled_id()?;
+        .into_fully_peeled_id()?
+        .detach();
+    let key = (
+        repo.git_dir().to_path_buf(),
+        origin_main,
+        target_sha.to_string(),
+    );
+    if let Some(timestamp) = lock_commit_timestamps().get(&key) {
+        debug!("Reusing the commit timestamp of {}", target_sha);
+        return Ok(timestamp);
+    }
 
-    let commit = get_commit_by_sha(&repo, target_sha, &origin_main)?;
-    let commit = commit.ok_or_else(|| {
-        print_friendly_git_may_be_stale_warning(target_sha);
-        NightlyError::GenericError(format!("commit '{target_sha}' not found on 'main'"))
-    })?;
+    let timestamp = match repo.rev_parse_single(target_sha) {
+        Ok(commit_oid) => {
+            if main_graph(repo, origin_main)?.contains(&commit_oid.detach()) {
+                Some(commit_timestamp(&commit_oid.object()?.into_commit())?)
+            } else {
+                None
+            }
+        }
+        Err(_) => None,
+    };
+    lock_commit_timestamps().insert(key, timestamp);
+    Ok(timestamp)
+}
 
-    let timestamp = DateTime::from_timestamp(commit.time()?.seconds, 0).ok_or(
-        NightlyError::DateParseError(format!(
-            "Couldn't use commit epoch value of {}",
-            commit.time()?.seconds
-        )),
-    )?;
+// The lookups remembered during a run are keyed by the checkout's git dir first, the
+// only setting of a `NightliesClient` they depend on, so clients using different checkouts
+// never answer from each other's entries
+type CommitTimestampKey = (PathBuf, ObjectId, String);
+
+/// Commit timestamps looked up during this run, per repository, 'main' tip and sha
+/// None means the commit is not on 'main'
+static COMMIT_TIMESTAMPS: LazyLock<Mutex<Lru<CommitTimestampKey, Option<DateTime<Utc>>>>> =
+    LazyLock::new(|| Mutex::new(Lru::new(1024)));
+
+fn lock_commit_timestamps() -> MutexGuard<'static, Lru<CommitTimestampKey, Option<DateTime<Utc>>>> {
+    COMMIT_TIMESTAMPS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// The graph of 'main' at `origin_main`, walked once per run rather than once per lookup
+fn main_graph(repo: &Repository, origin_main: ObjectId) -> Result<Arc<CommitGraph>> {
+    let key = (repo.git_dir().to_path_buf(), origin_main);
+    if let Some(graph) = lock_main_graphs().get(&key) {
+        return Ok(graph);
+    }
+    let graph = Arc::new(CommitGraph::build(repo, Some(origin_main))?);
+    lock_main_graphs().insert(key, Arc::clone(&graph));
+    Ok(graph)
+}
+
+/// Builds the graph of 'main' ahead of lookups spread over threads, which would otherwise
+/// each walk 'main' before any of them remembers it
+pub(crate) fn warm_main_graph(repo: &Repository) -> Result<()> {
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?
+        .detach();
+    main_graph(repo, origin_main)?;
+    Ok(())
+}
+
+/// Forgets every lookup remembered so far during this run, so that benchmarks time the
+/// lookups themselves rather than the memos
+pub fn forget_lookups() {
+    lock_commit_timestamps().clear();
+    lock_main_graphs().clear();
+    lock_commit_ranges().clear();
+}
+
+type MainGraphKey = (PathBuf, ObjectId);
 
+/// Graphs of 'main' built during this run, per repository and 'main' tip
+static MAIN_GRAPHS: LazyLock<Mutex<Lru<MainGraphKey, Arc<CommitGraph>>>> =
+    LazyLock::new(|| Mutex::new(Lru::new(4)));
+
+fn lock_main_graphs() -> MutexGuard<'static, Lru<MainGraphKey, Arc<CommitGraph>>> {
+    MAIN_GRAPHS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn commit_timestamp(commit: &Commit) -> Result<DateTime<Utc>> {
+    let seconds = commit.time()?.seconds;
+    let timestamp = DateTime::from_timestamp(seconds, 0).ok_or(NightlyError::DateParseError(
+        format!("Couldn't use commit epoch value of {seconds}"),
+    ))?;
     Ok(timestamp)
 }
 
-/// Given a sha that exists in the 'main' branch of the datadog-agent repo, print
-/// the first nightly build that contains that change
-/// nightlies is assumed to be ordered from newest to oldest
+/// Given a sha that exists in the 'main' branch of the datadog-agent checkout at
+/// `repo_path`, return the first nightly build that contains that change
 ///
 /// # Errors
 /// - If the given sha is not found on the main branch
 /// - If no nightly is found containing the given sha
 /// - If the git repo cannot be opened
 pub fn get_first_nightly_containing_change(
+    repo_path: &Path,
     nightlies: &[Nightly],
     change_sha: &str,
 ) -> Result<Nightly> {
-    let repo = open_git_repo()?;
-    let origin_main = repo
-        .find_reference("refs/remotes/origin/main")?
-        .into_fully_peeled_id()?;
+    get_first_nightlies_containing_changes(repo_path, nightlies, &[change_sha.to_string()])?
+        .pop()
+        .unwrap_or_else(|| Err(anyhow::Error::msg("No result for the change")))
+}
 
-    let commit = get_commit_by_sha(&repo, change_sha, &origin_main)?;
-    let Some(_commit) = commit else {
-        print_friendly_git_may_be_stale_warning(change_sha);
-        anyhow::bail!("commit '{change_sha}' not found on 'main'");
-    };
+/// Like [`get_first_nightly_containing_change`] for several changes at once, walking the
+/// history a single time for all of them
+/// Returns one result per change, in the same order
+///
+/// # Errors
+/// - If the git repo cannot be opened
+/// - If the history cannot be walked
+pub fn get_first_nightlies_containing_changes(
+    repo_path: &Path,
+    nightlies: &[Nightly],
+    change_shas: &[String],
+) -> Result<Vec<Result<Nightly>>> {
+    get_first_nightlies_containing_changes_in(&open_git_repo(repo_path)?, nightlies, change_shas)
+}
 
-    let mut containing_nightly: Option<Nightly> = None;
+/// Like [`get_first_nightlies_containing_changes`], in the given checkout
+///
+/// # Errors
+/// - If 'origin/main' cannot be found
+/// - If the history cannot be walked
+pub fn get_first_nightlies_containing_changes_in(
+    repo: &Repository,
+    nightlies: &[Nightly],
+    change_shas: &[String],
+) -> Result<Vec<Result<Nightly>>> {
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?
+        .detach();
 
-    debug!("Searching for nightly containing sha: {}", change_sha);
+    let mut heads = Vec::new();
     for nightly in nightlies {
-        debug!(
-            "Checking if nightly-{} (last pushed: {}) contains the target sha",
-            nightly.sha, nightly.estimated_last_pushed
-        );
-
-        // I may be able to simplify all this by using repo.graph_descendant_of() instead of calling get_commit_by_sha
-        // I think these two do roughly the same thing
-        let current_nightly_head = match repo.rev_parse_single(nightly.sha.as_str()) {
-            Ok(obj) => obj,
+        match repo.rev_parse_single(nightly.sha.as_str()) {
+            Ok(head) => heads.push((nightly, head.detach())),
             Err(e) => {
                 warn!("Error finding nightly sha: {}", e);
-                print_friendly_git_may_be_stale_warning(nightly.sha.as_str());
-                continue;
+                print_commit_not_found_diagnostic(repo, nightly.sha.as_str());
             }
+        }
+    }
+    // Nightlies built from another branch aren't reachable from 'main', so their
+    // heads are walked too
+    let graph = CommitGraph::build(
+        repo,
+        std::iter::once(origin_main).chain(heads.iter().map(|(_, head)| *head)),
+    )?;
+
+    // Shas are resolved up front as the repository can't be shared between threads, the
+    // graph can
+    let changes: Vec<Option<ObjectId>> = change_shas
+        .iter()
+        .map(
+            |change_sha| match repo.rev_parse_single(change_sha.as_str()) {
+                Ok(change) if graph.is_ancestor(&change.detach(), &origin_main) => {
+                    Some(change.detach())
+                }
+                result => {
+                    if let Err(e) = result {
+                        warn!("Error finding sha: {}", e);
+                    }
+                    print_commit_not_found_diagnostic(repo, change_sha);
+                    None
+                }
+            },
+        )
+        .collect();
+    let workers = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+    let chunk_size = changes.len().div_ceil(workers).max(1);
+    let firsts: Vec<Option<&Nightly>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = changes
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(|| {
+                    chunk
+                        .iter()
+                        .map(|change| {
+                            change
+                                .and_then(|change| first_nightly_containing(&graph, &heads, change))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+
+    Ok(change_shas
+        .iter()
+        .zip(changes)
+        .zip(firsts)
+        .map(|((change_sha, change), first)| match (change, first) {
+            (None, _) => Err(NightlyError::CommitNotFound(change_sha.clone()).into()),
+            (Some(_), Some(nightly)) => Ok(nightly.clone()),
+            (Some(_), None) => Err(anyhow::Error::msg(format!(
+                "No nightly found containing commit: {change_sha}"
+            ))),
+        })
+        .collect())
+}
+
+/// The first nightly containing the change, the one built from the oldest commit as the
+/// caller's order can't be relied on
+fn first_nightly_containing<'a>(
+    graph: &CommitGraph,
+    heads: &[(&'a Nightly, ObjectId)],
+    change: ObjectId,
+) -> Option<&'a Nightly> {
+    debug!("Searching for nightly containing sha: {}", change);
+    let mut containing_nightly: Option<(&Nightly, Option<DateTime<Utc>>)> = None;
+    for (nightly, head) in heads {
+        if !graph.is_ancestor(&change, head) {
+            debug!("Didn't find commit: {} in nightly: {}", change, nightly.sha);
+            continue;
+        }
+        let key = (graph.commit_time(head), nightly.estimated_last_pushed);
+        match containing_nightly {
+            Some((first, time)) if (time, first.estimated_last_pushed) <= key => {}
+            _ => containing_nightly = Some((nightly, key.0)),
+        }
+    }
+    containing_nightly.map(|(nightly, _)| nightly)
+}
+
+/// Whether the branch name looks like an agent release branch, e.g. '7.55.x'
+fn is_release_branch(name: &str) -> bool {
+    let mut parts = name.split('.');
+    let is_number = |part: Option<&str>| {
+        part.is_some_and(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    };
+    is_number(parts.next())
+        && is_number(parts.next())
+        && parts.next() == Some("x")
+        && parts.next().is_none()
+}
+
+/// Finds the release branches ('origin/7.55.x') that were cut from 'main' between two of
+/// the given nightlies, which are expected oldest first
+/// Returns the branch names keyed by the sha of the first nightly containing the commit
+/// each branch was cut at
+///
+/// # Errors
+/// - If 'origin/main' cannot be found
+/// - If the history cannot be walked
+pub fn release_branch_cuts(
+    repo: &Repository,
+    nightlies: &[&Nightly],
+) -> Result<HashMap<String, Vec<String>>> {
+    let mut cuts: HashMap<String, Vec<String>> = HashMap::new();
+    let Some(since) = nightlies.iter().filter_map(|n| n.sha_timestamp).min() else {
+        return Ok(cuts);
+    };
+    // A day of slack for commits whose commit time is older than their parent's
+    let since = since - chrono::Duration::days(1);
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?
+        .detach();
+    let main_graph = CommitGraph::build_since(repo, Some(origin_main), since)?;
+    let heads: Vec<(&Nightly, Option<ObjectId>)> = nightlies
+        .iter()
+        .map(|n| {
+            (
+                *n,
+                repo.rev_parse_single(n.sha.as_str()).ok().map(Id::detach),
+            )
+        })
+        .collect();
+
+    let references = repo.references()?;
+    for mut reference in references
+        .prefixed("refs/remotes/origin/")?
+        .filter_map(Result::ok)
+    {
+        let name = reference.name().as_bstr().to_string();
+        let Some(branch) = name.strip_prefix("refs/remotes/origin/") else {
+            continue;
         };
-        //let current_nightly_head_commit = repo.find_commit(current_nightly_head_object.id())?;
-        if let Some(_commit) = get_commit_by_sha(&repo, change_sha, &current_nightly_head)? {
-            containing_nightly = Some(nightly.clone());
-        } else {
+        if !is_release_branch(branch) {
+            continue;
+        }
+        let tip = reference.peel_to_id_in_place()?.detach();
+        // The newest commit of the branch that is also on 'main' is where it was cut
+        let fork_point = repo
+            .rev_walk(Some(tip))
+            .sorting(Sorting::ByCommitTimeNewestFirstCutoffOlderThan {
+                seconds: since.timestamp(),
+            })
+            .all()?
+            .filter_map(Result::ok)
+            .find(|rev| main_graph.contains(&rev.id));
+        let Some(fork_point) = fork_point else {
             debug!(
-                "Didn't find commit: {} in nightly: {}",
-                change_sha, nightly.sha
+                "Release branch {} was cut before the listed nightlies",
+                branch
             );
+            continue;
+        };
+
+        let contains = |head: &Option<ObjectId>| {
+            head.is_some_and(|head| main_graph.is_ancestor(&fork_point.id, &head))
         };
+        let first = heads
+            .windows(2)
+            .find(|pair| !contains(&pair[0].1) && contains(&pair[1].1));
+        if let Some(pair) = first {
+            debug!(
+                "Release branch {} was cut at {} before nightly {}",
+                branch, fork_point.id, pair[1].0.sha
+            );
+            cuts.entry(pair[1].0.sha.clone())
+                .or_default()
+                .push(branch.to_string());
+        }
+    }
+    for branches in cuts.values_mut() {
+        branches.sort();
     }
+    Ok(cuts)
+}
+
+/// The commits that `newer` adds on top of `older`, newest first
+/// Returns None if `older` is not an ancestor of `newer`
+/// Ranges are remembered for the rest of the run, as the same pair of nightlies is often
+/// compared from several places
+fn get_commits_between<'repo>(
+    repo: &'repo Repository,
+    older: &Id,
+    newer: &Id,
+) -> Result<Option<Vec<Commit<'repo>>>> {
+    let key = (repo.git_dir().to_path_buf(), older.detach(), newer.detach());
+    let cached = lock_commit_ranges().get(&key);
+    let ids = if let Some(ids) = cached {
+        debug!("Reusing the commits between {} and {}", older, newer);
+        ids
+    } else {
+        // Nightlies on 'main' are answered from its graph, which is walked once per run
+        let origin_main = repo
+            .find_reference("refs/remotes/origin/main")
+            .ok()
+            .and_then(|reference| reference.into_fully_peeled_id().ok());
+        let graph_range = match origin_main {
+            Some(origin_main) => {
+                main_graph(repo, origin_main.detach())?.range(&newer.detach(), &older.detach())
+            }
+            None => None,
+        };
+        let range = match graph_range {
+            Some(range) => range,
+            None => walk_range(repo, newer.detach(), older.detach())?,
+        };
+        let ids = range.hidden_is_ancestor.then_some(range.commits);
+        lock_commit_ranges().insert(key, ids.clone());
+        ids
+    };
+    let Some(ids) = ids else {
+        return Ok(None);
+    };
+    ids.into_iter()
+        .map(|id| Ok(repo.find_object(id)?.try_into_commit()?))
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+type CommitRangeKey = (PathBuf, ObjectId, ObjectId);
+
+/// Commit ranges listed during this run, per repository, older and newer commit
+/// None means the older commit is not an ancestor of the newer one
+static COMMIT_RANGES: LazyLock<Mutex<Lru<CommitRangeKey, Option<Vec<ObjectId>>>>> =
+    LazyLock::new(|| Mutex::new(Lru::new(256)));
+
+fn lock_commit_ranges() -> MutexGuard<'static, Lru<CommitRangeKey, Option<Vec<ObjectId>>>> {
+    COMMIT_RANGES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Counts the commits that `newer_sha` adds on top of `older_sha`
+///
+/// # Errors
+/// - If either sha cannot be found
+/// - If `older_sha` is not an ancestor of `newer_sha`
+pub fn count_commits_between(repo: &Repository, older_sha: &str, newer_sha: &str) -> Result<usize> {
+    let older = repo.rev_parse_single(older_sha)?;
+    let newer = repo.rev_parse_single(newer_sha)?;
+    let Some(commits) = get_commits_between(repo, &older, &newer)? else {
+        anyhow::bail!("'{older_sha}' is not an ancestor of '{newer_sha}'");
+    };
+    Ok(commits.len())
+}
+
+/// The Go version `.go-version` pins, or else the `toolchain` (or `go`) directive of go.mod
+fn parse_go_version(go_version: Option<&str>, go_mod: Option<&str>) -> Option<String> {
+    if let Some(version) = go_version.map(str::trim).filter(|v| !v.is_empty()) {
+        return Some(version.to_string());
+    }
+    let directive = |name: &str| {
+        go_mod?.lines().find_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next() == Some(name)).then(|| words.next()).flatten()
+        })
+    };
+    directive("toolchain")
+        .map(|toolchain| toolchain.trim_start_matches("go"))
+        .or_else(|| directive("go"))
+        .map(String::from)
+}
 
-    containing_nightly.ok_or_else(|| {
-        anyhow::Error::msg(format!("No nightly found containing commit: {change_sha}"))
+/// Returns the Go toolchain version the datadog-agent was built with at `sha`, read from
+/// `.go-version` or go.mod, None when neither file names one
+///
+/// # Errors
+/// - If the sha cannot be found
+/// - If the commit's files cannot be read
+pub fn go_version_at(repo: &Repository, sha: &str) -> Result<Option<String>> {
+    let tree = repo.rev_parse_single(sha)?.object()?.into_commit().tree()?;
+    let mut buf = Vec::new();
+    let mut read = |path: &str| -> Result<Option<String>> {
+        let Some(entry) = tree.lookup_entry_by_path(path, &mut buf)? else {
+            return Ok(None);
+        };
+        let blob = entry.object()?;
+        Ok(Some(String::from_utf8_lossy(&blob.data).into_owned()))
+    };
+    let go_version = read(".go-version")?;
+    let go_mod = read("go.mod")?;
+    Ok(parse_go_version(go_version.as_deref(), go_mod.as_deref()))
+}
+
+/// Returns the commits (short sha and subject) that `newer_sha` adds on top of `older_sha`,
+/// newest first
+/// Commits only changing ignored paths are left out
+///
+/// # Errors
+/// - If either sha cannot be found
+/// - If `older_sha` is not an ancestor of `newer_sha`
+pub fn list_commit_range(
+    repo: &Repository,
+    older_sha: &str,
+    newer_sha: &str,
+    ignore: &IgnorePaths,
+) -> Result<Vec<String>> {
+    let older = repo.rev_parse_single(older_sha)?;
+    let newer = repo.rev_parse_single(newer_sha)?;
+    let Some(commits) = get_commits_between(repo, &older, &newer)? else {
+        anyhow::bail!("'{older_sha}' is not an ancestor of '{newer_sha}'");
+    };
+
+    let mut listed = Vec::new();
+    for commit in commits {
+        if !ignore.is_empty() && ignore.ignores_commit(get_changed_files(repo, &commit)?.iter()) {
+            continue;
+        }
+        let subject = commit.message()?.summary().to_string();
+        listed.push(format!("{} {subject}", commit.id().shorten_or_id()));
+    }
+    Ok(listed)
+}
+
+/// Like [`list_commit_range`], keeping the commits whose subject contains `pattern`,
+/// ignoring case
+///
+/// # Errors
+/// - If either sha cannot be found
+/// - If `older_sha` is not an ancestor of `newer_sha`
+pub fn grep_commit_range(
+    repo: &Repository,
+    older_sha: &str,
+    newer_sha: &str,
+    pattern: &str,
+    ignore: &IgnorePaths,
+) -> Result<Vec<String>> {
+    let pattern = pattern.to_lowercase();
+    Ok(list_commit_range(repo, older_sha, newer_sha, ignore)?
+        .into_iter()
+        .filter(|commit| {
+            let subject = commit.split_once(' ').map_or("", |(_, subject)| subject);
+            subject.to_lowercase().contains(&pattern)
+        })
+        .collect())
+}
+
+/// The full sha of the commit a git revision (branch, tag, sha, ...) of the datadog-agent
+/// checkout points at, None if there is no such revision
+#[must_use]
+pub fn resolve_revision(repo: &Repository, revision: &str) -> Option<String> {
+    let commit = repo
+        .rev_parse_single(revision)
+        .ok()?
+        .object()
+        .ok()?
+        .peel_to_kind(gix::object::Kind::Commit)
+        .ok()?;
+    Some(commit.id.to_string())
+}
+
+/// The full sha of the newest commit on 'main' that merged the given pull request, going
+/// by the `(#N)` GitHub appends to squash merge subjects
+/// The walk of 'main' stops at commits older than `since`, e.g. the commit of the oldest
+/// nightly a merge could be found in
+/// None if there is no such commit
+///
+/// # Errors
+/// - If 'origin/main' cannot be found or walked
+pub fn find_pull_request_commit(
+    repo: &Repository,
+    number: u64,
+    since: DateTime<Utc>,
+) -> Result<Option<String>> {
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?
+        .detach();
+    let suffix = format!("(#{number})");
+    let walk = repo
+        .rev_walk(Some(origin_main))
+        .sorting(Sorting::ByCommitTimeNewestFirstCutoffOlderThan {
+            seconds: since.timestamp(),
+        })
+        .all()?;
+    for rev in walk {
+        let commit = rev?.object()?;
+        if commit
+            .message()?
+            .summary()
+            .to_string()
+            .trim_end()
+            .ends_with(&suffix)
+        {
+            return Ok(Some(commit.id.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Summary of the commits on 'main' that have not made it into a nightly yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingCommits {
+    pub num_commits: usize,
+    /// Commit authors and their number of pending commits, most active first
+    pub authors: Vec<(String, usize)>,
+    /// Top-level paths and the number of pending commits touching them, busiest first
+    pub paths: Vec<(String, usize)>,
+}
+
+/// Calls `f` with the location of every entry the given commit changes over its first
+/// parent, and whether that entry is a tree
+fn for_each_changed_path(
+    repo: &Repository,
+    commit: &Commit,
+    mut f: impl FnMut(String, bool),
+) -> Result<()> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent_ids().next() {
+        Some(parent_id) => parent_id.object()?.into_commit().tree()?,
+        None => repo.empty_tree(),
+    };
+
+    parent_tree
+        .changes()?
+        .track_path()
+        .track_rewrites(None)
+        .for_each_to_obtain_tree(&tree, |change| {
+            f(
+                change.location.to_string(),
+                change.event.entry_mode().is_tree(),
+            );
+            Ok::<_, Infallible>(Action::Continue)
+        })?;
+    Ok(())
+}
+
+/// Returns the top-level directories (with a trailing '/') and files changed by the given commit
+fn get_changed_top_level_paths(repo: &Repository, commit: &Commit) -> Result<HashSet<String>> {
+    let mut paths = HashSet::new();
+    for_each_changed_path(repo, commit, |location, is_tree| {
+        let top_level = match location.split_once('/') {
+            Some((dir, _)) => format!("{dir}/"),
+            None if is_tree => format!("{location}/"),
+            None => location,
+        };
+        paths.insert(top_level);
+    })?;
+    Ok(paths)
+}
+
+/// Returns the top-level directories (with a trailing '/') and files of the given files
+fn top_level_paths<'a>(files: impl Iterator<Item = &'a String>) -> HashSet<String> {
+    files
+        .map(|file| match file.split_once('/') {
+            Some((dir, _)) => format!("{dir}/"),
+            None => file.clone(),
+        })
+        .collect()
+}
+
+/// Returns the files changed by the given commit
+fn get_changed_files(repo: &Repository, commit: &Commit) -> Result<HashSet<String>> {
+    let mut files = HashSet::new();
+    for_each_changed_path(repo, commit, |location, is_tree| {
+        if !is_tree {
+            files.insert(location);
+        }
+    })?;
+    Ok(files)
+}
+
+fn sorted_counts(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// Summarizes the commits on 'main' of the datadog-agent repo that are newer than
+/// the given nightly sha, ie what is queued up for the next nightly build
+/// Ignored paths are left out, along with the commits only changing them
+///
+/// # Errors
+/// - If the given sha is not found on the main branch
+/// - If the git repo cannot be opened
+pub fn get_pending_commits(
+    repo_path: &Path,
+    latest_nightly_sha: &str,
+    ignore: &IgnorePaths,
+) -> Result<PendingCommits> {
+    let repo = open_git_repo(repo_path)?;
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?;
+
+    let Ok(nightly_head) = repo.rev_parse_single(latest_nightly_sha) else {
+        print_commit_not_found_diagnostic(&repo, latest_nightly_sha);
+        return Err(NightlyError::CommitNotFound(latest_nightly_sha.to_string()).into());
+    };
+
+    let Some(commits) = get_commits_between(&repo, &nightly_head, &origin_main)? else {
+        print_commit_not_found_diagnostic(&repo, latest_nightly_sha);
+        return Err(NightlyError::CommitNotFound(latest_nightly_sha.to_string()).into());
+    };
+
+    let mut num_commits = 0;
+    let mut authors: HashMap<String, usize> = HashMap::new();
+    let mut paths: HashMap<String, usize> = HashMap::new();
+    for commit in &commits {
+        // Without globs the top-level paths are all that is needed, no file list is built
+        let top_level = if ignore.is_empty() {
+            get_changed_top_level_paths(&repo, commit)?
+        } else {
+            let files = get_changed_files(&repo, commit)?;
+            if ignore.ignores_commit(files.iter()) {
+                continue;
+            }
+            top_level_paths(files.iter().filter(|file| !ignore.is_ignored(file)))
+        };
+        num_commits += 1;
+        *authors
+            .entry(commit.author()?.name.to_string())
+            .or_default() += 1;
+        for path in top_level {
+            *paths.entry(path).or_default() += 1;
+        }
+    }
+
+    Ok(PendingCommits {
+        num_commits,
+        authors: sorted_counts(authors),
+        paths: sorted_counts(paths),
     })
 }
+
+/// Files that a branch and a range of 'main' both change, ie where they may conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// Files changed on both sides, sorted
+    pub files: Vec<String>,
+    /// Commits of the range (short sha and subject) with the overlapping files they change,
+    /// newest first
+    pub commits: Vec<(String, Vec<String>)>,
+}
+
+/// Compares the files changed by the commits `newer_sha` adds on top of `older_sha` with
+/// the files changed by the commits of `branch` that aren't on 'main'
+/// Merge commits of the branch are left out, as they mostly bring in changes from 'main',
+/// and so are ignored paths
+///
+/// # Errors
+/// - If either sha or the branch cannot be found
+/// - If `older_sha` is not an ancestor of `newer_sha`
+pub fn branch_overlap(
+    repo: &Repository,
+    older_sha: &str,
+    newer_sha: &str,
+    branch: &str,
+    ignore: &IgnorePaths,
+) -> Result<Overlap> {
+    let older = repo.rev_parse_single(older_sha)?;
+    let newer = repo.rev_parse_single(newer_sha)?;
+    let Some(range) = get_commits_between(repo, &older, &newer)? else {
+        anyhow::bail!("'{older_sha}' is not an ancestor of '{newer_sha}'");
+    };
+    let branch_tip = repo
+        .rev_parse_single(branch)
+        .map_err(|e| NightlyError::GitError(format!("Could not find branch '{branch}': {e}")))?
+        .detach();
+
+    let origin_main = repo
+        .find_reference("refs/remotes/origin/main")?
+        .into_fully_peeled_id()?
+        .detach();
+    let mut branch_files = HashSet::new();
+    for id in walk_range(repo, branch_tip, origin_main)?.commits {
+        let commit = repo.find_object(id)?.into_commit();
+        if commit.parent_ids().count() > 1 {
+            continue;
+        }
+        branch_files.extend(
+            get_changed_files(repo, &commit)?
+                .into_iter()
+                .filter(|file| !ignore.is_ignored(file)),
+        );
+    }
+    debug!("Branch {} changes {} files", branch, branch_files.len());
+
+    let mut files = HashSet::new();
+    let mut commits = Vec::new();
+    for commit in range {
+        let mut overlapping: Vec<String> = get_changed_files(repo, &commit)?
+            .into_iter()
+            .filter(|file| branch_files.contains(file))
+            .collect();
+        if overlapping.is_empty() {
+            continue;
+        }
+        overlapping.sort();
+        files.extend(overlapping.iter().cloned());
+        let subject = commit.message()?.summary().to_string();
+        commits.push((
+            format!("{} {subject}", commit.id().shorten_or_id()),
+            overlapping,
+        ));
+    }
+    let mut files: Vec<String> = files.into_iter().collect();
+    files.sort();
+    Ok(Overlap { files, commits })
+}
@@ -0,0 +1,206 @@
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use gix::{traverse::commit::simple::Sorting, ObjectId, Repository};
+use tracing::debug;
+
+use super::range::Range;
+
+// Answering "is A an ancestor of B" with a rev-walk per question gets slow when the
+// questions come in batches, e.g. one per nightly. The graph is walked once instead and
+// every question is answered from memory
+
+/// The commits reachable from a set of tips, with their parents and commit times
+pub struct CommitGraph {
+    index: HashMap<ObjectId, usize>,
+    ids: Vec<ObjectId>,
+    parents: Vec<Vec<usize>>,
+    times: Vec<i64>,
+    /// 1 for root commits, 1 + the highest generation of the parents otherwise
+    /// An ancestor always has a lower generation than its descendants, which bounds
+    /// ancestry searches
+    generations: Vec<u32>,
+}
+
+impl CommitGraph {
+    /// Walks every commit reachable from any of the `tips`
+    ///
+    /// # Errors
+    /// - If the history cannot be walked
+    pub fn build(repo: &Repository, tips: impl IntoIterator<Item = ObjectId>) -> Result<Self> {
+        Self::walk(repo, tips, Sorting::ByCommitTimeNewestFirst)
+    }
+
+    /// Like [`CommitGraph::build`], leaving out commits older than `since`
+    /// Ancestry is only known within the window
+    ///
+    /// # Errors
+    /// - If the history cannot be walked
+    pub fn build_since(
+        repo: &Repository,
+        tips: impl IntoIterator<Item = ObjectId>,
+        since: DateTime<Utc>,
+    ) -> Result<Self> {
+        let sorting = Sorting::ByCommitTimeNewestFirstCutoffOlderThan {
+            seconds: since.timestamp(),
+        };
+        Self::walk(repo, tips, sorting)
+    }
+
+    fn walk(
+        repo: &Repository,
+        tips: impl IntoIterator<Item = ObjectId>,
+        sorting: Sorting,
+    ) -> Result<Self> {
+        let mut index = HashMap::new();
+        let mut ids = Vec::new();
+        let mut parent_ids = Vec::new();
+        let mut times = Vec::new();
+        let walk = repo.rev_walk(tips).sorting(sorting).all()?;
+        for info in walk {
+            let info = info?;
+            index.insert(info.id, times.len());
+            ids.push(info.id);
+            parent_ids.push(info.parent_ids.to_vec());
+            times.push(info.commit_time.unwrap_or_default());
+        }
+        let parents: Vec<Vec<usize>> = parent_ids
+            .iter()
+            .map(|ids| ids.iter().filter_map(|id| index.get(id).copied()).collect())
+            .collect();
+        let generations = compute_generations(&parents);
+        debug!("Built a commit graph of {} commits", times.len());
+
+        Ok(Self {
+            index,
+            ids,
+            parents,
+            times,
+            generations,
+        })
+    }
+
+    /// Whether the commit is reachable from the tips
+    #[must_use]
+    pub fn contains(&self, id: &ObjectId) -> bool {
+        self.index.contains_key(id)
+    }
+
+    /// The commit time of a commit in the graph
+    #[must_use]
+    pub fn commit_time(&self, id: &ObjectId) -> Option<DateTime<Utc>> {
+        let i = *self.index.get(id)?;
+        DateTime::from_timestamp(self.times[i], 0)
+    }
+
+    /// Whether `ancestor` is reachable from `descendant`, a commit is its own ancestor
+    /// False if either commit is not in the graph
+    #[must_use]
+    pub fn is_ancestor(&self, ancestor: &ObjectId, descendant: &ObjectId) -> bool {
+        let (Some(&ancestor), Some(&descendant)) =
+            (self.index.get(ancestor), self.index.get(descendant))
+        else {
+            return false;
+        };
+        let min_generation = self.generations[ancestor];
+        // Only commits above the ancestor's generation are visited, usually few enough
+        // that a set beats clearing a buffer as large as the graph
+        let mut seen = HashSet::from([descendant]);
+        let mut queue = VecDeque::from([descendant]);
+        while let Some(commit) = queue.pop_front() {
+            if commit == ancestor {
+                return true;
+            }
+            for &parent in &self.parents[commit] {
+                if self.generations[parent] >= min_generation && seen.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        false
+    }
+
+    /// The commits reachable from `tip` but not from `hidden`, like `git log hidden..tip`
+    /// None if either commit is not in the graph
+    /// Commits are visited highest generation first, so every commit is reached by all
+    /// its children before it's visited and no commit date is trusted
+    pub(crate) fn range(&self, tip: &ObjectId, hidden: &ObjectId) -> Option<Range> {
+        const TIP: u8 = 1;
+        const HIDDEN: u8 = 2;
+        let (Some(&tip), Some(&hidden)) = (self.index.get(tip), self.index.get(hidden)) else {
+            return None;
+        };
+        let mut flags: HashMap<usize, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (commit, flag) in [(tip, TIP), (hidden, HIDDEN)] {
+            *flags.entry(commit).or_default() |= flag;
+            queue.push((self.generations[commit], commit));
+        }
+
+        // Until the tip side reaches `hidden`, its commits above the generation of
+        // `hidden` may still lead there
+        let interesting = |flags: &HashMap<usize, u8>, commit: usize| {
+            let flag = flags[&commit];
+            flag & HIDDEN == 0
+                || (flags[&hidden] & TIP == 0
+                    && flag & TIP != 0
+                    && self.generations[commit] > self.generations[hidden])
+        };
+        while queue.iter().any(|&(_, commit)| interesting(&flags, commit)) {
+            let Some((_, commit)) = queue.pop() else {
+                break;
+            };
+            let flag = flags[&commit];
+            for &parent in &self.parents[commit] {
+                let parent_flag = flags.entry(parent).or_default();
+                if *parent_flag | flag != *parent_flag {
+                    *parent_flag |= flag;
+                    queue.push((self.generations[parent], parent));
+                }
+            }
+        }
+
+        let mut commits: Vec<usize> = flags
+            .iter()
+            .filter(|(_, &flag)| flag == TIP)
+            .map(|(&commit, _)| commit)
+            .collect();
+        commits.sort_by_key(|&commit| {
+            std::cmp::Reverse((self.times[commit], self.generations[commit]))
+        });
+        Some(Range {
+            commits: commits.into_iter().map(|commit| self.ids[commit]).collect(),
+            hidden_is_ancestor: flags[&hidden] & TIP != 0,
+        })
+    }
+}
+
+/// Generation numbers of every commit, without recursing so deep histories are fine
+fn compute_generations(parents: &[Vec<usize>]) -> Vec<u32> {
+    let mut generations = vec![0; parents.len()];
+    for start in 0..parents.len() {
+        if generations[start] != 0 {
+            continue;
+        }
+        let mut stack = vec![start];
+        while let Some(&commit) = stack.last() {
+            let pending: Vec<usize> = parents[commit]
+                .iter()
+                .copied()
+                .filter(|&p| generations[p] == 0)
+                .collect();
+            if pending.is_empty() {
+                stack.pop();
+                generations[commit] = 1 + parents[commit]
+                    .iter()
+                    .map(|&p| generations[p])
+                    .max()
+                    .unwrap_or(0);
+            } else {
+                stack.extend(pending);
+            }
+        }
+    }
+    generations
+}
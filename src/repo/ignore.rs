@@ -0,0 +1,69 @@
+use gix::{
+    bstr::BStr,
+    glob::{pattern::Case, wildmatch, Pattern},
+};
+
+use crate::NightlyError;
+
+/// Paths left out of commit ranges, as gitignore-style globs, e.g. `test/**` or `*.md`,
+/// with `!` globs bringing paths back
+#[derive(Debug, Clone, Default)]
+pub struct IgnorePaths {
+    patterns: Vec<Pattern>,
+}
+
+impl IgnorePaths {
+    /// # Errors
+    /// - If a pattern is empty or otherwise not a glob
+    pub fn new<S: AsRef<str>>(globs: &[S]) -> Result<Self, NightlyError> {
+        let patterns = globs
+            .iter()
+            .map(|glob| {
+                let glob = glob.as_ref();
+                gix::glob::parse(glob)
+                    .ok_or_else(|| NightlyError::GlobError(format!("'{glob}' is not a path glob")))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the file at the given repository relative path is ignored
+    /// As in a .gitignore the last matching glob decides, so `!` globs bring back paths
+    /// that earlier globs ignore
+    #[must_use]
+    pub fn is_ignored(&self, path: &str) -> bool {
+        let basename_start = path.rfind('/').map(|slash| slash + 1);
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| {
+                pattern.matches_repo_relative_path(
+                    BStr::new(path),
+                    basename_start,
+                    Some(false),
+                    Case::Sensitive,
+                    wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+                )
+            })
+            .is_some_and(|pattern| !pattern.is_negative())
+    }
+
+    /// Whether a commit changing the given files is left out, ie whether it changes
+    /// files and all of them are ignored
+    pub fn ignores_commit<'a>(&self, mut files: impl Iterator<Item = &'a String>) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let mut changes_files = false;
+        let all_ignored = files.all(|file| {
+            changes_files = true;
+            self.is_ignored(file)
+        });
+        changes_files && all_ignored
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use anyhow::Result;
+use gix::{ObjectId, Repository};
+
+// The commits a tip adds on top of another commit, like `git log hidden..tip`. Both sides
+// are walked together newest first, every commit is marked with the sides it's reachable
+// from, and the walk ends once only commits reachable from `hidden` are left to visit.
+// Unlike walking back from `tip` until `hidden` shows up, this holds with merges, and it
+// stops at the merge base when `hidden` is on another line of history
+
+/// Reachable from the tip
+const TIP: u8 = 1;
+/// Reachable from the hidden commit
+const HIDDEN: u8 = 2;
+
+/// Pops made after the walk could have ended, in case a commit is dated before one of
+/// its parents
+const SLOP: usize = 5;
+
+/// The commits a tip adds on top of a hidden commit, see [`walk_range`]
+#[derive(Debug, Clone)]
+pub(crate) struct Range {
+    /// Commits reachable from the tip but not from the hidden commit, newest first
+    pub(crate) commits: Vec<ObjectId>,
+    /// Whether the hidden commit is an ancestor of the tip, a commit is its own ancestor
+    pub(crate) hidden_is_ancestor: bool,
+}
+
+/// Lists the commits reachable from `tip` but not from `hidden`
+///
+/// # Errors
+/// - If a commit of either side cannot be read
+pub(crate) fn walk_range(repo: &Repository, tip: ObjectId, hidden: ObjectId) -> Result<Range> {
+    let mut times: HashMap<ObjectId, i64> = HashMap::new();
+    let mut flags: HashMap<ObjectId, u8> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let hidden_time = commit_time(repo, &mut times, hidden)?;
+    for (id, flag) in [(tip, TIP), (hidden, HIDDEN)] {
+        *flags.entry(id).or_default() |= flag;
+        queue.push((commit_time(repo, &mut times, id)?, id));
+    }
+
+    // Until the tip side reaches `hidden`, its commits dated after `hidden` may still lead
+    // there and are visited too
+    let interesting = |flags: &HashMap<ObjectId, u8>, time: i64, id: &ObjectId| {
+        let flag = flags[id];
+        flag & HIDDEN == 0 || (flags[&hidden] & TIP == 0 && flag & TIP != 0 && time >= hidden_time)
+    };
+    let mut slop = SLOP;
+    while let Some((_, id)) = queue.pop() {
+        let flag = flags[&id];
+        let commit = repo.find_object(id)?.try_into_commit()?;
+        for parent in commit.parent_ids() {
+            let parent = parent.detach();
+            let parent_flag = flags.entry(parent).or_default();
+            if *parent_flag | flag != *parent_flag {
+                *parent_flag |= flag;
+                queue.push((commit_time(repo, &mut times, parent)?, parent));
+            }
+        }
+        if queue
+            .iter()
+            .any(|(time, id)| interesting(&flags, *time, id))
+        {
+            slop = SLOP;
+        } else if slop == 0 {
+            break;
+        } else {
+            slop -= 1;
+        }
+    }
+
+    let mut commits: Vec<ObjectId> = flags
+        .iter()
+        .filter(|(_, &flag)| flag == TIP)
+        .map(|(id, _)| *id)
+        .collect();
+    commits.sort_by_key(|id| std::cmp::Reverse((times[id], *id)));
+    Ok(Range {
+        commits,
+        hidden_is_ancestor: flags[&hidden] & TIP != 0,
+    })
+}
+
+fn commit_time(repo: &Repository, times: &mut HashMap<ObjectId, i64>, id: ObjectId) -> Result<i64> {
+    if let Some(&time) = times.get(&id) {
+        return Ok(time);
+    }
+    let time = repo.find_object(id)?.try_into_commit()?.time()?.seconds;
+    times.insert(id, time);
+    Ok(time)
+}
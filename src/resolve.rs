@@ -0,0 +1,207 @@
+use std::{fmt, path::Path};
+
+use crate::nightly::Nightly;
+
+mod date;
+mod digest;
+#[cfg(feature = "client")]
+mod git_ref;
+mod index;
+#[cfg(feature = "client")]
+mod pull_request;
+mod sha;
+mod tag;
+
+pub use date::DateResolver;
+pub use digest::DigestResolver;
+#[cfg(feature = "client")]
+pub use git_ref::GitRefResolver;
+pub use index::IndexResolver;
+#[cfg(feature = "client")]
+pub use pull_request::PullRequestResolver;
+pub use sha::ShaResolver;
+pub use tag::TagResolver;
+
+// Identifiers given on the command line are tried against a chain of resolvers, each
+// recognizing one way of naming a nightly. Commands pick the kinds of identifiers that
+// make sense for them
+
+/// A kind of identifier naming a nightly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResolverClass {
+    /// `@0`, `@1`, ... for the latest, previous, ... nightly
+    Index,
+    /// An image digest or a prefix of it, e.g. `sha256:0123abcd`
+    Digest,
+    /// A tag name, with or without the image repository, e.g. `nightly-main-0123abcd-py3`
+    Tag,
+    /// The 8 character sha a nightly was built from
+    Sha,
+    /// The latest nightly at a date or RFC3339 instant
+    Date,
+    /// `#N`, the first nightly containing the merge of pull request N
+    #[cfg(feature = "client")]
+    PullRequest,
+    /// A branch, tag or commit of the datadog-agent checkout, the first nightly containing it
+    #[cfg(feature = "client")]
+    GitRef,
+}
+
+impl ResolverClass {
+    /// Every class, in the order they are tried
+    pub const ALL: &'static [ResolverClass] = &[
+        ResolverClass::Index,
+        ResolverClass::Digest,
+        ResolverClass::Tag,
+        ResolverClass::Sha,
+        ResolverClass::Date,
+        #[cfg(feature = "client")]
+        ResolverClass::PullRequest,
+        #[cfg(feature = "client")]
+        ResolverClass::GitRef,
+    ];
+
+    /// Classes naming one exact image without looking at git history
+    pub const BUILDS: &'static [ResolverClass] = &[
+        ResolverClass::Index,
+        ResolverClass::Digest,
+        ResolverClass::Tag,
+        ResolverClass::Sha,
+    ];
+
+    /// The resolver of the class, None for the classes that need a checkout when none
+    /// is given
+    #[cfg_attr(
+        not(feature = "client"),
+        allow(unused_variables, clippy::unnecessary_wraps)
+    )]
+    fn resolver(self, repo_path: Option<&Path>) -> Option<Box<dyn Resolver>> {
+        let resolver: Box<dyn Resolver> = match self {
+            ResolverClass::Index => Box::new(IndexResolver),
+            ResolverClass::Digest => Box::new(DigestResolver),
+            ResolverClass::Tag => Box::new(TagResolver),
+            ResolverClass::Sha => Box::new(ShaResolver),
+            ResolverClass::Date => Box::new(DateResolver),
+            #[cfg(feature = "client")]
+            ResolverClass::PullRequest => Box::new(PullRequestResolver {
+                repo_path: repo_path?.to_path_buf(),
+            }),
+            #[cfg(feature = "client")]
+            ResolverClass::GitRef => Box::new(GitRefResolver {
+                repo_path: repo_path?.to_path_buf(),
+            }),
+        };
+        Some(resolver)
+    }
+}
+
+impl fmt::Display for ResolverClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResolverClass::Index => "@N index",
+            ResolverClass::Digest => "digest",
+            ResolverClass::Tag => "tag",
+            ResolverClass::Sha => "build sha",
+            ResolverClass::Date => "date",
+            #[cfg(feature = "client")]
+            ResolverClass::PullRequest => "#PR",
+            #[cfg(feature = "client")]
+            ResolverClass::GitRef => "git ref",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What a resolver makes of an identifier
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution<'a> {
+    /// Not the kind of identifier the resolver handles, the next one is tried
+    Unrecognized,
+    /// The nightly the identifier names
+    Found(&'a Nightly),
+    /// The kind of identifier the resolver handles, naming no known nightly
+    Missing,
+}
+
+impl<'a> Resolution<'a> {
+    /// The nightly found, if any
+    #[must_use]
+    pub fn nightly(self) -> Option<&'a Nightly> {
+        match self {
+            Resolution::Found(nightly) => Some(nightly),
+            Resolution::Unrecognized | Resolution::Missing => None,
+        }
+    }
+}
+
+/// A recognized identifier's lookup, None meaning it names no known nightly
+impl<'a> From<Option<&'a Nightly>> for Resolution<'a> {
+    fn from(nightly: Option<&'a Nightly>) -> Self {
+        nightly.map_or(Resolution::Missing, Resolution::Found)
+    }
+}
+
+/// One way of naming a nightly
+pub trait Resolver {
+    fn class(&self) -> ResolverClass;
+
+    /// The nightly the identifier names, or whether this resolver recognizes it at all
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a>;
+}
+
+/// A chain of resolvers, the first one to recognize an identifier decides
+pub struct Resolvers {
+    chain: Vec<Box<dyn Resolver>>,
+}
+
+impl Resolvers {
+    /// The resolvers of the given classes, tried in the order of [`ResolverClass::ALL`]
+    /// The classes looking in the datadog-agent checkout are left out, see
+    /// [`Resolvers::accepting_in`]
+    #[must_use]
+    pub fn accepting(classes: &[ResolverClass]) -> Self {
+        Self::chain(classes, None)
+    }
+
+    /// Like [`Resolvers::accepting`], with the classes looking in the datadog-agent
+    /// checkout at `repo_path`
+    #[cfg(feature = "client")]
+    #[must_use]
+    pub fn accepting_in(classes: &[ResolverClass], repo_path: &Path) -> Self {
+        Self::chain(classes, Some(repo_path))
+    }
+
+    fn chain(classes: &[ResolverClass], repo_path: Option<&Path>) -> Self {
+        let chain = ResolverClass::ALL
+            .iter()
+            .filter(|class| classes.contains(class))
+            .filter_map(|class| class.resolver(repo_path))
+            .collect();
+        Self { chain }
+    }
+
+    /// Adds a resolver at the end of the chain
+    #[must_use]
+    pub fn with(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.chain.push(Box::new(resolver));
+        self
+    }
+
+    /// The classes of the resolvers in the chain, in order
+    #[must_use]
+    pub fn classes(&self) -> Vec<ResolverClass> {
+        self.chain.iter().map(|r| r.class()).collect()
+    }
+
+    /// What the first resolver recognizing the identifier makes of it, later resolvers
+    /// aren't tried even if it names no nightly, e.g. a sha that isn't a build
+    #[must_use]
+    pub fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let identifier = identifier.trim();
+        self.chain
+            .iter()
+            .map(|resolver| resolver.resolve(nightlies, identifier))
+            .find(|resolution| *resolution != Resolution::Unrecognized)
+            .unwrap_or(Resolution::Unrecognized)
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use super::{Resolution, Resolver, ResolverClass};
+use crate::{nightly::Nightly, query::latest_as_of};
+
+/// Resolves a date or RFC3339 instant to the latest nightly pushed by then, a date means
+/// the end of that day in UTC
+pub struct DateResolver;
+
+fn parse_instant(identifier: &str) -> Option<DateTime<Utc>> {
+    if let Ok(instant) = DateTime::parse_from_rfc3339(identifier) {
+        return Some(instant.into());
+    }
+    let date = NaiveDate::parse_from_str(identifier, "%Y-%m-%d").ok()?;
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).expect("Invalid time");
+    Some(date.and_time(end_of_day).and_utc())
+}
+
+impl Resolver for DateResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::Date
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let Some(instant) = parse_instant(identifier) else {
+            return Resolution::Unrecognized;
+        };
+        latest_as_of(nightlies, instant).into()
+    }
+}
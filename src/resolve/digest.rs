@@ -0,0 +1,29 @@
+use super::{Resolution, Resolver, ResolverClass};
+use crate::nightly::Nightly;
+
+/// Shortest digest prefix accepted after `sha256:`, shorter ones are too likely to be
+/// ambiguous
+const MIN_DIGEST_PREFIX_LEN: usize = 8;
+
+/// Resolves an image digest, or a prefix of one, to the nightly with an image of that digest
+pub struct DigestResolver;
+
+impl Resolver for DigestResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::Digest
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let Some(hex) = identifier.strip_prefix("sha256:") else {
+            return Resolution::Unrecognized;
+        };
+        if hex.len() < MIN_DIGEST_PREFIX_LEN || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Resolution::Unrecognized;
+        }
+        let prefix = identifier.to_lowercase();
+        nightlies
+            .iter()
+            .find(|n| n.tags().any(|t| t.digest.starts_with(&prefix)))
+            .into()
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use super::{Resolution, Resolver, ResolverClass};
+use crate::{
+    nightly::Nightly,
+    repo::{get_first_nightly_containing_change, resolve_revision},
+};
+
+/// Resolves a branch, tag or commit of the datadog-agent checkout to the first nightly
+/// containing the commit it points at
+pub struct GitRefResolver {
+    /// The datadog-agent checkout the identifiers are looked up in
+    pub repo_path: PathBuf,
+}
+
+impl Resolver for GitRefResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::GitRef
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let Some(commit) = gix::open(&self.repo_path)
+            .ok()
+            .and_then(|repo| resolve_revision(&repo, identifier))
+        else {
+            return Resolution::Unrecognized;
+        };
+        let first = get_first_nightly_containing_change(&self.repo_path, nightlies, &commit).ok();
+        first
+            .and_then(|first| nightlies.iter().find(|n| n.sha == first.sha))
+            .into()
+    }
+}
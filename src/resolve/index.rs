@@ -0,0 +1,21 @@
+use super::{Resolution, Resolver, ResolverClass};
+use crate::{
+    nightly::Nightly,
+    query::{nth_latest, parse_index_shorthand},
+};
+
+/// Resolves `@N` to the Nth latest nightly by commit time, `@0` being the latest
+pub struct IndexResolver;
+
+impl Resolver for IndexResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::Index
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let Some(n) = parse_index_shorthand(identifier) else {
+            return Resolution::Unrecognized;
+        };
+        nth_latest(nightlies, n).into()
+    }
+}
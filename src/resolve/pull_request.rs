@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use tracing::warn;
+
+use super::{Resolution, Resolver, ResolverClass};
+use crate::{
+    nightly::Nightly,
+    repo::{find_pull_request_commit, get_first_nightly_containing_change},
+};
+
+/// Resolves `#N` to the first nightly containing the merge of pull request N, found in
+/// the datadog-agent checkout
+pub struct PullRequestResolver {
+    /// The datadog-agent checkout the merges are looked up in
+    pub repo_path: PathBuf,
+}
+
+impl Resolver for PullRequestResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::PullRequest
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let Some(number) = identifier
+            .strip_prefix('#')
+            .and_then(|number| number.parse().ok())
+        else {
+            return Resolution::Unrecognized;
+        };
+        // Merges older than every nightly can't be in a first nightly
+        let Some(since) = nightlies.iter().filter_map(|n| n.sha_timestamp).min() else {
+            return Resolution::Missing;
+        };
+        let repo = match gix::open(&self.repo_path) {
+            Ok(repo) => repo,
+            Err(e) => {
+                warn!(
+                    "Cannot look up pull request #{} without the datadog-agent checkout at {}: {}",
+                    number,
+                    self.repo_path.display(),
+                    e
+                );
+                return Resolution::Missing;
+            }
+        };
+        let merge = match find_pull_request_commit(&repo, number, since) {
+            Ok(merge) => merge,
+            Err(e) => {
+                warn!(
+                    "Error looking up the merge of pull request #{}: {}",
+                    number, e
+                );
+                return Resolution::Missing;
+            }
+        };
+        merge
+            .and_then(|merge| {
+                get_first_nightly_containing_change(&self.repo_path, nightlies, &merge).ok()
+            })
+            .and_then(|first| nightlies.iter().find(|n| n.sha == first.sha))
+            .into()
+    }
+}
@@ -0,0 +1,19 @@
+use super::{Resolution, Resolver, ResolverClass};
+use crate::nightly::Nightly;
+
+/// Resolves the sha a nightly was built from, longer shas are cut down to the 8
+/// characters nightly tags carry
+pub struct ShaResolver;
+
+impl Resolver for ShaResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::Sha
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        if identifier.len() < 8 || !identifier.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Resolution::Unrecognized;
+        }
+        nightlies.iter().find(|n| n.sha == identifier[..8]).into()
+    }
+}
@@ -0,0 +1,28 @@
+use super::{Resolution, Resolver, ResolverClass};
+use crate::nightly::Nightly;
+
+/// Every nightly tag starts with it, other names are left to later resolvers, e.g. branches
+const NIGHTLY_TAG_PREFIX: &str = "nightly-";
+
+/// Resolves a tag name to its nightly, the image repository in front of it is ignored,
+/// e.g. `datadog/agent-dev:nightly-main-0123abcd-py3`
+pub struct TagResolver;
+
+impl Resolver for TagResolver {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::Tag
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], identifier: &str) -> Resolution<'a> {
+        let name = identifier
+            .rsplit_once(':')
+            .map_or(identifier, |(_, name)| name);
+        if !name.starts_with(NIGHTLY_TAG_PREFIX) {
+            return Resolution::Unrecognized;
+        }
+        nightlies
+            .iter()
+            .find(|n| n.tags().any(|t| t.name == name))
+            .into()
+    }
+}
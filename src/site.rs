@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fmt::Write, hash::BuildHasher};
+
+use crate::{
+    format::day_heading, image::pipeline_from_labels, nightly::Nightly, query::go_version_changes,
+};
+
+// A static website of the cached nightlies: a timeline with a trend chart, and a page per
+// nightly with the commits it adds over its predecessor. Rendering is kept free of any IO
+// so the same pages can be produced by a WASM dashboard
+
+/// Number of most recent nightlies plotted in the trend chart
+const TREND_SAMPLE_SIZE: usize = 60;
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:auto;padding:1em}\
+    li{margin:.2em 0}.archived{color:#888}.bar{fill:#632ca6}";
+
+/// A file of the site, `path` is relative to the site root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteFile {
+    pub path: String,
+    pub html: String,
+}
+
+/// Escapes text so that it shows as is in HTML (or XML) content and attributes
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+fn nightly_path(sha: &str) -> String {
+    format!("nightly/{sha}.html")
+}
+
+fn tag_name(nightly: &Nightly) -> &str {
+    nightly
+        .first_valid_tag()
+        .map_or(nightly.sha.as_str(), |t| t.name.as_str())
+}
+
+/// An SVG bar chart of the commits each nightly adds, oldest on the left
+fn trend_chart(nightlies: &[&Nightly]) -> String {
+    let sample: Vec<&&Nightly> = nightlies.iter().take(TREND_SAMPLE_SIZE).rev().collect();
+    let max = sample
+        .iter()
+        .filter_map(|n| n.incremental_commits)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let (bar_width, height) = (10, 100);
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{height}\" role=\"img\" \
+         aria-label=\"Commits added by each nightly\">\n",
+        sample.len() * bar_width
+    );
+    for (i, nightly) in sample.iter().enumerate() {
+        let Some(count) = nightly.incremental_commits else {
+            continue;
+        };
+        let bar_height = (count * height / max).max(1);
+        writeln!(
+            svg,
+            "<rect class=\"bar\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{bar_height}\">\
+             <title>{}: {count} commits</title></rect>",
+            i * bar_width,
+            height - bar_height,
+            bar_width - 2,
+            escape(tag_name(nightly))
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The timeline of nightlies, grouped by push day, newest first
+fn render_index(nightlies: &[&Nightly]) -> String {
+    let mut body = String::from("<h1>Agent nightlies</h1>\n<h2>Commits per nightly</h2>\n");
+    body.push_str(&trend_chart(nightlies));
+    let mut day = None;
+    for nightly in nightlies {
+        let date = nightly.estimated_last_pushed.date_naive();
+        if day != Some(date) {
+            if day.is_some() {
+                body.push_str("</ul>\n");
+            }
+            writeln!(body, "<h2>{}</h2>\n<ul>", day_heading(date)).unwrap();
+            day = Some(date);
+        }
+        write!(
+            body,
+            "<li{}><a href=\"{}\">{}</a> pushed {}",
+            if nightly.archived {
+                " class=\"archived\""
+            } else {
+                ""
+            },
+            nightly_path(&nightly.sha),
+            escape(tag_name(nightly)),
+            nightly.estimated_last_pushed.format("%H:%M UTC")
+        )
+        .unwrap();
+        if let Some(count) = nightly.incremental_commits {
+            write!(body, ", {count} new commits").unwrap();
+        }
+        body.push_str("</li>\n");
+    }
+    if day.is_some() {
+        body.push_str("</ul>\n");
+    }
+    page("Agent nightlies", &body)
+}
+
+/// The page of one nightly, listing `commits` (short sha and subject) it adds over its
+/// predecessor, and the Go toolchain bump over it if there was one
+fn render_nightly(
+    nightly: &Nightly,
+    commits: Option<&Vec<String>>,
+    go_bump: Option<&(String, String)>,
+) -> String {
+    let name = tag_name(nightly);
+    let mut body = format!(
+        "<p><a href=\"../index.html\">All nightlies</a></p>\n<h1>{}</h1>\n<ul>\n",
+        escape(name)
+    );
+    writeln!(
+        body,
+        "<li>Image: <code>datadog/agent-dev:{}</code></li>",
+        escape(name)
+    )
+    .unwrap();
+    writeln!(
+        body,
+        "<li>Commit: <a href=\"https://github.com/DataDog/datadog-agent/tree/{sha}\">{sha}</a>\
+         </li>",
+        sha = escape(&nightly.sha)
+    )
+    .unwrap();
+    writeln!(
+        body,
+        "<li>Pushed: {}</li>",
+        nightly.estimated_last_pushed.to_rfc3339()
+    )
+    .unwrap();
+    if let Some(timestamp) = nightly.sha_timestamp {
+        writeln!(body, "<li>Committed: {}</li>", timestamp.to_rfc3339()).unwrap();
+    }
+    if let Some(go_version) = &nightly.go_version {
+        writeln!(body, "<li>Go version: {}</li>", escape(go_version)).unwrap();
+    }
+    if nightly.archived {
+        body.push_str("<li>Archived, the image can no longer be pulled</li>\n");
+    }
+    if let Some(pipeline) = nightly.labels.as_ref().and_then(pipeline_from_labels) {
+        writeln!(body, "<li>Built by {}</li>", escape(&pipeline)).unwrap();
+    }
+    body.push_str("</ul>\n");
+
+    if let Some(prev) = &nightly.predecessor_sha {
+        let prev = escape(prev);
+        writeln!(
+            body,
+            "<h2>Changes since <a href=\"{prev}.html\">{prev}</a></h2>\n\
+             <p><a href=\"https://github.com/DataDog/datadog-agent/compare/{prev}...{}\">\
+             Compare on GitHub</a></p>",
+            escape(&nightly.sha)
+        )
+        .unwrap();
+        if let Some((from, to)) = go_bump {
+            writeln!(
+                body,
+                "<p>Go toolchain bumped from {} to {}</p>",
+                escape(from),
+                escape(to)
+            )
+            .unwrap();
+        }
+        if let Some(commits) = commits {
+            body.push_str("<ul>\n");
+            for commit in commits {
+                writeln!(body, "<li><code>{}</code></li>", escape(commit)).unwrap();
+            }
+            body.push_str("</ul>\n");
+        }
+    }
+    page(name, &body)
+}
+
+/// Renders the site for the given nightlies, `changes` maps a nightly's sha to the commits
+/// (short sha and subject) it adds over its predecessor, when they are known
+#[must_use]
+pub fn render_site<S: BuildHasher>(
+    nightlies: &[Nightly],
+    changes: &HashMap<String, Vec<String>, S>,
+) -> Vec<SiteFile> {
+    let mut newest_first: Vec<&Nightly> = nightlies.iter().collect();
+    newest_first.sort_by_key(|n| std::cmp::Reverse(n.estimated_last_pushed));
+
+    let go_bumps = go_version_changes(nightlies);
+    let mut files = vec![SiteFile {
+        path: String::from("index.html"),
+        html: render_index(&newest_first),
+    }];
+    files.extend(newest_first.iter().map(|nightly| SiteFile {
+        path: nightly_path(&nightly.sha),
+        html: render_nightly(
+            nightly,
+            changes.get(&nightly.sha),
+            go_bumps.get(&nightly.sha),
+        ),
+    }));
+    files
+}
@@ -0,0 +1,338 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    client::NightliesClient,
+    config::get_config_path,
+    crash_report::{last_crash_report, redact},
+    nightly::{redact_url, Nightly},
+    NightlyError,
+};
+
+// A snapshot bundles the state that shapes a run into a single JSON file, so a maintainer
+// can reproduce a bug report with the reporter's exact state. Nothing is sent anywhere,
+// it is meant to be attached to bug reports by hand
+
+/// Config keys whose values are never put in a snapshot
+const SECRET_KEY_PARTS: [&str; 5] = ["token", "secret", "password", "api_key", "apikey"];
+
+/// Stands in for redacted values
+const REDACTED: &str = "<redacted>";
+
+/// Everything needed to reproduce a run, but its logs, which are not captured
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of nightlies that took the snapshot
+    pub version: String,
+    pub created: DateTime<Utc>,
+    /// The config file as written, before `${NAME}` interpolation, with secrets redacted
+    pub config: Option<String>,
+    /// The cached nightlies
+    pub nightlies: Vec<Nightly>,
+    /// The most recent crash report
+    pub crash_report: Option<String>,
+}
+
+/// Replaces the values of secret looking keys (tokens, passwords, ...) in a TOML config,
+/// at any depth, and the credentials of urls, commented out settings included
+/// Everything else is kept as written, comments and layout too, so a restored config is
+/// the user's file minus its secrets
+/// Values taken from the environment with `${NAME}` are kept, they hold no secret
+/// None if the config isn't valid TOML, as its secrets can't be told apart then
+#[must_use]
+pub fn redact_config(content: &str) -> Option<String> {
+    toml::from_str::<toml::Table>(content).ok()?;
+    Some(redact(&ConfigRedactor::default().redact(content)))
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// Whether any part of a possibly dotted or quoted key, or table header, is secret
+fn is_secret_path(path: &str) -> bool {
+    path.split('.')
+        .any(|part| is_secret_key(part.trim().trim_matches(['"', '\''])))
+}
+
+fn has_credentials(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| !url.username().is_empty() || url.password().is_some())
+}
+
+/// Walks a TOML document as written, copying it through with the secrets replaced
+#[derive(Default)]
+struct ConfigRedactor {
+    /// Whether the values of the current `[table]` are secret
+    section_secret: bool,
+    /// The inline tables (true) and arrays (false) being read, and whether their values
+    /// are secret
+    open: Vec<(bool, bool)>,
+}
+
+impl ConfigRedactor {
+    fn enclosing_secret(&self) -> bool {
+        self.open
+            .last()
+            .map_or(self.section_secret, |&(_, secret)| secret)
+    }
+
+    fn redact(&mut self, content: &str) -> String {
+        let mut redacted = String::with_capacity(content.len());
+        let mut rest = content;
+        let mut expect_key = true;
+        let mut secret = false;
+        while let Some(c) = rest.chars().next() {
+            let len = match c {
+                '#' => {
+                    let end = rest.find('\n').unwrap_or(rest.len());
+                    redacted.push('#');
+                    redacted.push_str(&self.redact_comment(&rest[1..end]));
+                    rest = &rest[end..];
+                    continue;
+                }
+                '\n' => {
+                    expect_key |= self.open.is_empty();
+                    redacted.push(c);
+                    1
+                }
+                c if c.is_whitespace() => {
+                    redacted.push(c);
+                    c.len_utf8()
+                }
+                '[' if expect_key && self.open.is_empty() => {
+                    let end = rest.find(['\n', '#']).unwrap_or(rest.len());
+                    let header = &rest[..end];
+                    self.section_secret = is_secret_path(header.trim().trim_matches(['[', ']']));
+                    redacted.push_str(header);
+                    end
+                }
+                '}' | ']' if !self.open.is_empty() => {
+                    self.open.pop();
+                    expect_key = false;
+                    secret = self.enclosing_secret();
+                    redacted.push(c);
+                    1
+                }
+                _ if expect_key => {
+                    let Some(end) = key_end(rest) else {
+                        let end = rest.find('\n').unwrap_or(rest.len());
+                        redacted.push_str(&rest[..end]);
+                        rest = &rest[end..];
+                        continue;
+                    };
+                    secret = self.enclosing_secret() || is_secret_path(&rest[..end]);
+                    expect_key = false;
+                    redacted.push_str(&rest[..=end]);
+                    end + 1
+                }
+                '{' | '[' => {
+                    self.open.push((c == '{', secret));
+                    expect_key = c == '{';
+                    redacted.push(c);
+                    1
+                }
+                ',' => {
+                    expect_key = self.open.last().is_some_and(|&(table, _)| table);
+                    secret = self.enclosing_secret();
+                    redacted.push(c);
+                    1
+                }
+                '"' | '\'' => {
+                    let end = string_end(rest);
+                    redacted.push_str(&redact_string(&rest[..end], secret));
+                    end
+                }
+                _ => {
+                    let end = rest.find([',', ']', '}', '#', '\n']).unwrap_or(rest.len());
+                    let scalar = rest[..end].trim_end();
+                    if secret {
+                        redacted.push_str(&redacted_value());
+                    } else {
+                        redacted.push_str(scalar);
+                    }
+                    scalar.len()
+                }
+            };
+            rest = &rest[len..];
+        }
+        redacted
+    }
+
+    /// Commented out settings are redacted like settings, and urls anywhere else in
+    /// comments lose their credentials
+    fn redact_comment(&self, comment: &str) -> String {
+        if toml::from_str::<toml::Table>(comment).is_ok_and(|table| !table.is_empty()) {
+            let mut redactor = ConfigRedactor {
+                section_secret: self.section_secret,
+                open: Vec::new(),
+            };
+            return redactor.redact(comment);
+        }
+        if let Some((key, _)) = comment.split_once('=') {
+            if self.section_secret || is_secret_path(key) {
+                return format!("{key}= {REDACTED}");
+            }
+        }
+        comment
+            .split_inclusive(char::is_whitespace)
+            .map(|word| {
+                let url = word.trim_end();
+                if has_credentials(url) {
+                    format!("{}{}", redact_url(url), &word[url.len()..])
+                } else {
+                    word.to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Index of the `=` ending the key at the start of `text`, None if there is none
+fn key_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (_, '\n') => return None,
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '=') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Length of the string literal, single or multi-line, at the start of `text`
+fn string_end(text: &str) -> usize {
+    let quote = &text[..1];
+    let delimiter = if text.starts_with(&quote.repeat(3)) {
+        quote.repeat(3)
+    } else {
+        quote.to_string()
+    };
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(delimiter.len()) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == "\"" {
+            escaped = true;
+        } else if text[i..].starts_with(&delimiter) {
+            return i + delimiter.len();
+        }
+    }
+    text.len()
+}
+
+/// A redacted value, as a TOML string whatever the type of the secret
+fn redacted_value() -> String {
+    format!("\"{REDACTED}\"")
+}
+
+fn redact_string(literal: &str, secret: bool) -> String {
+    let multiline = literal.starts_with("\"\"\"") || literal.starts_with("'''");
+    let quotes = if multiline && literal.len() >= 6 {
+        3
+    } else {
+        1
+    };
+    let (open, value, close) = (
+        &literal[..quotes],
+        &literal[quotes..literal.len() - quotes],
+        &literal[literal.len() - quotes..],
+    );
+    if secret && !value.starts_with("${") {
+        redacted_value()
+    } else if has_credentials(value) {
+        format!("{open}{}{close}", redact_url(value))
+    } else {
+        literal.to_string()
+    }
+}
+
+/// Gathers the config, cache and latest crash report
+///
+/// # Errors
+/// - Errors if the config file, cache or crash reports exist but cannot be read
+pub fn take_snapshot(client: &NightliesClient) -> Result<Snapshot, NightlyError> {
+    let config = match get_config_path().map(fs::read_to_string) {
+        Some(Ok(content)) => {
+            let redacted = redact_config(&content);
+            if redacted.is_none() {
+                warn!("The config isn't valid TOML, it is left out of the snapshot");
+            }
+            redacted
+        }
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => None,
+    };
+    Ok(Snapshot {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created: Utc::now(),
+        config,
+        nightlies: client.load_cache()?,
+        crash_report: last_crash_report()?.map(|(_, report)| report),
+    })
+}
+
+/// Writes a snapshot of the current state to `path`
+///
+/// # Errors
+/// - Errors if the state cannot be gathered or the file cannot be written
+pub fn write_snapshot(client: &NightliesClient, path: &Path) -> Result<Snapshot, NightlyError> {
+    let snapshot = take_snapshot(client)?;
+    fs::write(path, serde_json::to_string_pretty(&snapshot)?)?;
+    Ok(snapshot)
+}
+
+/// Restores the cache and config from the snapshot at `path`
+/// The existing cache and config are kept next to the restored ones as
+/// `<name>.<timestamp>.bak`
+///
+/// # Errors
+/// - Errors if the snapshot cannot be read or parsed
+/// - Errors if the cache or config cannot be backed up or written
+pub fn load_snapshot(client: &NightliesClient, path: &Path) -> Result<Snapshot, NightlyError> {
+    let snapshot: Snapshot = serde_json::from_str(&fs::read_to_string(path)?)?;
+    back_up(&client.cache_file)?;
+    client.save_cache(&snapshot.nightlies)?;
+    if let (Some(config), Some(config_path)) = (&snapshot.config, get_config_path()) {
+        back_up(&config_path)?;
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&config_path, config)?;
+    }
+    Ok(snapshot)
+}
+
+/// Moves an existing file out of the way to `<name>.<timestamp>.bak`, so restoring
+/// several snapshots in a row keeps every previous state
+/// An existing backup is never overwritten
+fn back_up(path: &Path) -> Result<Option<PathBuf>, NightlyError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.bak", Utc::now().format("%Y%m%dT%H%M%S%.3fZ")));
+    let backup = PathBuf::from(backup);
+    if backup.exists() {
+        return Err(NightlyError::GenericError(format!(
+            "Not overwriting the backup {}",
+            backup.display()
+        )));
+    }
+    fs::rename(path, &backup)?;
+    info!(
+        "Kept the previous {} as {}",
+        path.display(),
+        backup.display()
+    );
+    Ok(Some(backup))
+}
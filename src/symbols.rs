@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    nightly::{http_send, Nightly},
+    NightlyError,
+};
+
+// Debug symbols are published next to the CI artifacts rather than in the registry, so
+// their location comes from URL templates in the config
+
+/// Fills in the `{sha}`, `{tag}` and `{pipeline}` placeholders of a symbol URL template
+///
+/// # Errors
+/// - Errors if the template uses `{pipeline}` and no pipeline id is known
+/// - Errors if the template has an unknown placeholder
+pub fn render_symbol_url(
+    template: &str,
+    nightly: &Nightly,
+    pipeline_id: Option<&str>,
+) -> Result<String, NightlyError> {
+    let mut url = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        url.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find('}').ok_or_else(|| {
+            NightlyError::GenericError(format!("Unclosed '{{' in symbol URL '{template}'"))
+        })?;
+        let value = match &after[..end] {
+            "sha" => nightly.sha.as_str(),
+            "tag" => nightly
+                .first_valid_tag()
+                .map(|t| t.name.as_str())
+                .unwrap_or_default(),
+            "pipeline" => pipeline_id.ok_or_else(|| {
+                NightlyError::GenericError(format!(
+                    "No pipeline id is known for nightly {}",
+                    nightly.sha
+                ))
+            })?,
+            other => {
+                return Err(NightlyError::GenericError(format!(
+                    "Unknown placeholder '{{{other}}}' in symbol URL '{template}'"
+                )))
+            }
+        };
+        url.push_str(value);
+        rest = &after[end + 1..];
+    }
+    url.push_str(rest);
+    Ok(url)
+}
+
+/// Downloads `url` into `dir`, naming the file after the last segment of the URL path
+///
+/// # Errors
+/// - Errors if the download fails or the file cannot be written
+pub async fn download_symbols(url: &str, dir: &Path) -> Result<PathBuf, NightlyError> {
+    let file_name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("symbols");
+    let client = reqwest::Client::new();
+    let bytes = http_send(&client, client.get(url).build()?)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(file_name);
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
@@ -0,0 +1,1333 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{FakeRegistry, FixtureHome};
+
+fn stdout(output: &std::process::Output) -> String {
+    assert!(output.status.success(), "nightlies failed: {output:?}");
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn lists_nightlies_from_requested_pages_only() {
+    let home = FixtureHome::new(4);
+    // Newest nightlies come first, like docker hub orders them
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(3), home.tags_for_commit(2)].concat(),
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat(),
+    ]);
+
+    let one_page = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(one_page.contains(&home.commits[3]));
+    assert!(!one_page.contains(&home.commits[0]));
+
+    let both_pages = stdout(&home.run(
+        &registry,
+        &["--from-date", "2000-01-01", "--num-registry-pages", "2"],
+    ));
+    for sha in &home.commits {
+        assert!(both_pages.contains(sha), "{sha} missing from {both_pages}");
+    }
+}
+
+#[test]
+fn enriches_nightlies_with_commit_timestamps() {
+    let home = FixtureHome::new(2);
+    let registry = FakeRegistry::start(vec![home.tags_for_commit(1)]);
+
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    let expected = format!("Commit Timestamp: {}", common::commit_time(1).to_rfc3339());
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+}
+
+#[test]
+fn latest_only_prints_the_newest_py3_tag() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    let output = stdout(&home.run(&registry, &["--latest-only"]));
+    let expected = format!("nightly-main-{}-py3", home.commits[2]);
+    assert!(
+        output.lines().any(|l| l == expected),
+        "{expected} missing from {output}"
+    );
+}
+
+#[test]
+fn cached_nightlies_survive_later_runs() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(1)].concat(),
+        home.tags_for_commit(0),
+    ]);
+    stdout(&home.run(&registry, &["--num-registry-pages", "2"]));
+
+    // The oldest nightly is only on the 2nd page, but is remembered from the first run
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(output.contains(&home.commits[0]), "{output}");
+}
+
+#[test]
+fn lists_nightlies_without_a_git_repo() {
+    let home = FixtureHome::new(2);
+    std::fs::remove_dir_all(home.repo_path()).unwrap();
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat()
+    ]);
+
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(output.contains(&home.commits[1]), "{output}");
+    assert!(output.contains("Commit Timestamp: unknown"), "{output}");
+    assert_eq!(
+        output
+            .matches("Could not open the datadog-agent checkout")
+            .count(),
+        1,
+        "{output}"
+    );
+
+    // The output is written before failing, whatever RUST_LOG shows
+    let args = ["--from-date", "2000-01-01", "--strict"];
+    for output in [
+        home.run(&registry, &args),
+        home.run_with_env(&registry, &args, ("RUST_LOG", "error")),
+    ] {
+        assert_eq!(output.status.code(), Some(7), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Commit Timestamp: unknown"), "{stdout}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("--strict"), "{stderr}");
+    }
+}
+
+#[test]
+fn grep_lists_nightlies_that_added_a_matching_commit() {
+    let home = FixtureHome::new(5);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(4),
+        home.tags_for_commit(2),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    let output = stdout(&home.run(
+        &registry,
+        &["--from-date", "2000-01-01", "--grep", "(#1003)"],
+    ));
+    assert!(
+        output.contains(&format!("tree/{}", home.commits[4])),
+        "{output}"
+    );
+    assert!(
+        !output.contains(&format!("tree/{}", home.commits[2])),
+        "{output}"
+    );
+    assert!(output.contains("Matching commit:"), "{output}");
+}
+
+#[test]
+fn ranges_include_merged_commits_dated_before_the_older_nightly() {
+    let mut home = FixtureHome::new(3);
+    home.merge_branch("feature", 0);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(3), home.tags_for_commit(2)].concat()
+    ]);
+
+    let output = stdout(&home.run(
+        &registry,
+        &["--from-date", "2000-01-01", "--grep", "work on feature"],
+    ));
+    assert!(
+        output.contains(&format!("tree/{}", home.commits[3])),
+        "{output}"
+    );
+    assert!(output.contains("Matching commit:"), "{output}");
+}
+
+#[test]
+fn ignored_paths_leave_commits_out_of_ranges() {
+    let home = FixtureHome::new(5);
+    let pending_registry = FakeRegistry::start(vec![home.tags_for_commit(0)]);
+
+    // Commits 1 to 4 change comp/, cmd/, pkg/ and comp/ in turn
+    let output = stdout(&home.run(&pending_registry, &["--pending"]));
+    assert!(output.contains("cmd/ (1)"), "{output}");
+    let output = stdout(&home.run(
+        &pending_registry,
+        &[
+            "--pending",
+            "--ignore-paths",
+            "cmd/**",
+            "--ignore-paths",
+            "*.md",
+        ],
+    ));
+    assert!(output.contains("not yet in a nightly"), "{output}");
+    assert!(!output.contains("cmd/"), "{output}");
+    let output = stdout(&home.run(
+        &pending_registry,
+        &[
+            "--pending",
+            "--ignore-paths",
+            "cmd/**",
+            "--ignore-paths",
+            "!cmd/file2.go",
+        ],
+    ));
+    assert!(output.contains("cmd/ (1)"), "{output}");
+    let output = stdout(&home.run(&pending_registry, &["--pending", "--ignore-paths", "!*.go"]));
+    assert!(output.contains("cmd/ (1)"), "{output}");
+
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(4), home.tags_for_commit(0)].concat()
+    ]);
+    let args = ["--from-date", "2000-01-01", "--grep", "(#1003)"];
+    let output = stdout(&home.run(&registry, &args));
+    assert!(output.contains("Matching commit:"), "{output}");
+    let output = stdout(&home.run(
+        &registry,
+        &[&args[..], &["--ignore-paths", "pkg/*"]].concat(),
+    ));
+    assert!(!output.contains("Matching commit:"), "{output}");
+}
+
+#[test]
+fn trend_charts_commits_per_day() {
+    let home = FixtureHome::new(5);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(4),
+        home.tags_for_commit(2),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+    stdout(&home.run(&registry, &["--latest-only"]));
+
+    let day = |n: usize| common::commit_time(n).format("%Y-%m-%d").to_string();
+    let output = stdout(&home.run(&registry, &["--trend", "--from-date", &day(0), "--ascii"]));
+    let line = |n: usize| {
+        output
+            .lines()
+            .find(|l| l.starts_with(&day(n)))
+            .unwrap_or_else(|| panic!("{} missing from {output}", day(n)))
+    };
+    // The nightlies of commits 2 and 4 each add 2 commits over their predecessor
+    assert!(line(2).contains(" 2 "), "{output}");
+    assert!(line(2).trim_end().ends_with('#'), "{output}");
+    assert!(line(3).contains(" - "), "{output}");
+}
+
+#[test]
+fn investigate_writes_up_the_bracketing_nightlies() {
+    let home = FixtureHome::new(5);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(4),
+        home.tags_for_commit(2),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+    // Noticed the day after the nightly of commit 4, which was already in use by then
+    let since = (common::commit_time(4) + chrono::Duration::days(1))
+        .format("%Y-%m-%d")
+        .to_string();
+    let doc = home.dir.path().join("investigation.md");
+
+    let output = stdout(&home.run(
+        &registry,
+        &[
+            "--investigate",
+            &since,
+            "--query",
+            "(#1003)",
+            "--investigation-doc",
+            doc.to_str().unwrap(),
+        ],
+    ));
+    assert!(
+        output.contains(&format!("Last good nightly: {}", home.commits[2])),
+        "{output}"
+    );
+    assert!(
+        output.contains(&format!("First bad nightly: {}", home.commits[4])),
+        "{output}"
+    );
+
+    let doc = std::fs::read_to_string(doc).unwrap();
+    let compare = format!("compare/{}...{}", home.commits[2], home.commits[4]);
+    assert!(doc.contains(&compare), "{doc}");
+    let first_suspect = doc
+        .lines()
+        .find(|l| l.starts_with("1. "))
+        .unwrap_or_else(|| panic!("no suspects in {doc}"));
+    assert!(
+        first_suspect.contains("change 3 (#1003) (matches the query)"),
+        "{doc}"
+    );
+    assert!(doc.contains("2. "), "{doc}");
+    assert!(
+        doc.contains(&format!("--build-local {}", home.commits[2])),
+        "{doc}"
+    );
+
+    // An instant brackets at that time, before the nightly of commit 4 was pushed
+    let since = common::commit_time(4).to_rfc3339();
+    let output = stdout(&home.run(
+        &registry,
+        &[
+            "--investigate",
+            &since,
+            "--investigation-doc",
+            home.dir.path().join("instant.md").to_str().unwrap(),
+        ],
+    ));
+    assert!(
+        output.contains(&format!("First bad nightly: {}", home.commits[2])),
+        "{output}"
+    );
+    assert!(
+        output.contains(&format!("Last good nightly: {}", home.commits[0])),
+        "{output}"
+    );
+}
+
+#[test]
+fn format_prints_a_template_line_per_nightly() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(0)].concat()
+    ]);
+
+    let output = stdout(&home.run(
+        &registry,
+        &[
+            "--from-date",
+            "2000-01-01",
+            "--format",
+            "nightly {sha} {gh_url}",
+        ],
+    ));
+    // Only the template lines, without headers, footer or estimate
+    let lines: Vec<&str> = output.lines().collect();
+    let expected: Vec<String> = [0, 2]
+        .iter()
+        .map(|n| {
+            let sha = &home.commits[*n];
+            format!("nightly {sha} https://github.com/DataDog/datadog-agent/tree/{sha}")
+        })
+        .collect();
+    assert_eq!(lines, expected, "{output}");
+
+    // Tabs are kept as given rather than aligned
+    let output = stdout(&home.run(
+        &registry,
+        &["--from-date", "2000-01-01", "--format", "{sha}\\t|"],
+    ));
+    assert_eq!(
+        output,
+        format!("{}\t|\n{}\t|\n", home.commits[0], home.commits[2])
+    );
+
+    // Single nightly answers print the template line alone
+    let output = stdout(&home.run(
+        &registry,
+        &["--agent-sha", &home.commits[1], "--format", "{sha}\t|"],
+    ));
+    assert_eq!(output, format!("{}\t|\n", home.commits[2]));
+
+    let output = home.run(&registry, &["--format", "{size}"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
+
+#[test]
+fn ping_checks_the_cache_without_the_registry() {
+    let home = FixtureHome::new(1);
+    // Nothing is served, ping must not need the registry
+    let registry = FakeRegistry::start(vec![]);
+
+    let output = stdout(&home.run(&registry, &["--ping"]));
+    assert!(output.starts_with("ok: "), "{output}");
+
+    std::fs::write(home.cache_path(), "not json").unwrap();
+    let output = home.run(&registry, &["--ping"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("unhealthy: "));
+}
+
+#[test]
+fn crash_report_last_prints_the_newest_report() {
+    let home = FixtureHome::new(1);
+    let registry = FakeRegistry::start(vec![]);
+
+    let output = stdout(&home.run(&registry, &["--crash-report-last"]));
+    assert_eq!(output, "No crash reports found\n");
+
+    let dir = home.dir.path().join(".local/share/nightlies/crash-reports");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("crash-20240101T000000.000Z.txt"), "old crash").unwrap();
+    std::fs::write(dir.join("crash-20240201T000000.000Z.txt"), "new crash").unwrap();
+    let output = stdout(&home.run(&registry, &["--crash-report-last"]));
+    assert!(output.ends_with("new crash"), "{output}");
+}
+
+#[test]
+fn plain_output_uses_labeled_lines() {
+    let home = FixtureHome::new(2);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat()
+    ]);
+
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01", "--plain"]));
+    assert!(output.is_ascii(), "{output}");
+    let expected = format!(
+        "URI: datadog/agent-dev:nightly-main-{}-py3",
+        home.commits[1]
+    );
+    assert!(
+        output.lines().any(|l| l == expected),
+        "{expected} missing from {output}"
+    );
+    assert!(output.lines().any(|l| l == "Commits added: 1"), "{output}");
+    assert!(output.lines().any(|l| l.starts_with("Day: ")), "{output}");
+}
+
+#[test]
+fn ascii_output_can_be_enabled_from_the_config() {
+    let home = FixtureHome::new(2);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat()
+    ]);
+
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(!output.is_ascii(), "{output}");
+
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), "[output]\nascii = true\n").unwrap();
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(output.is_ascii(), "{output}");
+    assert!(output.lines().any(|l| l.starts_with("-- ")), "{output}");
+}
+
+#[test]
+fn build_sha_accepts_index_shorthand() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    let output = stdout(&home.run(&registry, &["--build-sha", "@1"]));
+    let expected = format!("nightly-main-{}-py3", home.commits[1]);
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+}
+
+#[test]
+fn identifiers_resolve_pull_requests_and_git_refs() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(0)].concat()
+    ]);
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[symbols]\nurls = [\"https://symbols.example/{sha}.tar\"]\n",
+    )
+    .unwrap();
+
+    // The fixture commit 1 merged #1001, the nightly of commit 2 is the first with it
+    let output = stdout(&home.run(&registry, &["--symbols", "#1001"]));
+    let expected = format!("https://symbols.example/{}.tar", home.commits[2]);
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+
+    let output = stdout(&home.run(&registry, &["--symbols", "main~2"]));
+    let expected = format!("https://symbols.example/{}.tar", home.commits[0]);
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+
+    // --build-sha only takes identifiers of a build
+    let output = home.run(&registry, &["--build-sha", "main~2"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    // A sha is taken for a build sha, not for the commit of the first nightly containing it
+    let output = home.run(&registry, &["--provenance", &home.commits[1]]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+}
+
+#[test]
+fn pull_requests_are_looked_up_back_to_the_oldest_nightly() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(1)].concat()
+    ]);
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[symbols]\nurls = [\"https://symbols.example/{sha}.tar\"]\n",
+    )
+    .unwrap();
+
+    // #1000 was merged before the oldest nightly, the walk of 'main' stops short of it
+    let output = home.run(&registry, &["--symbols", "#1000"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    std::fs::remove_dir_all(home.repo_path()).unwrap();
+    let output = home.run(&registry, &["--symbols", "#1001"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("without the datadog-agent checkout"),
+        "{output:?}"
+    );
+}
+
+#[test]
+fn context_base_stands_in_for_an_identifier() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[symbols]\nurls = [\"https://symbols.example/{sha}.tar\"]\n",
+    )
+    .unwrap();
+
+    let output = home.run(&registry, &["--symbols", "base"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    let output = stdout(&home.run(&registry, &["--context-set-base", "@1"]));
+    assert!(output.contains(&home.commits[1]), "{output}");
+    let output = stdout(&home.run(&registry, &["--context-show"]));
+    assert!(
+        output.contains(&format!("Base nightly: {}", home.commits[1])),
+        "{output}"
+    );
+    let output = stdout(&home.run(&registry, &["--symbols", "base"]));
+    let expected = format!("https://symbols.example/{}.tar", home.commits[1]);
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+
+    stdout(&home.run(&registry, &["--context-clear"]));
+    let output = stdout(&home.run(&registry, &["--context-show"]));
+    assert!(output.contains("No base nightly is set"), "{output}");
+
+    // A damaged context is only an error when showing it
+    std::fs::write(home.cache_path().with_extension("context.json"), "{").unwrap();
+    let output = home.run(&registry, &["--context-show"]);
+    assert!(!output.status.success(), "{output:?}");
+    let output = stdout(&home.run(&registry, &["--latest-only"]));
+    assert!(output.contains(&home.commits[2]), "{output}");
+    let output = stdout(&home.run(&registry, &["--context-set-base", "@0"]));
+    assert!(output.contains(&home.commits[2]), "{output}");
+}
+
+#[test]
+fn orphans_lists_nightlies_without_a_commit() {
+    let home = FixtureHome::new(1);
+    let orphan = nightlies::nightly::Tag {
+        name: String::from("nightly-main-deadbeef-py3"),
+        last_pushed: common::commit_time(0),
+        digest: String::from("sha256:deadbeef"),
+        last_pulled: None,
+        pull_count: None,
+    };
+    let registry = FakeRegistry::start(vec![[home.tags_for_commit(0), vec![orphan]].concat()]);
+
+    stdout(&home.run(&registry, &["--latest-only"]));
+    let output = stdout(&home.run(&registry, &["--orphans"]));
+    let line = output
+        .lines()
+        .find(|l| l.contains("nightly-main-deadbeef-py3"))
+        .unwrap_or_else(|| panic!("orphan missing from {output}"));
+    assert!(line.trim_end().ends_with(" 2"), "{line}");
+    let resolved = format!("nightly-main-{}", home.commits[0]);
+    assert!(!output.contains(&resolved), "{output}");
+}
+
+#[test]
+fn build_sha_reads_identifiers_from_stdin() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    let stdin = format!("{}\n\n@0\n", home.commits[0]);
+    let output = stdout(&home.run_with_stdin(&registry, &["--build-sha", "-"], &stdin));
+    for n in [0, 2] {
+        let expected = format!("nightly-main-{}-py3", home.commits[n]);
+        assert!(
+            output.contains(&expected),
+            "{expected} missing from {output}"
+        );
+    }
+    assert!(!output.contains(&format!("nightly-main-{}-py3", home.commits[1])));
+}
+
+#[test]
+fn trace_http_logs_registry_requests() {
+    let home = FixtureHome::new(1);
+    let registry = FakeRegistry::start(vec![home.tags_for_commit(0)]);
+
+    let output = stdout(&home.run(&registry, &["--latest-only", "--trace-http"]));
+    let expected = format!("GET {}", registry.url);
+    assert!(
+        output
+            .lines()
+            .any(|l| l.contains(&expected) && l.contains("200 OK")),
+        "{expected} missing from {output}"
+    );
+
+    let output = stdout(&home.run(&registry, &["--latest-only"]));
+    assert!(!output.contains(&expected), "{output}");
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    let home = FixtureHome::new(1);
+    let registry = FakeRegistry::start(vec![home.tags_for_commit(0)]);
+
+    let output = home.run(&registry, &["--build-sha", "deadbeef"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    let unreachable = FakeRegistry {
+        // Nothing listens on the discard port
+        url: String::from("http://127.0.0.1:9/tags"),
+        oci_url: String::from("http://127.0.0.1:9/v2/datadog/agent-dev"),
+    };
+    let output = home.run(&unreachable, &["--latest-only"]);
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+
+    let output = home.run(&registry, &["--from-date", "yesterday"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    let output = home.run(&registry, &["--pending", "--ignore-paths", ""]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
+
+#[test]
+fn generates_a_man_page() {
+    let home = FixtureHome::new(1);
+    let registry = FakeRegistry::start(vec![]);
+    let dir = home.dir.path().join("man");
+
+    stdout(&home.run(&registry, &["--generate-man", dir.to_str().unwrap()]));
+    let page = std::fs::read_to_string(dir.join("nightlies.1")).unwrap();
+    assert!(page.contains("\n.TH NIGHTLIES 1 "), "{page}");
+    assert!(
+        page.contains(".SH \"EXIT STATUS\"\n.TP\n0\nSuccess\n"),
+        "{page}"
+    );
+    assert!(page.contains("\\fB\\-\\-strict\\fR"), "{page}");
+    assert!(!page.contains("\\-\\-complete"), "{page}");
+}
+
+#[test]
+fn bench_times_each_implementation_against_a_local_repo() {
+    let home = FixtureHome::new(6);
+    let registry = FakeRegistry::start(vec![]);
+
+    let output = stdout(&home.run(&registry, &["--bench", home.repo_path().to_str().unwrap()]));
+    for name in [
+        "containment/rev_walk",
+        "containment/commit_graph",
+        "enrichment/serial",
+        "enrichment/parallel",
+    ] {
+        assert!(output.contains(&format!("{name}: ")), "{output}");
+    }
+}
+
+#[test]
+fn labels_are_fetched_once_and_cached() {
+    let home = FixtureHome::new(1);
+    let tag = format!("nightly-main-{}-py3", home.commits[0]);
+    let labels = HashMap::from([(
+        tag.clone(),
+        vec![
+            (
+                String::from("ci.pipeline.url"),
+                String::from("https://example.com/pipelines/42"),
+            ),
+            (String::from("ci.pipeline.id"), String::from("42")),
+        ],
+    )]);
+    let registry = FakeRegistry::start_with_labels(vec![home.tags_for_commit(0)], labels);
+
+    let args = ["--build-sha", "@0", "--labels", "--trace-http"];
+    let output = stdout(&home.run(&registry, &args));
+    assert!(
+        output.contains("Label ci.pipeline.url:  https://example.com/pipelines/42"),
+        "{output}"
+    );
+    assert!(
+        output.contains(&format!("manifests/{tag} -> 200 OK")),
+        "{output}"
+    );
+
+    let output = stdout(&home.run(&registry, &args));
+    assert!(
+        output.contains("https://example.com/pipelines/42"),
+        "{output}"
+    );
+    assert!(
+        !output.contains("manifests/"),
+        "labels were fetched again: {output}"
+    );
+}
+
+#[test]
+fn provenance_flags_mismatched_commits_and_repushed_tags() {
+    let home = FixtureHome::new(2);
+    let revision = |n: usize| {
+        (
+            String::from("org.opencontainers.image.revision"),
+            home.commits[n].clone(),
+        )
+    };
+    let tag = |n: usize| format!("nightly-main-{}-py3", home.commits[n]);
+    let labels = HashMap::from([(tag(0), vec![revision(0)]), (tag(1), vec![revision(0)])]);
+    let pages = vec![[home.tags_for_commit(1), home.tags_for_commit(0)].concat()];
+    let registry = FakeRegistry::start_with_labels(pages.clone(), labels.clone());
+
+    let output = stdout(&home.run(&registry, &["--provenance", "@1"]));
+    assert!(
+        output.contains(&format!("The image matches commit {}", home.commits[0])),
+        "{output}"
+    );
+
+    // The tags of the latest nightly are pushed again with other content
+    let mut repushed = pages;
+    for tag in &mut repushed[0] {
+        tag.digest = String::from("sha256:repushed");
+    }
+    let registry = FakeRegistry::start_with_labels(repushed, labels);
+    let output = home.run(&registry, &["--provenance", "@0"]);
+    assert_eq!(output.status.code(), Some(8), "{output:?}");
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.contains(&format!(
+            "Mismatch: the image's org.opencontainers.image.revision label names commit {}",
+            home.commits[0]
+        )),
+        "{output}"
+    );
+    assert!(
+        output.contains(&format!("Mismatch: {} was re-pushed", tag(1))),
+        "{output}"
+    );
+}
+
+#[test]
+fn agent_sha_batch_finds_the_first_containing_nightly_of_each_change() {
+    let home = FixtureHome::new(4);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(3), home.tags_for_commit(1)].concat()
+    ]);
+
+    let stdin = format!(
+        "{}\n{}\n{}\n",
+        home.commits[0], home.commits[1], home.commits[2]
+    );
+    let output = stdout(&home.run_with_stdin(&registry, &["--agent-sha", "-", "--plain"], &stdin));
+    let sections: Vec<&str> = output
+        .split("The first nightly containing ")
+        .skip(1)
+        .collect();
+    assert_eq!(sections.len(), 3, "{output}");
+    for (section, (change, nightly)) in sections.iter().zip([(0, 1), (1, 1), (2, 3)]) {
+        assert!(section.starts_with(&home.commits[change]), "{section}");
+        let expected = format!("nightly-main-{}-py3", home.commits[nightly]);
+        assert!(
+            section.contains(&expected),
+            "{expected} missing from {section}"
+        );
+    }
+}
+
+#[test]
+fn listing_marks_release_branch_cuts() {
+    let home = FixtureHome::new(4);
+    home.add_remote_branch("7.1.x", 1);
+    home.add_remote_branch("feature", 1);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(3),
+        home.tags_for_commit(2),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01", "--plain"]));
+    let cut = output
+        .find("Release branch cut: 7.1.x")
+        .unwrap_or_else(|| panic!("cut missing from {output}"));
+    let before = output
+        .find(&format!("nightly-main-{}-py3", home.commits[0]))
+        .unwrap();
+    let after = output
+        .find(&format!("nightly-main-{}-py3", home.commits[2]))
+        .unwrap();
+    assert!(before < cut && cut < after, "{output}");
+    assert!(!output.contains("feature"), "{output}");
+}
+
+#[test]
+fn listing_marks_go_toolchain_bumps() {
+    let mut home = FixtureHome::new(1);
+    home.commit_file(
+        "go.mod",
+        "module example\n\ngo 1.22.0\n\ntoolchain go1.22.5\n",
+    );
+    home.commit_file(".go-version", "1.23.1\n");
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01", "--plain"]));
+    let bump = output
+        .find("Go toolchain changed: 1.22.5 to 1.23.1")
+        .unwrap_or_else(|| panic!("bump missing from {output}"));
+    let before = output
+        .find(&format!("nightly-main-{}-py3", home.commits[1]))
+        .unwrap();
+    let after = output
+        .find(&format!("nightly-main-{}-py3", home.commits[2]))
+        .unwrap();
+    assert!(before < bump && bump < after, "{output}");
+    assert_eq!(
+        output.matches("Go toolchain changed").count(),
+        1,
+        "{output}"
+    );
+    assert!(output.contains("Go version: 1.23.1"), "{output}");
+
+    let cached = std::fs::read_to_string(home.cache_path()).unwrap();
+    assert!(cached.contains("\"go_version\": \"1.22.5\""), "{cached}");
+}
+
+#[test]
+fn pruning_the_cache_fetches_nothing() {
+    let home = FixtureHome::new(15);
+    let cached = FakeRegistry::start(vec![
+        [home.tags_for_commit(14), home.tags_for_commit(0)].concat()
+    ]);
+    stdout(&home.run(&cached, &["--latest-only"]));
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[retention]\nnightlies_days = 20\n",
+    )
+    .unwrap();
+
+    // A newer nightly is served, pruning must leave it out of the cache
+    let registry = FakeRegistry::start(vec![home.tags_for_commit(13)]);
+    let output = stdout(&home.run(&registry, &["--prune-cache"]));
+    assert!(
+        output.contains("Pruned 1 nightlies older than 20 days"),
+        "{output}"
+    );
+    let cache = std::fs::read_to_string(home.cache_path()).unwrap();
+    let cached = |n: usize| cache.contains(&format!("\"sha\": \"{}\"", home.commits[n]));
+    assert!(cached(14), "{cache}");
+    assert!(!cached(0), "{cache}");
+    assert!(!cached(13), "{cache}");
+}
+
+#[test]
+fn snapshots_restore_the_cache_and_config_elsewhere() {
+    let home = FixtureHome::new(2);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat()
+    ]);
+    stdout(&home.run(&registry, &["--latest-only"]));
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[output]\nascii = true\n# token = \"hunter2\"\n",
+    )
+    .unwrap();
+
+    let snapshot = home.dir.path().join("state.json");
+    stdout(&home.run(&registry, &["--snapshot", snapshot.to_str().unwrap()]));
+    let written = std::fs::read_to_string(&snapshot).unwrap();
+    assert!(!written.contains("hunter2"), "{written}");
+
+    let other = FixtureHome::new(1);
+    let output = stdout(&other.run(&registry, &["--load-snapshot", snapshot.to_str().unwrap()]));
+    assert!(
+        output.contains("Restored 2 nightlies and the config"),
+        "{output}"
+    );
+    let cache = std::fs::read_to_string(other.cache_path()).unwrap();
+    assert!(cache.contains(&home.commits[1]), "{cache}");
+    let config =
+        std::fs::read_to_string(other.dir.path().join(".config/nightlies/config.toml")).unwrap();
+    assert!(config.contains("ascii = true"), "{config}");
+
+    // Loading again keeps the config and cache of the first load
+    stdout(&other.run(&registry, &["--load-snapshot", snapshot.to_str().unwrap()]));
+    let backups = |dir: &std::path::Path, prefix: &str| {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.starts_with(prefix) && name.ends_with(".bak")
+            })
+            .count()
+    };
+    assert_eq!(
+        backups(&other.dir.path().join(".config/nightlies"), "config.toml."),
+        1
+    );
+    assert_eq!(
+        backups(
+            other.cache_path().parent().unwrap(),
+            "agent_nightlies.json."
+        ),
+        1
+    );
+}
+
+#[test]
+fn flavor_selects_the_images_of_one_agent_flavor() {
+    let home = FixtureHome::new(2);
+    let mut tags = home.tags_for_commit(0);
+    let mut iot = tags[0].clone();
+    iot.name = format!("nightly-main-{}-iot", home.commits[0]);
+    tags.push(iot);
+    let registry = FakeRegistry::start(vec![[home.tags_for_commit(1), tags].concat()]);
+
+    let output = stdout(&home.run(&registry, &["--latest-only"]));
+    assert_eq!(
+        output.lines().last().unwrap(),
+        format!("nightly-main-{}-py3", home.commits[1])
+    );
+    let output = stdout(&home.run(&registry, &["--latest-only", "--flavor", "iot"]));
+    assert_eq!(
+        output.lines().last().unwrap(),
+        format!("nightly-main-{}-iot", home.commits[0])
+    );
+}
+
+#[test]
+fn build_local_runs_the_build_command_in_a_worktree() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(1)].concat()
+    ]);
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[build]\ncommand = \"git rev-parse --short=8 HEAD > built.txt\"\n",
+    )
+    .unwrap();
+
+    let output = stdout(&home.run(&registry, &["--build-local", "@1"]));
+    assert!(output.contains("Built nightly"), "{output}");
+    let worktree = home
+        .dir
+        .path()
+        .join(".cache/nightlies/worktrees")
+        .join(format!("nightly-{}", home.commits[1]));
+    let built = std::fs::read_to_string(worktree.join("built.txt")).unwrap();
+    assert_eq!(built.trim(), home.commits[1]);
+}
+
+#[test]
+fn symbols_are_located_and_downloaded_from_url_templates() {
+    let home = FixtureHome::new(2);
+    let labels = HashMap::from([(
+        format!("nightly-main-{}-py3", home.commits[1]),
+        vec![(
+            String::from("com.datadoghq.ci.pipeline_id"),
+            String::from("42"),
+        )],
+    )]);
+    let registry = FakeRegistry::start_with_labels(
+        vec![[home.tags_for_commit(1), home.tags_for_commit(0)].concat()],
+        labels,
+    );
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        format!(
+            "[symbols]\nurls = [\"https://symbols.example/{{pipeline}}/{{sha}}.tar\", \"{}/{{tag}}\"]\n",
+            registry.url
+        ),
+    )
+    .unwrap();
+
+    let output = stdout(&home.run(&registry, &["--symbols", "@0"]));
+    let expected = format!("https://symbols.example/42/{}.tar", home.commits[1]);
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+
+    std::fs::write(
+        config_dir.join("config.toml"),
+        format!("[symbols]\nurls = [\"{}/{{tag}}\"]\n", registry.url),
+    )
+    .unwrap();
+    let dir = home.dir.path().join("symbols");
+    let output = stdout(&home.run(
+        &registry,
+        &[
+            "--symbols",
+            "@0",
+            "--download-symbols",
+            dir.to_str().unwrap(),
+        ],
+    ));
+    assert!(output.contains("Downloaded"), "{output}");
+    let tag = format!("nightly-main-{}-py3", home.commits[1]);
+    let downloaded = std::fs::read_to_string(dir.join(&tag)).unwrap();
+    assert!(downloaded.contains(&tag), "{downloaded}");
+}
+
+#[test]
+fn conflicts_with_lists_files_changed_on_both_sides() {
+    let home = FixtureHome::new(4);
+    // Fixture commit 2 adds cmd/file2.go
+    home.add_local_branch("my-feature", 1, "cmd/file2.go");
+    home.add_local_branch("unrelated", 1, "docs/notes.md");
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(3), home.tags_for_commit(1)].concat()
+    ]);
+
+    let output = stdout(&home.run(&registry, &["--conflicts-with", "my-feature"]));
+    assert!(output.contains("  cmd/file2.go"), "{output}");
+    assert!(output.contains("change 2 (#1002)"), "{output}");
+    assert!(!output.contains("change 3"), "{output}");
+
+    let output = stdout(&home.run(
+        &registry,
+        &["--conflicts-with", "unrelated", "--range", "@1..@0"],
+    ));
+    assert!(output.contains("No file changed by unrelated"), "{output}");
+}
+
+#[test]
+fn as_of_prints_the_latest_nightly_at_a_past_time() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+
+    // Nightlies are pushed 4 hours after their commit
+    let instant = common::commit_time(1) + chrono::Duration::hours(5);
+    let output = stdout(&home.run(
+        &registry,
+        &["--as-of", &instant.format("%Y-%m-%dT%H:%MZ").to_string()],
+    ));
+    let expected = format!("nightly-main-{}-py3", home.commits[1]);
+    assert!(
+        output.contains(&expected),
+        "{expected} missing from {output}"
+    );
+
+    let output = home.run(&registry, &["--as-of", "2000-01-01"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+}
+
+#[test]
+fn pull_metadata_is_refreshed_and_summarized() {
+    let home = FixtureHome::new(2);
+    let tags = [home.tags_for_commit(1), home.tags_for_commit(0)].concat();
+    let registry = FakeRegistry::start(vec![tags.clone()]);
+    let output = stdout(&home.run(&registry, &["--build-sha", &home.commits[1]]));
+    assert!(!output.contains("Last Pulled"), "{output}");
+
+    let pulled_at = chrono::Utc::now() - chrono::Duration::days(1);
+    let pulled: Vec<_> = tags
+        .into_iter()
+        .map(|mut tag| {
+            if tag.name.contains(&home.commits[1]) {
+                tag.last_pulled = Some(pulled_at);
+                tag.pull_count = Some(12);
+            }
+            tag
+        })
+        .collect();
+    let registry = FakeRegistry::start(vec![pulled]);
+    let output = stdout(&home.run(&registry, &["--build-sha", &home.commits[1]]));
+    assert!(
+        output.contains(&format!("Last Pulled: {}", pulled_at.to_rfc3339())),
+        "{output}"
+    );
+    assert!(output.contains("Pulls: 12"), "{output}");
+
+    let output = stdout(&home.run(&registry, &[]));
+    let summary = output
+        .lines()
+        .find(|l| l.starts_with("Pulled in the last 7 days:"))
+        .unwrap_or_else(|| panic!("summary missing from {output}"));
+    assert!(summary.ends_with(" 1 of 2 nightlies"), "{summary}");
+}
+
+#[test]
+fn site_renders_a_timeline_and_a_page_per_nightly() {
+    let home = FixtureHome::new(4);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(3), home.tags_for_commit(0)].concat()
+    ]);
+    let site = home.dir.path().join("public");
+    let output = home.run(&registry, &["--site", site.to_str().unwrap()]);
+    assert!(output.status.success(), "{output:?}");
+
+    let index = std::fs::read_to_string(site.join("index.html")).unwrap();
+    let newest = &home.commits[3];
+    assert!(
+        index.contains(&format!("href=\"nightly/{newest}.html\"")),
+        "{index}"
+    );
+    assert!(index.contains("3 new commits"), "{index}");
+
+    let page = std::fs::read_to_string(site.join(format!("nightly/{newest}.html"))).unwrap();
+    assert!(page.contains("change 2 (#1002)"), "{page}");
+    assert!(
+        page.contains(&format!("compare/{}...{newest}", home.commits[0])),
+        "{page}"
+    );
+    assert!(site
+        .join(format!("nightly/{}.html", home.commits[0]))
+        .exists());
+}
+
+#[test]
+fn unwritable_site_directories_have_their_own_exit_code() {
+    let home = FixtureHome::new(1);
+    let registry = FakeRegistry::start(vec![home.tags_for_commit(0)]);
+    // A file where the directory should be, which even root cannot write under
+    let site = home.dir.path().join("public");
+    std::fs::write(&site, "not a directory").unwrap();
+
+    let output = home.run(&registry, &["--site", site.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(9), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Could not write"),
+        "{output:?}"
+    );
+}
+
+#[test]
+fn feed_has_an_entry_per_nightly_with_its_changes() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(0)].concat()
+    ]);
+    let path = home.dir.path().join("nightlies.xml");
+    let output = home.run(&registry, &["--feed", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{output:?}");
+
+    let feed = std::fs::read_to_string(path).unwrap();
+    assert!(
+        feed.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"),
+        "{feed}"
+    );
+    assert_eq!(feed.matches("<entry>").count(), 2, "{feed}");
+    let newest = feed.find(&home.commits[2]).unwrap();
+    assert!(
+        newest
+            < feed
+                .find(&format!("urn:nightlies:{}", home.commits[0]))
+                .unwrap()
+    );
+    assert!(feed.contains("2 new commits since"), "{feed}");
+    assert!(feed.contains("change 1 (#1001)"), "{feed}");
+}
+
+#[test]
+fn ical_exports_publish_times_and_the_next_nightly() {
+    let home = FixtureHome::new(3);
+    let registry = FakeRegistry::start(vec![[
+        home.tags_for_commit(2),
+        home.tags_for_commit(1),
+        home.tags_for_commit(0),
+    ]
+    .concat()]);
+    let path = home.dir.path().join("nightlies.ics");
+    stdout(&home.run(&registry, &["--ical", path.to_str().unwrap()]));
+
+    let calendar = std::fs::read_to_string(&path).unwrap();
+    assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"), "{calendar}");
+    assert!(calendar.ends_with("END:VCALENDAR\r\n"), "{calendar}");
+    assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 4, "{calendar}");
+    let pushed = (common::commit_time(1) + chrono::Duration::hours(4))
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string();
+    assert!(
+        calendar.contains(&format!("DTSTART:{pushed}")),
+        "{calendar}"
+    );
+    assert!(calendar.contains("SUMMARY:Expected nightly"), "{calendar}");
+
+    stdout(&home.run(
+        &registry,
+        &["--ical", path.to_str().unwrap(), "--ical-days", "1"],
+    ));
+    let calendar = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1, "{calendar}");
+}
+
+#[test]
+fn background_refresh_answers_from_the_cache() {
+    let home = FixtureHome::new(2);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat()
+    ]);
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[refresh]\nbackground = true\n",
+    )
+    .unwrap();
+
+    // Nothing is cached yet, the refresh only lands after the command answered
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(!output.contains(&home.commits[1]), "{output}");
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    while !home.cache_path().exists() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "no background refresh"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let output = stdout(&home.run(&registry, &["--from-date", "2000-01-01"]));
+    assert!(output.contains(&home.commits[1]), "{output}");
+    assert!(home.cache_path().with_extension("refreshed").exists());
+}
+
+#[test]
+fn cache_only_and_refresh_control_freshness() {
+    let home = FixtureHome::new(3);
+    let old = FakeRegistry::start(vec![home.tags_for_commit(0)]);
+    stdout(&home.run(&old, &["--from-date", "2000-01-01"]));
+
+    // The registry has a new nightly, which cache-only runs don't ask for
+    let new = FakeRegistry::start(vec![
+        [home.tags_for_commit(2), home.tags_for_commit(0)].concat()
+    ]);
+    let output = stdout(&home.run(&new, &["--from-date", "2000-01-01", "--cache-only"]));
+    assert!(output.contains(&home.commits[0]), "{output}");
+    assert!(!output.contains(&home.commits[2]), "{output}");
+
+    // Background refreshes would answer from the cache, --refresh syncs first
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[refresh]\nbackground = true\ninterval_minutes = 600\n",
+    )
+    .unwrap();
+    std::fs::write(
+        home.cache_path().with_extension("refreshed"),
+        chrono::Utc::now().to_rfc3339(),
+    )
+    .unwrap();
+    let output = stdout(&home.run(&new, &["--from-date", "2000-01-01"]));
+    assert!(!output.contains(&home.commits[2]), "{output}");
+    let output = stdout(&home.run(&new, &["--from-date", "2000-01-01", "--refresh"]));
+    assert!(output.contains(&home.commits[2]), "{output}");
+
+    let output = home.run(&new, &["--refresh", "--cache-only"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
+
+#[test]
+fn unparseable_tags_are_quarantined_once() {
+    let home = FixtureHome::new(1);
+    let mut renamed = home.tags_for_commit(0)[0].clone();
+    renamed.name = String::from("nightly-main-7.55.0-py3");
+    let mut not_hex = renamed.clone();
+    not_hex.name = String::from("nightly-main-release1-py3");
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(0), vec![renamed, not_hex]].concat()
+    ]);
+
+    let output = stdout(&home.run(&registry, &[]));
+    assert!(
+        output.contains("Quarantined 2 registry entries"),
+        "{output}"
+    );
+    let output = stdout(&home.run(&registry, &[]));
+    assert!(!output.contains("Quarantined"), "{output}");
+
+    let output = stdout(&home.run(&registry, &["--quarantine", "--cache-only"]));
+    assert!(output.contains("nightly-main-7.55.0-py3"), "{output}");
+    assert!(
+        output.contains("'release1' is not an 8 character hex sha"),
+        "{output}"
+    );
+    assert!(!output.contains(&home.commits[0]), "{output}");
+}
+
+#[test]
+fn git_commands_explain_how_to_set_up_a_checkout() {
+    let home = FixtureHome::new(2);
+    let registry = FakeRegistry::start(vec![
+        [home.tags_for_commit(1), home.tags_for_commit(0)].concat()
+    ]);
+    let elsewhere = home.dir.path().join("src/datadog-agent");
+    std::fs::create_dir_all(elsewhere.parent().unwrap()).unwrap();
+    std::fs::rename(home.repo_path(), &elsewhere).unwrap();
+
+    let output = home.run(&registry, &["--agent-sha", &home.commits[0]]);
+    assert_eq!(output.status.code(), Some(5), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("[repo] section of the config"), "{stderr}");
+
+    let config_dir = home.dir.path().join(".config/nightlies");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        format!("[repo]\npath = {:?}\n", elsewhere.to_str().unwrap()),
+    )
+    .unwrap();
+    let output = stdout(&home.run(&registry, &["--agent-sha", &home.commits[0]]));
+    assert!(
+        output.contains(&format!("nightly-main-{}-py3", home.commits[0])),
+        "{output}"
+    );
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use common::{FakeRegistry, FixtureHome};
+use nightlies::{
+    client::{Freshness, NightliesClient},
+    nightly::{tags_to_nightlies, tags_to_nightlies_in, Nightly, Tag},
+};
+use tempfile::TempDir;
+
+fn tag(sha: &str) -> Tag {
+    Tag {
+        name: format!("nightly-main-{sha}-py3"),
+        last_pushed: Utc.with_ymd_and_hms(2024, 3, 4, 4, 0, 0).unwrap(),
+        digest: format!("sha256:{sha}"),
+        last_pulled: None,
+        pull_count: None,
+    }
+}
+
+fn client(registry: &FakeRegistry, cache: &TempDir) -> NightliesClient {
+    NightliesClient {
+        registry_url: registry.url.clone(),
+        oci_registry_url: registry.oci_url.clone(),
+        cache_file: cache.path().join("nightlies.json"),
+        repo_path: cache.path().join("datadog-agent"),
+        freshness: Freshness::Hybrid,
+    }
+}
+
+#[tokio::test]
+async fn differently_configured_clients_share_a_process() {
+    let (first_registry, second_registry) = (
+        FakeRegistry::start(vec![vec![tag("aaaaaaaa")]]),
+        FakeRegistry::start(vec![vec![tag("bbbbbbbb")]]),
+    );
+    let (first_cache, second_cache) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let first = client(&first_registry, &first_cache);
+    let second = client(&second_registry, &second_cache);
+
+    let (first_tags, second_tags) = tokio::join!(first.fetch_tags(1), second.fetch_tags(1));
+    let (first_tags, second_tags) = (first_tags.unwrap(), second_tags.unwrap());
+    assert_eq!(first_tags[0].name, "nightly-main-aaaaaaaa-py3");
+    assert_eq!(second_tags[0].name, "nightly-main-bbbbbbbb-py3");
+
+    first.save_cache(&tags_to_nightlies(&first_tags)).unwrap();
+    second.save_cache(&tags_to_nightlies(&second_tags)).unwrap();
+    assert_eq!(first.load_cache().unwrap()[0].sha, "aaaaaaaa");
+    assert_eq!(second.load_cache().unwrap()[0].sha, "bbbbbbbb");
+}
+
+#[test]
+fn clients_look_up_commits_in_their_own_checkout() {
+    // The same history, one commit longer in the second checkout
+    let (short, long) = (FixtureHome::new(2), FixtureHome::new(3));
+    let registry = FakeRegistry::start(vec![]);
+    let cache = TempDir::new().unwrap();
+    let with_checkout = |home: &FixtureHome| NightliesClient {
+        repo_path: home.repo_path(),
+        ..client(&registry, &cache)
+    };
+    let (short_client, long_client) = (with_checkout(&short), with_checkout(&long));
+
+    let tags = long.tags_for_commit(2);
+    let committed = |client: &NightliesClient| {
+        let repo = client.open_agent_repo().unwrap();
+        tags_to_nightlies_in(&repo, &tags)[0].sha_timestamp
+    };
+    // Looked up in the longer checkout first, so an answer remembered for one client
+    // would leak into the other
+    assert_eq!(committed(&long_client), Some(common::commit_time(2)));
+    assert_eq!(committed(&short_client), None);
+    assert_eq!(committed(&long_client), Some(common::commit_time(2)));
+}
+
+#[tokio::test]
+async fn deleted_tags_are_archived_and_unchecked_ones_kept() {
+    let live = vec![tag("aaaaaaaa")];
+    let registry = FakeRegistry::start(vec![live.clone()]);
+    let cache = TempDir::new().unwrap();
+    let nightlies = || {
+        ["aaaaaaaa", "bbbbbbbb", "cccccccc"]
+            .map(|sha| Nightly::builder(sha).tags([tag(sha)]).build().unwrap())
+    };
+
+    let mut checked = nightlies();
+    let archived = client(&registry, &cache)
+        .archive_deleted_nightlies(&live, &mut checked)
+        .await
+        .unwrap();
+    assert_eq!(archived, 2);
+    assert_eq!(checked.map(|n| n.archived), [false, true, true]);
+
+    // Nothing listens on the discard port, no check succeeds and nothing is archived
+    let unreachable = NightliesClient {
+        registry_url: String::from("http://127.0.0.1:9/tags"),
+        ..client(&registry, &cache)
+    };
+    let mut unchecked = nightlies();
+    let archived = unreachable
+        .archive_deleted_nightlies(&live, &mut unchecked)
+        .await
+        .unwrap();
+    assert_eq!(archived, 0);
+    assert!(unchecked.iter().all(|n| !n.archived));
+}
+
+#[tokio::test]
+async fn pages_are_reassembled_in_order() {
+    let pages: Vec<Vec<Tag>> = (0..4)
+        .map(|page| {
+            vec![
+                tag(&format!("{page}aaaaaaa")),
+                tag(&format!("{page}bbbbbbb")),
+            ]
+        })
+        .collect();
+    let registry = FakeRegistry::start(pages.clone());
+    let cache = TempDir::new().unwrap();
+    let client = client(&registry, &cache);
+
+    let names = |tags: &[Tag]| tags.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+    let fetched = client.fetch_tags(3).await.unwrap();
+    assert_eq!(names(&fetched), names(&pages[..3].concat()));
+    let fetched = client.fetch_tags(10).await.unwrap();
+    assert_eq!(names(&fetched), names(&pages.concat()));
+}
+
+#[test]
+fn failed_background_refreshes_are_retried_before_the_interval() {
+    let registry = FakeRegistry::start(vec![vec![tag("0123abcd")]]);
+    let cache = TempDir::new().unwrap();
+    let client = client(&registry, &cache);
+    let interval = chrono::Duration::hours(1);
+
+    assert!(client.claim_background_refresh(interval).unwrap());
+    // The refresh may still be running
+    assert!(!client.claim_background_refresh(interval).unwrap());
+
+    // It never recorded a success, so once the retry delay passed it's tried again
+    let attempted = Utc::now() - chrono::Duration::minutes(10);
+    std::fs::write(
+        cache.path().join("nightlies.refresh-attempted"),
+        attempted.to_rfc3339(),
+    )
+    .unwrap();
+    assert!(client.claim_background_refresh(interval).unwrap());
+
+    client.record_refresh().unwrap();
+    std::fs::write(
+        cache.path().join("nightlies.refresh-attempted"),
+        attempted.to_rfc3339(),
+    )
+    .unwrap();
+    assert!(!client.claim_background_refresh(interval).unwrap());
+}
@@ -0,0 +1,417 @@
+// Shared harness for the end-to-end tests: a fake docker registry serving canned
+// tag pages and a fixture datadog-agent checkout inside a throwaway home directory
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::LazyLock,
+    thread,
+};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use nightlies::nightly::Tag;
+use serde_json::json;
+use tempfile::TempDir;
+
+/// A local HTTP server mimicking the docker hub tags API, and the OCI distribution API
+/// for image labels
+pub struct FakeRegistry {
+    /// Value for `NIGHTLIES_REGISTRY_URL`
+    pub url: String,
+    /// Value for `NIGHTLIES_OCI_REGISTRY_URL`
+    pub oci_url: String,
+}
+
+/// Bearer token handed out by the fake registry's token endpoint
+const FAKE_TOKEN: &str = "fake-token";
+
+impl FakeRegistry {
+    /// Serves `pages` of tags, each page linking to the next one like docker hub does
+    /// Tags from any page can also be looked up individually, anything else is a 404
+    pub fn start(pages: Vec<Vec<Tag>>) -> Self {
+        Self::start_with_labels(pages, HashMap::new())
+    }
+
+    /// Like [`FakeRegistry::start`], also serving images with the given labels per tag
+    /// behind token authentication
+    pub fn start_with_labels(
+        pages: Vec<Vec<Tag>>,
+        labels: HashMap<String, Vec<(String, String)>>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind fake registry");
+        let address = listener.local_addr().unwrap();
+        let url = format!("http://{address}/tags");
+        let oci_url = format!("http://{address}/v2/datadog/agent-dev");
+
+        let base_url = url.clone();
+        let token_url = format!("http://{address}/token");
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                // Drain the headers, the requests we care about have no body
+                let mut authorized = false;
+                let mut header = String::new();
+                while reader.read_line(&mut header).is_ok() && header != "\r\n" {
+                    if header
+                        .trim()
+                        .eq_ignore_ascii_case(&format!("authorization: Bearer {FAKE_TOKEN}"))
+                    {
+                        authorized = true;
+                    }
+                    header.clear();
+                }
+
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let (status, extra_headers, body) = match path.strip_prefix("/v2/datadog/agent-dev/") {
+                    Some(_) if !authorized => (
+                        "401 Unauthorized",
+                        format!("WWW-Authenticate: Bearer realm=\"{token_url}\",service=\"fake\",scope=\"repository:datadog/agent-dev:pull\"\r\n"),
+                        String::from("{}"),
+                    ),
+                    Some(oci_path) => {
+                        let (status, body) = respond_oci(&labels, oci_path);
+                        (status, String::new(), body)
+                    }
+                    None if path.starts_with("/token?") => {
+                        ("200 OK", String::new(), json!({ "token": FAKE_TOKEN }).to_string())
+                    }
+                    None => {
+                        let (status, body) = respond(&base_url, &pages, path);
+                        (status, String::new(), body)
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\n{extra_headers}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        Self { url, oci_url }
+    }
+}
+
+/// Serves a multi-platform index per tag, whose amd64 manifest points at a config
+/// carrying the tag's labels
+fn respond_oci(
+    labels: &HashMap<String, Vec<(String, String)>>,
+    path: &str,
+) -> (&'static str, String) {
+    let not_found = ("404 Not Found", String::from("{}"));
+    if let Some(reference) = path.strip_prefix("manifests/") {
+        if let Some(tag) = reference.strip_prefix("sha256:amd64-") {
+            let body = json!({ "config": { "digest": format!("sha256:config-{tag}") } });
+            return ("200 OK", body.to_string());
+        }
+        if !labels.contains_key(reference) {
+            return not_found;
+        }
+        let body = json!({
+            "manifests": [
+                { "digest": "sha256:arm64-unused", "platform": { "os": "linux", "architecture": "arm64" } },
+                { "digest": format!("sha256:amd64-{reference}"), "platform": { "os": "linux", "architecture": "amd64" } },
+            ]
+        });
+        return ("200 OK", body.to_string());
+    }
+    let Some(tag) = path.strip_prefix("blobs/sha256:config-") else {
+        return not_found;
+    };
+    let Some(tag_labels) = labels.get(tag) else {
+        return not_found;
+    };
+    let tag_labels: serde_json::Map<String, serde_json::Value> = tag_labels
+        .iter()
+        .map(|(key, value)| (key.clone(), json!(value)))
+        .collect();
+    (
+        "200 OK",
+        json!({ "config": { "Labels": tag_labels } }).to_string(),
+    )
+}
+
+fn respond(base_url: &str, pages: &[Vec<Tag>], path: &str) -> (&'static str, String) {
+    if let Some(tag_name) = path.strip_prefix("/tags/") {
+        return match pages.iter().flatten().find(|t| t.name == tag_name) {
+            Some(tag) => ("200 OK", serde_json::to_string(tag).unwrap()),
+            None => ("404 Not Found", String::from("{}")),
+        };
+    }
+
+    let page: usize = path
+        .split(['?', '&'])
+        .find_map(|param| param.strip_prefix("page="))
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(1);
+    let Some(tags) = pages.get(page - 1) else {
+        return ("404 Not Found", String::from("{}"));
+    };
+    let next = (page < pages.len()).then(|| format!("{base_url}?page={}", page + 1));
+    let body = json!({
+        "count": pages.iter().map(Vec::len).sum::<usize>(),
+        "next": next,
+        "results": tags,
+    });
+    ("200 OK", body.to_string())
+}
+
+/// A throwaway home directory containing a datadog-agent checkout with one commit per day
+pub struct FixtureHome {
+    pub dir: TempDir,
+    /// Short shas of the fixture commits, oldest first
+    pub commits: Vec<String>,
+}
+
+/// Fixture commits start a month ago so they are inside the default cache retention
+static FIRST_COMMIT_TIME: LazyLock<DateTime<Utc>> = LazyLock::new(|| {
+    let start = (Utc::now() - Duration::days(30)).date_naive();
+    Utc.from_utc_datetime(&start.and_hms_opt(10, 0, 0).unwrap())
+});
+
+/// Timestamp of the nth fixture commit
+pub fn commit_time(n: usize) -> DateTime<Utc> {
+    *FIRST_COMMIT_TIME + Duration::days(n as i64)
+}
+
+fn git(repo: &Path, args: &[&str], date: Option<DateTime<Utc>>) -> String {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo).args(args);
+    command.env("GIT_AUTHOR_NAME", "Fixture Author");
+    command.env("GIT_AUTHOR_EMAIL", "fixture@example.com");
+    command.env("GIT_COMMITTER_NAME", "Fixture Author");
+    command.env("GIT_COMMITTER_EMAIL", "fixture@example.com");
+    if let Some(date) = date {
+        command.env("GIT_AUTHOR_DATE", date.to_rfc3339());
+        command.env("GIT_COMMITTER_DATE", date.to_rfc3339());
+    }
+    let output = command.output().expect("Could not run git");
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+impl FixtureHome {
+    pub fn new(num_commits: usize) -> Self {
+        let dir = TempDir::new().expect("Could not create fixture home");
+        std::fs::create_dir(dir.path().join("tmp")).unwrap();
+        let repo = dir.path().join("go/src/github.com/DataDog/datadog-agent");
+        std::fs::create_dir_all(&repo).unwrap();
+
+        git(&repo, &["init", "-q", "-b", "main"], None);
+        let mut commits = Vec::new();
+        for n in 0..num_commits {
+            let component = ["pkg", "comp", "cmd"][n % 3];
+            std::fs::create_dir_all(repo.join(component)).unwrap();
+            std::fs::write(
+                repo.join(component).join(format!("file{n}.go")),
+                n.to_string(),
+            )
+            .unwrap();
+            git(&repo, &["add", "-A"], None);
+            let message = format!("change {n} (#{})", 1000 + n);
+            git(
+                &repo,
+                &["commit", "-q", "-m", &message],
+                Some(commit_time(n)),
+            );
+            commits.push(git(&repo, &["rev-parse", "--short=8", "HEAD"], None));
+        }
+        git(
+            &repo,
+            &["update-ref", "refs/remotes/origin/main", "HEAD"],
+            None,
+        );
+
+        Self { dir, commits }
+    }
+
+    /// Points the remote branch `origin/<name>` at the fixture commit `n`, plus one
+    /// commit of its own like a release branch
+    pub fn add_remote_branch(&self, name: &str, n: usize) {
+        let repo = self.repo_path();
+        git(
+            &repo,
+            &["checkout", "-q", "-b", name, &self.commits[n]],
+            None,
+        );
+        std::fs::write(repo.join("release.json"), name).unwrap();
+        git(&repo, &["add", "-A"], None);
+        git(
+            &repo,
+            &["commit", "-q", "-m", &format!("cut {name}")],
+            Some(commit_time(n) + Duration::hours(1)),
+        );
+        git(
+            &repo,
+            &["update-ref", &format!("refs/remotes/origin/{name}"), "HEAD"],
+            None,
+        );
+        git(&repo, &["checkout", "-q", "main"], None);
+    }
+
+    /// Creates the local branch `name` off the fixture commit `n`, with one commit
+    /// writing `path`
+    pub fn add_local_branch(&self, name: &str, n: usize, path: &str) {
+        let repo = self.repo_path();
+        git(
+            &repo,
+            &["checkout", "-q", "-b", name, &self.commits[n]],
+            None,
+        );
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(repo.join(dir)).unwrap();
+        }
+        std::fs::write(repo.join(path), name).unwrap();
+        git(&repo, &["add", "-A"], None);
+        git(
+            &repo,
+            &["commit", "-q", "-m", &format!("work on {name}")],
+            Some(commit_time(n) + Duration::hours(1)),
+        );
+        git(&repo, &["checkout", "-q", "main"], None);
+    }
+
+    /// Merges a branch off the fixture commit `n` into main, the next fixture commit
+    /// The branch commit keeps the date of commit `n`, like work merged long after it began
+    pub fn merge_branch(&mut self, name: &str, n: usize) {
+        let repo = self.repo_path();
+        git(
+            &repo,
+            &["checkout", "-q", "-b", name, &self.commits[n]],
+            None,
+        );
+        std::fs::write(repo.join(format!("{name}.go")), name).unwrap();
+        git(&repo, &["add", "-A"], None);
+        git(
+            &repo,
+            &["commit", "-q", "-m", &format!("work on {name}")],
+            Some(commit_time(n) + Duration::hours(1)),
+        );
+        git(&repo, &["checkout", "-q", "main"], None);
+        git(
+            &repo,
+            &[
+                "merge",
+                "-q",
+                "--no-ff",
+                "-m",
+                &format!("merge {name}"),
+                name,
+            ],
+            Some(commit_time(self.commits.len())),
+        );
+        self.commits
+            .push(git(&repo, &["rev-parse", "--short=8", "HEAD"], None));
+        git(
+            &repo,
+            &["update-ref", "refs/remotes/origin/main", "HEAD"],
+            None,
+        );
+    }
+
+    /// Adds a commit on main writing `content` to `path`, the next fixture commit
+    pub fn commit_file(&mut self, path: &str, content: &str) {
+        let repo = self.repo_path();
+        std::fs::write(repo.join(path), content).unwrap();
+        git(&repo, &["add", "-A"], None);
+        git(
+            &repo,
+            &["commit", "-q", "-m", &format!("update {path}")],
+            Some(commit_time(self.commits.len())),
+        );
+        self.commits
+            .push(git(&repo, &["rev-parse", "--short=8", "HEAD"], None));
+        git(
+            &repo,
+            &["update-ref", "refs/remotes/origin/main", "HEAD"],
+            None,
+        );
+    }
+
+    /// Where the cache file lives for this home
+    pub fn cache_path(&self) -> PathBuf {
+        self.dir.path().join("tmp/agent_nightlies.json")
+    }
+
+    pub fn repo_path(&self) -> PathBuf {
+        self.dir
+            .path()
+            .join("go/src/github.com/DataDog/datadog-agent")
+    }
+
+    /// Registry tags for the fixture commit `n`, pushed a few hours after the commit
+    pub fn tags_for_commit(&self, n: usize) -> Vec<Tag> {
+        let sha = &self.commits[n];
+        ["-py3", "-py3-jmx"]
+            .iter()
+            .map(|suffix| Tag {
+                name: format!("nightly-main-{sha}{suffix}"),
+                last_pushed: commit_time(n) + Duration::hours(4),
+                digest: format!("sha256:{sha}{suffix}"),
+                last_pulled: None,
+                pull_count: None,
+            })
+            .collect()
+    }
+
+    fn command(&self, registry: &FakeRegistry, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_nightlies"));
+        command
+            .args(args)
+            .env("HOME", self.dir.path())
+            .env("TMPDIR", self.dir.path().join("tmp"))
+            .env("NIGHTLIES_REGISTRY_URL", &registry.url)
+            .env("NIGHTLIES_OCI_REGISTRY_URL", &registry.oci_url)
+            .env("NO_COLOR", "1");
+        command
+    }
+
+    /// Runs the nightlies binary against this home directory and the given registry
+    pub fn run(&self, registry: &FakeRegistry, args: &[&str]) -> Output {
+        self.command(registry, args)
+            .output()
+            .expect("Could not run nightlies")
+    }
+
+    /// Like [`FixtureHome::run`], with an extra environment variable
+    pub fn run_with_env(
+        &self,
+        registry: &FakeRegistry,
+        args: &[&str],
+        (name, value): (&str, &str),
+    ) -> Output {
+        self.command(registry, args)
+            .env(name, value)
+            .output()
+            .expect("Could not run nightlies")
+    }
+
+    /// Like [`FixtureHome::run`], feeding `stdin` to the binary
+    pub fn run_with_stdin(&self, registry: &FakeRegistry, args: &[&str], stdin: &str) -> Output {
+        let mut child = self
+            .command(registry, args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Could not run nightlies");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().expect("Could not run nightlies")
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::BTreeMap;
+
+use nightlies::image::pipeline_from_labels;
+
+fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn pipeline_from_id_and_url_labels() {
+    let labels = labels(&[
+        ("org.opencontainers.image.version", "7.60.0-devel"),
+        ("com.datadoghq.ci.pipeline_id", "42"),
+        (
+            "com.datadoghq.ci.pipeline_url",
+            "https://gitlab.example.com/pipelines/42",
+        ),
+    ]);
+    assert_eq!(
+        pipeline_from_labels(&labels).as_deref(),
+        Some("pipeline 42 (https://gitlab.example.com/pipelines/42)")
+    );
+}
+
+#[test]
+fn pipeline_needs_a_pipeline_label() {
+    assert_eq!(pipeline_from_labels(&labels(&[])), None);
+    let unrelated = labels(&[(
+        "org.opencontainers.image.source",
+        "https://github.com/DataDog/datadog-agent",
+    )]);
+    assert_eq!(pipeline_from_labels(&unrelated), None);
+    let id_only = labels(&[("CI_PIPELINE_ID", "7")]);
+    assert_eq!(
+        pipeline_from_labels(&id_only).as_deref(),
+        Some("pipeline 7")
+    );
+}
@@ -0,0 +1,603 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono::{Datelike, Weekday};
+use nightlies::{
+    business_day::WeekendFilter,
+    nightly::{Nightly, Tag},
+    query::{
+        dedupe, group_untracked_tags, latest_as_of, link_predecessors, nth_latest, query_range,
+        sort_oldest_first, Clock,
+    },
+};
+use proptest::prelude::*;
+
+fn base_time() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap()
+}
+
+fn tag(sha: &str, suffix: &str, last_pushed: DateTime<Utc>) -> Tag {
+    Tag {
+        name: format!("nightly-main-{sha}{suffix}"),
+        last_pushed,
+        digest: format!("sha256:{sha}"),
+        last_pulled: None,
+        pull_count: None,
+    }
+}
+
+prop_compose! {
+    fn arb_nightly()(
+        // A small sha space so that duplicates are common
+        sha in "0000[0-3]{4}",
+        pushed_offset in 0i64..60 * 24 * 60,
+        commit_offset in proptest::option::of(0i64..60 * 24 * 60),
+    ) -> Nightly {
+        let estimated_last_pushed = base_time() + Duration::minutes(pushed_offset);
+        Nightly {
+            py3: Some(tag(&sha, "-py3", estimated_last_pushed)),
+            sha,
+            estimated_last_pushed,
+            sha_timestamp: commit_offset.map(|o| base_time() + Duration::minutes(o)),
+            py2: None,
+            py3_jmx: None,
+            py2_jmx: None,
+            jmx: None,
+            archived: false,
+            resolve_attempts: 0,
+            predecessor_sha: None,
+            incremental_commits: None,
+            labels: None,
+            flavor_tags: Vec::new(),
+            go_version: None,
+        }
+    }
+}
+
+fn timestamp(n: &Nightly) -> DateTime<Utc> {
+    n.sha_timestamp.unwrap_or(n.estimated_last_pushed)
+}
+
+proptest! {
+    #[test]
+    fn dedupe_keeps_one_of_each_sha(mut nightlies in proptest::collection::vec(arb_nightly(), 0..50)) {
+        let original = nightlies.clone();
+        dedupe(&mut nightlies);
+
+        let mut shas: Vec<&str> = nightlies.iter().map(|n| n.sha.as_str()).collect();
+        let num_shas = shas.len();
+        shas.sort_unstable();
+        shas.dedup();
+        prop_assert_eq!(shas.len(), num_shas);
+        for n in &original {
+            prop_assert!(nightlies.iter().any(|d| d.sha == n.sha));
+        }
+
+        let deduped = nightlies.clone();
+        dedupe(&mut nightlies);
+        prop_assert_eq!(nightlies, deduped);
+    }
+
+    #[test]
+    fn latest_as_of_is_the_last_push_by_then(
+        nightlies in proptest::collection::vec(arb_nightly(), 0..50),
+        offset in 0i64..60 * 24 * 60,
+    ) {
+        let instant = base_time() + Duration::minutes(offset);
+        match latest_as_of(&nightlies, instant) {
+            Some(latest) => {
+                prop_assert!(latest.estimated_last_pushed <= instant);
+                let newer_by_then = nightlies.iter().any(|n| {
+                    n.estimated_last_pushed > latest.estimated_last_pushed
+                        && n.estimated_last_pushed <= instant
+                });
+                prop_assert!(!newer_by_then);
+            }
+            None => prop_assert!(nightlies.iter().all(|n| n.estimated_last_pushed > instant)),
+        }
+    }
+
+    #[test]
+    fn query_range_matches_bounds(
+        nightlies in proptest::collection::vec(arb_nightly(), 0..50),
+        from_offset in 0i64..60 * 24 * 60,
+        span in proptest::option::of(0i64..60 * 24 * 60),
+    ) {
+        let from = base_time() + Duration::minutes(from_offset);
+        let to = span.map(|s| from + Duration::minutes(s));
+        let selected: Vec<&Nightly> = query_range(&nightlies, from, to).collect();
+
+        for n in &nightlies {
+            let in_range = timestamp(n) >= from && to.map_or(true, |to| timestamp(n) <= to);
+            prop_assert_eq!(selected.iter().any(|s| std::ptr::eq(*s, n)), in_range);
+        }
+    }
+
+    #[test]
+    fn sort_oldest_first_is_ordered(nightlies in proptest::collection::vec(arb_nightly(), 0..50)) {
+        let mut sorted: Vec<&Nightly> = nightlies.iter().collect();
+        sort_oldest_first(&mut sorted, Clock::Commit);
+        prop_assert_eq!(sorted.len(), nightlies.len());
+        for pair in sorted.windows(2) {
+            prop_assert!(pair[0].sha_timestamp <= pair[1].sha_timestamp);
+        }
+
+        sort_oldest_first(&mut sorted, Clock::Push);
+        for pair in sorted.windows(2) {
+            prop_assert!(pair[0].estimated_last_pushed <= pair[1].estimated_last_pushed);
+        }
+    }
+
+    #[test]
+    fn nth_latest_selects_by_commit_time(
+        nightlies in proptest::collection::vec(arb_nightly(), 0..50),
+        n in 0usize..60,
+    ) {
+        let selected = nth_latest(&nightlies, n);
+        if n >= nightlies.len() {
+            prop_assert!(selected.is_none());
+        } else {
+            let selected = selected.unwrap();
+            let newer = nightlies.iter().filter(|o| o.sha_timestamp > selected.sha_timestamp).count();
+            let same = nightlies.iter().filter(|o| o.sha_timestamp == selected.sha_timestamp).count();
+            prop_assert!(newer <= n && n < newer + same);
+        }
+    }
+
+    #[test]
+    fn group_untracked_tags_skips_tracked(
+        tracked in proptest::collection::vec(arb_nightly(), 0..20),
+        untracked in proptest::collection::vec(arb_nightly(), 0..20),
+    ) {
+        let tags: Vec<Tag> = tracked
+            .iter()
+            .chain(untracked.iter())
+            .flat_map(|n| [tag(&n.sha, "-py3", n.estimated_last_pushed), tag(&n.sha, "-jmx", n.estimated_last_pushed)])
+            .collect();
+        let grouped = group_untracked_tags(&tags, &tracked);
+
+        for n in &tracked {
+            prop_assert!(!grouped.contains_key(&n.sha));
+        }
+        for n in untracked.iter().filter(|u| !tracked.iter().any(|t| t.sha == u.sha)) {
+            let tags_for_sha = &grouped[&n.sha];
+            prop_assert!(tags_for_sha.iter().all(|t| t.name.contains(&n.sha)));
+        }
+    }
+
+    #[test]
+    fn weekend_filter_uses_the_chosen_clock(n in arb_nightly()) {
+        let on_weekend = |t: DateTime<Utc>| matches!(t.weekday(), Weekday::Sat | Weekday::Sun);
+        prop_assert!(!WeekendFilter::Off.excludes(&n));
+        prop_assert_eq!(WeekendFilter::Push.excludes(&n), on_weekend(n.estimated_last_pushed));
+        prop_assert_eq!(WeekendFilter::Commit.excludes(&n), on_weekend(timestamp(&n)));
+    }
+
+    #[test]
+    fn link_predecessors_points_to_nearest_older(mut nightlies in proptest::collection::vec(arb_nightly(), 0..50)) {
+        dedupe(&mut nightlies);
+        for n in &mut nightlies {
+            n.incremental_commits = Some(1);
+        }
+        link_predecessors(&mut nightlies);
+
+        for n in &nightlies {
+            let Some(prev_sha) = &n.predecessor_sha else {
+                prop_assert!(n.sha_timestamp.is_none() || !nightlies.iter().any(|o| o.sha_timestamp.is_some() && o.sha_timestamp < n.sha_timestamp));
+                continue;
+            };
+            let prev = nightlies.iter().find(|o| &o.sha == prev_sha).unwrap();
+            prop_assert!(prev.sha_timestamp.is_some() && prev.sha_timestamp < n.sha_timestamp);
+            prop_assert!(!nightlies.iter().any(|o| o.sha_timestamp > prev.sha_timestamp && o.sha_timestamp < n.sha_timestamp));
+        }
+
+        // Linking again keeps the links, and the counts computed for them
+        for n in &mut nightlies {
+            n.incremental_commits = Some(1);
+        }
+        let linked = nightlies.clone();
+        link_predecessors(&mut nightlies);
+        prop_assert_eq!(linked, nightlies);
+    }
+}
+
+#[test]
+fn group_untracked_tags_skips_tags_without_sha() {
+    let tags = vec![
+        tag("", "py3", base_time()),
+        tag("0123abcd", "-py3", base_time()),
+    ];
+    let grouped = group_untracked_tags(&tags, &[]);
+    assert_eq!(grouped.len(), 1);
+    assert_eq!(grouped["0123abcd"].len(), 1);
+}
+
+#[test]
+#[cfg(feature = "client")]
+fn exclusion_window_applies_offset_and_wraps_hours() {
+    let config: nightlies::config::Config = toml::from_str(
+        r#"
+        [[exclude]]
+        days = ["sat"]
+        hours = "22-6"
+        utc_offset = "+02:00"
+        "#,
+    )
+    .unwrap();
+    let window = &config.exclude[0];
+
+    let mut n = Nightly {
+        py3: None,
+        sha: String::from("0123abcd"),
+        estimated_last_pushed: base_time(),
+        sha_timestamp: None,
+        py2: None,
+        py3_jmx: None,
+        py2_jmx: None,
+        jmx: None,
+        archived: false,
+        resolve_attempts: 0,
+        predecessor_sha: None,
+        incremental_commits: None,
+        labels: None,
+        flavor_tags: Vec::new(),
+        go_version: None,
+    };
+    // Friday 21:00 UTC is Friday 23:00 at +02:00
+    n.estimated_last_pushed = Utc.with_ymd_and_hms(2024, 7, 5, 21, 0, 0).unwrap();
+    assert!(!window.excludes(&n));
+    // Saturday 03:00 UTC is Saturday 05:00 at +02:00
+    n.estimated_last_pushed = Utc.with_ymd_and_hms(2024, 7, 6, 3, 0, 0).unwrap();
+    assert!(window.excludes(&n));
+    // Saturday 12:00 UTC is outside of the hours
+    n.estimated_last_pushed = Utc.with_ymd_and_hms(2024, 7, 6, 12, 0, 0).unwrap();
+    assert!(!window.excludes(&n));
+    // Saturday 22:30 UTC is already Sunday at +02:00
+    n.estimated_last_pushed = Utc.with_ymd_and_hms(2024, 7, 6, 22, 30, 0).unwrap();
+    assert!(!window.excludes(&n));
+}
+
+#[test]
+#[cfg(feature = "client")]
+fn exclusion_window_rejects_invalid_hours() {
+    let config: Result<nightlies::config::Config, _> = toml::from_str(
+        r#"
+        [[exclude]]
+        hours = "late"
+        "#,
+    );
+    assert!(config
+        .unwrap_err()
+        .to_string()
+        .contains("invalid hours 'late'"));
+}
+
+#[test]
+fn ordinal_suffixes() {
+    use nightlies::format::ordinal_suffix;
+    let suffixes: Vec<&str> = [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 30, 31]
+        .into_iter()
+        .map(ordinal_suffix)
+        .collect();
+    assert_eq!(
+        suffixes,
+        ["st", "nd", "rd", "th", "th", "th", "th", "st", "nd", "rd", "th", "st"]
+    );
+}
+
+#[test]
+fn index_shorthand() {
+    use nightlies::query::parse_index_shorthand;
+    assert_eq!(parse_index_shorthand("@0"), Some(0));
+    assert_eq!(parse_index_shorthand("@12"), Some(12));
+    assert_eq!(parse_index_shorthand("0123abcd"), None);
+    assert_eq!(parse_index_shorthand("@"), None);
+    assert_eq!(parse_index_shorthand("@-1"), None);
+}
+
+#[test]
+fn next_nightly_estimate_wraps_around_midnight() {
+    use nightlies::nightly::estimate_next_nightly;
+    let at = |pushed: DateTime<Utc>| Nightly {
+        py3: None,
+        sha: String::from("0123abcd"),
+        estimated_last_pushed: pushed,
+        sha_timestamp: None,
+        py2: None,
+        py3_jmx: None,
+        py2_jmx: None,
+        jmx: None,
+        archived: false,
+        resolve_attempts: 0,
+        predecessor_sha: None,
+        incremental_commits: None,
+        labels: None,
+        flavor_tags: Vec::new(),
+        go_version: None,
+    };
+    // Pushed at 23:50 and 00:10 on alternate days
+    let nightlies = [
+        at(base_time() - Duration::minutes(10)),
+        at(base_time() + Duration::days(1) + Duration::minutes(10)),
+    ];
+    let estimate = estimate_next_nightly(&nightlies, base_time() + Duration::days(1)).unwrap();
+    assert_eq!(estimate.expected_at, base_time() + Duration::days(2));
+    assert_eq!(estimate.std_dev, Duration::minutes(10));
+}
+
+#[test]
+fn adaptive_window_doubles_until_a_nightly_is_found() {
+    use nightlies::query::adaptive_window_days;
+    let now = base_time() + Duration::days(100);
+    let at = |days_ago: i64| Nightly {
+        py3: None,
+        sha: String::from("0123abcd"),
+        estimated_last_pushed: now - Duration::days(days_ago),
+        sha_timestamp: None,
+        py2: None,
+        py3_jmx: None,
+        py2_jmx: None,
+        jmx: None,
+        archived: false,
+        resolve_attempts: 0,
+        predecessor_sha: None,
+        incremental_commits: None,
+        labels: None,
+        flavor_tags: Vec::new(),
+        go_version: None,
+    };
+    assert_eq!(adaptive_window_days(&[at(3)], now, 7, 56), 7);
+    assert_eq!(adaptive_window_days(&[at(10)], now, 7, 56), 14);
+    assert_eq!(adaptive_window_days(&[at(20)], now, 7, 56), 28);
+    assert_eq!(adaptive_window_days(&[at(50)], now, 7, 56), 56);
+    assert_eq!(adaptive_window_days(&[at(80)], now, 7, 56), 56);
+    assert_eq!(adaptive_window_days(&[], now, 7, 56), 56);
+}
+
+#[test]
+fn tag_format_advisory_needs_mostly_unparseable_tags() {
+    use nightlies::query::tag_format_advisory;
+    let nightly = |i: usize| tag(&format!("0000{i:04}"), "-py3", base_time());
+    let renamed = |i: usize| Tag {
+        name: format!("nightly-main-py3-{i}"),
+        last_pushed: base_time(),
+        digest: String::from("sha256:0"),
+        last_pulled: None,
+        pull_count: None,
+    };
+
+    let mostly_nightlies: Vec<Tag> = (0..6).map(nightly).chain((0..6).map(renamed)).collect();
+    assert_eq!(tag_format_advisory(&mostly_nightlies), None);
+
+    let mostly_renamed: Vec<Tag> = (0..3).map(nightly).chain((0..9).map(renamed)).collect();
+    let advisory = tag_format_advisory(&mostly_renamed).unwrap();
+    assert!(advisory.starts_with("9 of 12 registry tags"), "{advisory}");
+
+    let few: Vec<Tag> = (0..5).map(renamed).collect();
+    assert_eq!(tag_format_advisory(&few), None);
+}
+
+#[test]
+#[cfg(feature = "client")]
+fn flavor_tags_are_kept_apart_from_agent_images() {
+    use nightlies::nightly::{tags_to_nightlies, Flavor};
+    let tags = [
+        tag("0123abcd", "-py3", base_time()),
+        tag("0123abcd", "-iot", base_time()),
+        tag("0123abcd", "-dogstatsd-jmx", base_time()),
+        tag("4567cdef", "-heroku", base_time()),
+    ];
+    assert_eq!(tags[1].flavor(), Flavor::Iot);
+    assert_eq!(tags[2].flavor(), Flavor::Dogstatsd);
+    let nightlies = tags_to_nightlies(&tags);
+    let both = nightlies.iter().find(|n| n.sha == "0123abcd").unwrap();
+    assert_eq!(both.py3.as_ref().unwrap().name, tags[0].name);
+    assert!(both.jmx.is_none());
+    assert_eq!(both.flavor_tags.len(), 2);
+
+    let iot = both.only_flavor(Flavor::Iot).unwrap();
+    assert_eq!(iot.first_valid_tag().unwrap().name, tags[1].name);
+    assert!(both.only_flavor(Flavor::Heroku).is_none());
+
+    let heroku = nightlies.iter().find(|n| n.sha == "4567cdef").unwrap();
+    assert!(heroku.only_flavor(Flavor::Agent).is_none());
+    assert_eq!(heroku.first_valid_tag().unwrap().name, tags[3].name);
+}
+
+#[test]
+fn nightly_from_tags_needs_no_checkout() {
+    use nightlies::nightly::nightly_from_tags;
+    let pushed = base_time() + Duration::hours(4);
+    let tags = [
+        tag("0123abcd", "-py3-jmx", pushed),
+        tag("0123abcd", "-py3", base_time()),
+    ];
+    let nightly = nightly_from_tags("0123abcd", &tags).unwrap();
+    assert_eq!(nightly.estimated_last_pushed, base_time());
+    assert_eq!(nightly.py3_jmx.as_ref().unwrap().name, tags[0].name);
+    assert_eq!(nightly.sha_timestamp, None);
+    assert_eq!(nightly.resolve_attempts, 0);
+    assert!(nightly_from_tags("0123abcd", &[tag("0123abcd", "-unknown", pushed)]).is_err());
+}
+
+#[test]
+fn nightly_builder_validates_its_tags() {
+    use nightlies::nightly::{NightlyBuilder, ValidationError};
+    let pushed = base_time();
+    let nightly = Nightly::builder("0123abcd")
+        .tag(tag("0123abcd", "-py3", pushed))
+        .tag(tag("0123abcd", "-iot", pushed))
+        .sha_timestamp(pushed - Duration::hours(3))
+        .build()
+        .unwrap();
+    assert_eq!(nightly.estimated_last_pushed, pushed);
+    assert_eq!(nightly.sha_timestamp, Some(pushed - Duration::hours(3)));
+    assert_eq!(nightly.flavor_tags.len(), 1);
+
+    let error = |builder: NightlyBuilder| builder.build().unwrap_err().error;
+    assert_eq!(
+        error(Nightly::builder("0123abcz").tag(tag("0123abcz", "-py3", pushed))),
+        ValidationError::InvalidSha(String::from("0123abcz"))
+    );
+    assert_eq!(
+        error(Nightly::builder("0123abcd").tag(tag("4567cdef", "-py3", pushed))),
+        ValidationError::ForeignTag(String::from("nightly-main-4567cdef-py3"))
+    );
+    assert_eq!(
+        error(Nightly::builder("0123abcd").tag(tag("0123abcd", "-unknown", pushed))),
+        ValidationError::NoImage
+    );
+    assert!(matches!(
+        error(
+            Nightly::builder("0123abcd")
+                .tag(tag("0123abcd", "-py3", pushed))
+                .sha_timestamp(pushed + Duration::days(2))
+        ),
+        ValidationError::CommittedAfterPush { .. }
+    ));
+}
+
+#[test]
+fn filter_expressions_combine_fields() {
+    use nightlies::{nightly::nightly_from_tags, query::filter::Filter};
+    let nightly = |sha: &str, pushed: DateTime<Utc>, commits: Option<usize>| {
+        let mut n = nightly_from_tags(sha, &[tag(sha, "-py3", pushed)]).unwrap();
+        n.incremental_commits = commits;
+        n
+    };
+    // base_time() is a Monday
+    let monday = nightly("0123abcd", base_time(), Some(12));
+    let saturday = nightly("4567cdef", base_time() + Duration::days(5), Some(3));
+    let unknown = nightly("89abcdef", base_time() + Duration::days(1), None);
+
+    let filter: Filter = "pushed >= 2024-07-01 && !weekend && commits >= 10"
+        .parse()
+        .unwrap();
+    assert!(filter.matches(&monday));
+    assert!(!filter.matches(&saturday));
+    assert!(!filter.matches(&unknown));
+
+    let filter: Filter = "(weekend || sha ~ 89ab) && tag != \"nightly-main-89abcdef-py3\""
+        .parse()
+        .unwrap();
+    assert!(!filter.matches(&monday));
+    assert!(filter.matches(&saturday));
+    assert!(!filter.matches(&unknown));
+
+    for invalid in [
+        "size_mb < 900",
+        "pushed > soon",
+        "sha < 1",
+        "(weekend",
+        "commits ~ 1",
+        "tag = \"nightly-main",
+    ] {
+        assert!(invalid.parse::<Filter>().is_err(), "{invalid}");
+    }
+    let error = "size_mb < 900".parse::<Filter>().unwrap_err().to_string();
+    assert!(error.contains("known are pushed"), "{error}");
+    let error = "weekend )".parse::<Filter>().unwrap_err().to_string();
+    assert!(error.contains("unexpected ')'"), "{error}");
+}
+
+#[test]
+fn daily_trend_sums_commits_per_push_day() {
+    use nightlies::{
+        format::{bar, Glyphs},
+        nightly::nightly_from_tags,
+        query::{daily_trend, TrendMetric},
+    };
+    let nightly = |sha: &str, pushed: DateTime<Utc>, commits: Option<usize>| {
+        let mut n = nightly_from_tags(sha, &[tag(sha, "-py3", pushed)]).unwrap();
+        n.incremental_commits = commits;
+        n
+    };
+    let nightlies = [
+        nightly("0123abcd", base_time() + Duration::hours(4), Some(12)),
+        nightly("4567cdef", base_time() + Duration::hours(20), Some(3)),
+        nightly("89abcdef", base_time() + Duration::days(2), None),
+        nightly("fedcba98", base_time() + Duration::days(3), Some(5)),
+    ];
+
+    let day = |n: i64| (base_time() + Duration::days(n)).date_naive();
+    let trend = daily_trend(&nightlies, TrendMetric::Commits, day(0), day(3));
+    assert_eq!(
+        trend,
+        [
+            (day(0), Some(15)),
+            (day(1), None),
+            (day(2), None),
+            (day(3), Some(5))
+        ]
+    );
+
+    // Pulls are summed over each nightly's images
+    let mut pulled = nightlies.clone();
+    pulled[0].py3.as_mut().unwrap().pull_count = Some(40);
+    pulled[0].flavor_tags = vec![tag("0123abcd", "-fips", base_time())];
+    pulled[0].flavor_tags[0].pull_count = Some(2);
+    pulled[3].py3.as_mut().unwrap().pull_count = Some(7);
+    let trend = daily_trend(&pulled, TrendMetric::Pulls, day(0), day(3));
+    assert_eq!(
+        trend,
+        [
+            (day(0), Some(42)),
+            (day(1), None),
+            (day(2), None),
+            (day(3), Some(7))
+        ]
+    );
+
+    assert_eq!(bar(15, 15, 10, Glyphs::ASCII), "##########");
+    assert_eq!(bar(5, 15, 10, Glyphs::ASCII), "####");
+    assert_eq!(bar(1, 1000, 10, Glyphs::ASCII), "#");
+    assert_eq!(bar(0, 15, 10, Glyphs::ASCII), "");
+}
+
+#[test]
+fn format_templates_render_nightly_fields() {
+    use nightlies::nightly::{nightly_from_tags, NightlyTemplate};
+    let mut nightly =
+        nightly_from_tags("0123abcd", &[tag("0123abcd", "-py3", base_time())]).unwrap();
+    nightly.incremental_commits = Some(7);
+
+    let template: NightlyTemplate = "{sha}\\t{{{tag}}} +{commits} {committed}|{gh_url}"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        template.render(&nightly),
+        "0123abcd\t{nightly-main-0123abcd-py3} +7 |https://github.com/DataDog/datadog-agent/tree/0123abcd"
+    );
+    let template: NightlyTemplate = "{image} {pushed}".parse().unwrap();
+    assert_eq!(
+        template.render(&nightly),
+        "datadog/agent-dev:nightly-main-0123abcd-py3 2024-07-01T00:00:00+00:00"
+    );
+
+    for invalid in ["{sha", "{size}", "sha}", "{}"] {
+        assert!(invalid.parse::<NightlyTemplate>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn go_version_changes_ignore_how_a_version_is_spelled() {
+    use nightlies::{nightly::nightly_from_tags, query::go_version_changes};
+    let nightly = |sha: &str, days: i64, predecessor: Option<&str>, go: &str| {
+        let mut n = nightly_from_tags(sha, &[tag(sha, "-py3", base_time() + Duration::days(days))])
+            .unwrap();
+        n.predecessor_sha = predecessor.map(String::from);
+        n.go_version = Some(String::from(go));
+        n
+    };
+    let nightlies = [
+        nightly("0123abcd", 0, None, "1.22"),
+        nightly("4567cdef", 1, Some("0123abcd"), "1.22.0"),
+        nightly("89abcdef", 2, Some("4567cdef"), "go1.22.0"),
+        nightly("fedcba98", 3, Some("89abcdef"), "1.22.5"),
+    ];
+
+    let changes = go_version_changes(&nightlies);
+    assert_eq!(changes.len(), 1, "{changes:?}");
+    assert_eq!(
+        changes["fedcba98"],
+        (String::from("go1.22.0"), String::from("1.22.5"))
+    );
+}
@@ -0,0 +1,225 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use nightlies::{
+    nightly::{Nightly, Tag},
+    resolve::{
+        DateResolver, DigestResolver, IndexResolver, Resolution, Resolver, ResolverClass,
+        Resolvers, ShaResolver, TagResolver,
+    },
+};
+
+fn base_time() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 7, 1, 4, 0, 0).unwrap()
+}
+
+/// One nightly per sha, a day apart, the last one newest
+fn nightlies(shas: &[&str]) -> Vec<Nightly> {
+    shas.iter()
+        .enumerate()
+        .map(|(day, sha)| {
+            let pushed = base_time() + Duration::days(day as i64);
+            Nightly::builder(*sha)
+                .tag(Tag {
+                    name: format!("nightly-main-{sha}-py3"),
+                    last_pushed: pushed,
+                    digest: format!("sha256:{sha}{sha}"),
+                    last_pulled: None,
+                    pull_count: None,
+                })
+                .sha_timestamp(pushed - Duration::hours(4))
+                .build()
+                .unwrap()
+        })
+        .collect()
+}
+
+fn resolved_sha(
+    resolver: &dyn Resolver,
+    nightlies: &[Nightly],
+    identifier: &str,
+) -> Option<String> {
+    resolver
+        .resolve(nightlies, identifier)
+        .nightly()
+        .map(|n| n.sha.clone())
+}
+
+#[test]
+fn index_resolver_counts_back_from_the_latest() {
+    let nightlies = nightlies(&["aaaaaaaa", "bbbbbbbb", "cccccccc"]);
+    assert_eq!(
+        resolved_sha(&IndexResolver, &nightlies, "@0").as_deref(),
+        Some("cccccccc")
+    );
+    assert_eq!(
+        resolved_sha(&IndexResolver, &nightlies, "@2").as_deref(),
+        Some("aaaaaaaa")
+    );
+    assert_eq!(IndexResolver.resolve(&nightlies, "@3"), Resolution::Missing);
+    assert_eq!(
+        IndexResolver.resolve(&nightlies, "aaaaaaaa"),
+        Resolution::Unrecognized
+    );
+}
+
+#[test]
+fn digest_resolver_accepts_digest_prefixes() {
+    let nightlies = nightlies(&["aaaaaaaa", "bbbbbbbb"]);
+    assert_eq!(
+        resolved_sha(&DigestResolver, &nightlies, "sha256:bbbbbbbbbbbbbbbb").as_deref(),
+        Some("bbbbbbbb")
+    );
+    assert_eq!(
+        resolved_sha(&DigestResolver, &nightlies, "sha256:aaaaaaaaa").as_deref(),
+        Some("aaaaaaaa")
+    );
+    // Too short to be trusted
+    assert_eq!(
+        resolved_sha(&DigestResolver, &nightlies, "sha256:aaaa"),
+        None
+    );
+    assert_eq!(resolved_sha(&DigestResolver, &nightlies, "aaaaaaaa"), None);
+}
+
+#[test]
+fn tag_resolver_ignores_the_image_repository() {
+    let nightlies = nightlies(&["aaaaaaaa", "bbbbbbbb"]);
+    assert_eq!(
+        resolved_sha(&TagResolver, &nightlies, "nightly-main-aaaaaaaa-py3").as_deref(),
+        Some("aaaaaaaa")
+    );
+    assert_eq!(
+        resolved_sha(
+            &TagResolver,
+            &nightlies,
+            "datadog/agent-dev:nightly-main-bbbbbbbb-py3"
+        )
+        .as_deref(),
+        Some("bbbbbbbb")
+    );
+    assert_eq!(
+        TagResolver.resolve(&nightlies, "nightly-main-aaaaaaaa-py2"),
+        Resolution::Missing
+    );
+    // Left to the git ref resolver
+    assert_eq!(
+        TagResolver.resolve(&nightlies, "main"),
+        Resolution::Unrecognized
+    );
+}
+
+#[test]
+fn sha_resolver_shortens_full_shas() {
+    let nightlies = nightlies(&["0123abcd", "bbbbbbbb"]);
+    assert_eq!(
+        resolved_sha(&ShaResolver, &nightlies, "0123abcd").as_deref(),
+        Some("0123abcd")
+    );
+    assert_eq!(
+        resolved_sha(
+            &ShaResolver,
+            &nightlies,
+            "0123abcdef0123456789abcdef0123456789abcd"
+        )
+        .as_deref(),
+        Some("0123abcd")
+    );
+    assert_eq!(
+        ShaResolver.resolve(&nightlies, "deadbeef"),
+        Resolution::Missing
+    );
+    assert_eq!(
+        ShaResolver.resolve(&nightlies, "0123abc"),
+        Resolution::Unrecognized
+    );
+    assert_eq!(
+        ShaResolver.resolve(&nightlies, "@0"),
+        Resolution::Unrecognized
+    );
+}
+
+#[test]
+fn date_resolver_picks_the_latest_nightly_by_then() {
+    let nightlies = nightlies(&["aaaaaaaa", "bbbbbbbb", "cccccccc"]);
+    // A date covers the whole day
+    assert_eq!(
+        resolved_sha(&DateResolver, &nightlies, "2024-07-02").as_deref(),
+        Some("bbbbbbbb")
+    );
+    assert_eq!(
+        resolved_sha(&DateResolver, &nightlies, "2024-07-02T03:00:00Z").as_deref(),
+        Some("aaaaaaaa")
+    );
+    assert_eq!(resolved_sha(&DateResolver, &nightlies, "2024-06-30"), None);
+    assert_eq!(resolved_sha(&DateResolver, &nightlies, "yesterday"), None);
+}
+
+#[test]
+fn chains_only_try_the_accepted_classes() {
+    let nightlies = nightlies(&["aaaaaaaa", "bbbbbbbb"]);
+    let builds = Resolvers::accepting(ResolverClass::BUILDS);
+    assert_eq!(
+        builds.classes(),
+        [
+            ResolverClass::Index,
+            ResolverClass::Digest,
+            ResolverClass::Tag,
+            ResolverClass::Sha
+        ]
+    );
+    assert_eq!(
+        builds
+            .resolve(&nightlies, " @1 ")
+            .nightly()
+            .map(|n| n.sha.as_str()),
+        Some("aaaaaaaa")
+    );
+    assert_eq!(
+        builds.resolve(&nightlies, "2024-07-02"),
+        Resolution::Unrecognized
+    );
+
+    let dates = Resolvers::accepting(&[ResolverClass::Date]).with(ShaResolver);
+    assert_eq!(
+        dates
+            .resolve(&nightlies, "2024-07-02")
+            .nightly()
+            .map(|n| n.sha.as_str()),
+        Some("bbbbbbbb")
+    );
+    assert_eq!(
+        dates
+            .resolve(&nightlies, "aaaaaaaa")
+            .nightly()
+            .map(|n| n.sha.as_str()),
+        Some("aaaaaaaa")
+    );
+    assert_eq!(dates.resolve(&nightlies, "@0"), Resolution::Unrecognized);
+}
+
+/// Recognizes anything as the latest nightly
+struct Anything;
+
+impl Resolver for Anything {
+    fn class(&self) -> ResolverClass {
+        ResolverClass::Index
+    }
+
+    fn resolve<'a>(&self, nightlies: &'a [Nightly], _identifier: &str) -> Resolution<'a> {
+        nightlies.last().into()
+    }
+}
+
+#[test]
+fn chains_stop_at_the_first_resolver_recognizing_the_identifier() {
+    let nightlies = nightlies(&["aaaaaaaa", "bbbbbbbb"]);
+    let chain = Resolvers::accepting(&[ResolverClass::Sha]).with(Anything);
+    // A sha that isn't a build isn't passed on
+    assert_eq!(chain.resolve(&nightlies, "deadbeef"), Resolution::Missing);
+    assert_eq!(
+        chain
+            .resolve(&nightlies, "@5")
+            .nightly()
+            .map(|n| n.sha.as_str()),
+        Some("bbbbbbbb")
+    );
+}
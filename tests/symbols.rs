@@ -0,0 +1,29 @@
+use chrono::{TimeZone, Utc};
+use nightlies::{
+    nightly::{tags_to_nightlies, Tag},
+    symbols::render_symbol_url,
+};
+
+#[test]
+fn symbol_urls_fill_in_placeholders() {
+    let tags = [Tag {
+        name: String::from("nightly-main-0123abcd-py3"),
+        last_pushed: Utc.with_ymd_and_hms(2024, 7, 4, 12, 0, 0).unwrap(),
+        digest: String::from("sha256:0"),
+        last_pulled: None,
+        pull_count: None,
+    }];
+    let nightly = &tags_to_nightlies(&tags)[0];
+
+    assert_eq!(
+        render_symbol_url("https://s3/{sha}/{tag}.dbg", nightly, None).unwrap(),
+        "https://s3/0123abcd/nightly-main-0123abcd-py3.dbg"
+    );
+    assert_eq!(
+        render_symbol_url("https://ci/{pipeline}/symbols", nightly, Some("42")).unwrap(),
+        "https://ci/42/symbols"
+    );
+    assert!(render_symbol_url("https://ci/{pipeline}", nightly, None).is_err());
+    assert!(render_symbol_url("https://ci/{branch}", nightly, None).is_err());
+    assert!(render_symbol_url("https://ci/{sha", nightly, None).is_err());
+}